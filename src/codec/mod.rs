@@ -0,0 +1,5 @@
+mod ordered_key;
+mod serde_value;
+
+pub use ordered_key::OrderedKey;
+pub use serde_value::{Bincode, Cbor, Json, SerdeFormat, SerdeValue};
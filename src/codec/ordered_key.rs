@@ -0,0 +1,787 @@
+use crate::Key;
+use serde::{
+    de::{
+        self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess,
+        SeqAccess, VariantAccess, Visitor,
+    },
+    ser::{self, Serialize},
+};
+use std::{error::Error, fmt};
+
+// A key encoded so that the byte order of encodings is the order of the
+// values: integers are big endian with the sign bit flipped, strings and
+// bytes are escaped and terminated, options, sequences and maps are tagged,
+// tuples and structs are their fields one after another.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OrderedKey<T>(pub T);
+
+impl<T> Key for OrderedKey<T>
+where
+    T: Serialize + DeserializeOwned + Clone + Ord + Send + Sync,
+{
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        self.0.serialize(&mut KeySerializer { buf })?;
+        Ok(())
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let mut deserializer = KeyDeserializer { input: bytes };
+        let value = T::deserialize(&mut deserializer)?;
+        if !deserializer.input.is_empty() {
+            let reason = format!("{} bytes left after the key", deserializer.input.len());
+            return Err(Box::new(CodecError(reason)));
+        }
+        Ok(OrderedKey(value))
+    }
+}
+
+#[derive(Debug)]
+struct CodecError(String);
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for CodecError {}
+
+impl ser::Error for CodecError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CodecError(msg.to_string())
+    }
+}
+
+impl de::Error for CodecError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        CodecError(msg.to_string())
+    }
+}
+
+const END: u8 = 0;
+const MORE: u8 = 1;
+
+// A zero byte is escaped as 0x00 0xff and the bytes end with 0x00 0x00, so a
+// prefix sorts before any longer bytes.
+fn append_escaped(buf: &mut Vec<u8>, bytes: &[u8]) {
+    for byte in bytes {
+        buf.push(*byte);
+        if *byte == 0 {
+            buf.push(0xff);
+        }
+    }
+    buf.extend([0, 0]);
+}
+
+fn f32_to_ordered(v: f32) -> u32 {
+    let bits = v.to_bits();
+    if bits >> 31 == 1 {
+        !bits
+    } else {
+        bits ^ (1 << 31)
+    }
+}
+
+fn f64_to_ordered(v: f64) -> u64 {
+    let bits = v.to_bits();
+    if bits >> 63 == 1 {
+        !bits
+    } else {
+        bits ^ (1 << 63)
+    }
+}
+
+struct KeySerializer<'a> {
+    buf: &'a mut Vec<u8>,
+}
+
+impl<'a> ser::Serializer for &mut KeySerializer<'a> {
+    type Ok = ();
+    type Error = CodecError;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    fn serialize_bool(self, v: bool) -> Result<(), CodecError> {
+        self.buf.push(v as u8);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), CodecError> {
+        self.buf.push((v as u8) ^ (1 << 7));
+        Ok(())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), CodecError> {
+        self.buf.extend(((v as u16) ^ (1 << 15)).to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), CodecError> {
+        self.buf.extend(((v as u32) ^ (1 << 31)).to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), CodecError> {
+        self.buf.extend(((v as u64) ^ (1 << 63)).to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), CodecError> {
+        self.buf.extend(((v as u128) ^ (1 << 127)).to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), CodecError> {
+        self.buf.push(v);
+        Ok(())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), CodecError> {
+        self.buf.extend(v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), CodecError> {
+        self.buf.extend(v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), CodecError> {
+        self.buf.extend(v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), CodecError> {
+        self.buf.extend(v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), CodecError> {
+        self.buf.extend(f32_to_ordered(v).to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), CodecError> {
+        self.buf.extend(f64_to_ordered(v).to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), CodecError> {
+        self.buf.extend((v as u32).to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), CodecError> {
+        append_escaped(self.buf, v.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), CodecError> {
+        append_escaped(self.buf, v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), CodecError> {
+        self.buf.push(END);
+        Ok(())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), CodecError> {
+        self.buf.push(MORE);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), CodecError> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), CodecError> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), CodecError> {
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), CodecError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<(), CodecError> {
+        self.buf.extend(variant_index.to_be_bytes());
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self, CodecError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self, CodecError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, CodecError> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, CodecError> {
+        self.buf.extend(variant_index.to_be_bytes());
+        Ok(self)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self, CodecError> {
+        Ok(self)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, CodecError> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self, CodecError> {
+        self.buf.extend(variant_index.to_be_bytes());
+        Ok(self)
+    }
+}
+
+impl<'a> ser::SerializeSeq for &mut KeySerializer<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CodecError> {
+        self.buf.push(MORE);
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        self.buf.push(END);
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTuple for &mut KeySerializer<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CodecError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for &mut KeySerializer<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CodecError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTupleVariant for &mut KeySerializer<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CodecError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeMap for &mut KeySerializer<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), CodecError> {
+        self.buf.push(MORE);
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), CodecError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        self.buf.push(END);
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStruct for &mut KeySerializer<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), CodecError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for &mut KeySerializer<'a> {
+    type Ok = ();
+    type Error = CodecError;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<(), CodecError> {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<(), CodecError> {
+        Ok(())
+    }
+}
+
+struct KeyDeserializer<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> KeyDeserializer<'de> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], CodecError> {
+        if self.input.len() < N {
+            return Err(CodecError("the key is truncated".to_owned()));
+        }
+        let (bytes, rest) = self.input.split_at(N);
+        self.input = rest;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn take_tag(&mut self) -> Result<bool, CodecError> {
+        match self.take::<1>()?[0] {
+            END => Ok(false),
+            MORE => Ok(true),
+            tag => Err(CodecError(format!("unknown tag {}", tag))),
+        }
+    }
+
+    fn take_escaped(&mut self) -> Result<Vec<u8>, CodecError> {
+        let mut bytes = vec![];
+        loop {
+            let [byte] = self.take::<1>()?;
+            if byte != 0 {
+                bytes.push(byte);
+                continue;
+            }
+            match self.take::<1>()?[0] {
+                0 => return Ok(bytes),
+                0xff => bytes.push(0),
+                byte => return Err(CodecError(format!("unknown escape {}", byte))),
+            }
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &mut KeyDeserializer<'de> {
+    type Error = CodecError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, CodecError> {
+        Err(CodecError(
+            "ordered keys can only be decoded into a known type".to_owned(),
+        ))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        match self.take::<1>()?[0] {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            byte => Err(CodecError(format!("invalid bool {}", byte))),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let [byte] = self.take::<1>()?;
+        visitor.visit_i8((byte ^ (1 << 7)) as i8)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let v = u16::from_be_bytes(self.take()?);
+        visitor.visit_i16((v ^ (1 << 15)) as i16)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let v = u32::from_be_bytes(self.take()?);
+        visitor.visit_i32((v ^ (1 << 31)) as i32)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let v = u64::from_be_bytes(self.take()?);
+        visitor.visit_i64((v ^ (1 << 63)) as i64)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let v = u128::from_be_bytes(self.take()?);
+        visitor.visit_i128((v ^ (1 << 127)) as i128)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let [byte] = self.take::<1>()?;
+        visitor.visit_u8(byte)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_u16(u16::from_be_bytes(self.take()?))
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_u32(u32::from_be_bytes(self.take()?))
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_u64(u64::from_be_bytes(self.take()?))
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_u128(u128::from_be_bytes(self.take()?))
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let bits = u32::from_be_bytes(self.take()?);
+        let bits = if bits >> 31 == 1 {
+            bits ^ (1 << 31)
+        } else {
+            !bits
+        };
+        visitor.visit_f32(f32::from_bits(bits))
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let bits = u64::from_be_bytes(self.take()?);
+        let bits = if bits >> 63 == 1 {
+            bits ^ (1 << 63)
+        } else {
+            !bits
+        };
+        visitor.visit_f64(f64::from_bits(bits))
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        let v = u32::from_be_bytes(self.take()?);
+        match char::from_u32(v) {
+            Some(v) => visitor.visit_char(v),
+            None => Err(CodecError(format!("invalid char {}", v))),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        match String::from_utf8(self.take_escaped()?) {
+            Ok(v) => visitor.visit_string(v),
+            Err(err) => Err(CodecError(err.to_string())),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_byte_buf(self.take_escaped()?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        if self.take_tag()? {
+            visitor.visit_some(self)
+        } else {
+            visitor.visit_none()
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_seq(Tagged { de: self })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        visitor.visit_seq(Counted { de: self, len })
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        visitor.visit_map(Tagged { de: self })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        self.deserialize_u32(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CodecError> {
+        self.deserialize_any(visitor)
+    }
+}
+
+// Elements of sequences and maps, each one follows a tag.
+struct Tagged<'a, 'de> {
+    de: &'a mut KeyDeserializer<'de>,
+}
+
+impl<'de> SeqAccess<'de> for Tagged<'_, 'de> {
+    type Error = CodecError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, CodecError> {
+        if !self.de.take_tag()? {
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+}
+
+impl<'de> MapAccess<'de> for Tagged<'_, 'de> {
+    type Error = CodecError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, CodecError> {
+        if !self.de.take_tag()? {
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, CodecError> {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+// Fields of tuples and structs.
+struct Counted<'a, 'de> {
+    de: &'a mut KeyDeserializer<'de>,
+    len: usize,
+}
+
+impl<'de> SeqAccess<'de> for Counted<'_, 'de> {
+    type Error = CodecError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, CodecError> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+impl<'de> EnumAccess<'de> for &mut KeyDeserializer<'de> {
+    type Error = CodecError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), CodecError> {
+        let variant_index = u32::from_be_bytes(self.take()?);
+        let deserializer: de::value::U32Deserializer<CodecError> =
+            variant_index.into_deserializer();
+        let value = seed.deserialize(deserializer)?;
+        Ok((value, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &mut KeyDeserializer<'de> {
+    type Error = CodecError;
+
+    fn unit_variant(self) -> Result<(), CodecError> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, CodecError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, CodecError> {
+        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OrderedKey;
+    use crate::Key;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+    enum Kind {
+        File { size: u32 },
+        Dir(Option<String>),
+        Link,
+    }
+
+    fn assert_ordered<T>(mut values: Vec<T>)
+    where
+        T: Serialize + serde::de::DeserializeOwned + Clone + Ord + Send + Sync + std::fmt::Debug,
+    {
+        values.sort();
+        let mut prev: Option<Vec<u8>> = None;
+        for value in values {
+            let key = OrderedKey(value.clone());
+            let bytes = key.to_bytes().unwrap();
+            assert_eq!(value, OrderedKey::<T>::decode(&bytes).unwrap().0);
+            if let Some(prev) = prev {
+                assert!(prev < bytes, "{:?}", value);
+            }
+            prev = Some(bytes);
+        }
+    }
+
+    #[test]
+    fn test_integers() {
+        assert_ordered(vec![i64::MIN, -300, -1, 0, 1, 255, 256, i64::MAX]);
+        assert_ordered(vec![i8::MIN, -1, 0, 1, i8::MAX]);
+        assert_ordered(vec![0u64, 1, 255, 256, u64::MAX]);
+    }
+
+    #[test]
+    fn test_strings_and_bytes() {
+        let strings = vec!["", "\0", "\0\0", "a", "a\0", "a\0b", "ab", "b"];
+        assert_ordered(strings.into_iter().map(String::from).collect());
+        assert_ordered(vec![vec![], vec![0u8], vec![0, 0], vec![0, 1], vec![1]]);
+    }
+
+    #[test]
+    fn test_tuples_and_options() {
+        assert_ordered(vec![
+            (1u64, -5i64, "b".to_owned()),
+            (1, -5, "ba".to_owned()),
+            (1, 3, "a".to_owned()),
+            (2, i64::MIN, "".to_owned()),
+        ]);
+        assert_ordered(vec![None, Some(-1i32), Some(0), Some(7)]);
+        assert_ordered(vec![
+            Kind::File { size: 3 },
+            Kind::File { size: 10 },
+            Kind::Dir(None),
+            Kind::Dir(Some("a".to_owned())),
+            Kind::Link,
+        ]);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::Value;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{error::Error, marker::PhantomData, ops::Deref};
+
+pub trait SerdeFormat {
+    fn serialize<T: Serialize>(value: &T, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>>;
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Box<dyn Error>>;
+}
+
+pub struct Bincode;
+
+impl SerdeFormat for Bincode {
+    fn serialize<T: Serialize>(value: &T, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        bincode::serialize_into(buf, value)?;
+        Ok(())
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Box<dyn Error>> {
+        Ok(bincode::deserialize(bytes)?)
+    }
+}
+
+pub struct Json;
+
+impl SerdeFormat for Json {
+    fn serialize<T: Serialize>(value: &T, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        serde_json::to_writer(buf, value)?;
+        Ok(())
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Box<dyn Error>> {
+        Ok(serde_json::from_slice(bytes)?)
+    }
+}
+
+pub struct Cbor;
+
+impl SerdeFormat for Cbor {
+    fn serialize<T: Serialize>(value: &T, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        ciborium::into_writer(value, buf)?;
+        Ok(())
+    }
+
+    fn deserialize<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, Box<dyn Error>> {
+        Ok(ciborium::from_reader(bytes)?)
+    }
+}
+
+// A value stored in the given serde format.
+pub struct SerdeValue<T, F: SerdeFormat = Bincode> {
+    value: T,
+    format: PhantomData<fn() -> F>,
+}
+
+impl<T, F: SerdeFormat> SerdeValue<T, F> {
+    pub fn new(value: T) -> SerdeValue<T, F> {
+        SerdeValue {
+            value,
+            format: PhantomData,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, F: SerdeFormat> From<T> for SerdeValue<T, F> {
+    fn from(value: T) -> Self {
+        SerdeValue::new(value)
+    }
+}
+
+impl<T, F: SerdeFormat> Deref for SerdeValue<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Clone, F: SerdeFormat> Clone for SerdeValue<T, F> {
+    fn clone(&self) -> Self {
+        SerdeValue::new(self.value.clone())
+    }
+}
+
+impl<T, F> Value for SerdeValue<T, F>
+where
+    T: Serialize + DeserializeOwned + Clone + Send + Sync,
+    F: SerdeFormat,
+{
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        F::serialize(&self.value, buf)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        Ok(SerdeValue::new(F::deserialize(bytes)?))
+    }
+}
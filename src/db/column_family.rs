@@ -0,0 +1,27 @@
+pub(crate) const DEFAULT_CF_ID: u32 = 0;
+pub(crate) const DEFAULT_CF_NAME: &str = "default";
+
+// Names a column family of the DB, a handle of a dropped column family is
+// rejected by reads and writes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnFamilyHandle {
+    id: u32,
+    name: String,
+}
+
+impl ColumnFamilyHandle {
+    pub(crate) fn new(id: u32, name: &str) -> ColumnFamilyHandle {
+        ColumnFamilyHandle {
+            id,
+            name: name.to_owned(),
+        }
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{
+    model::{collect_versions, max_covering_seq, merge_versions, Entry, RangeTombstone},
+    sst, EikvResult, Key, MergeOperator, Value,
+};
+use std::{cmp::Reverse, sync::Arc};
+
+pub struct DBIterator<K: Key, V: Value> {
+    mem_entries: Vec<Entry<K, V>>,
+    mem_entry_index: usize,
+    sst_iterators: Vec<sst::Iterator<K, V>>,
+    range_tombstones: Vec<RangeTombstone<K>>,
+    merge_operator: Option<Arc<dyn MergeOperator>>,
+}
+
+impl<K: Key, V: Value> DBIterator<K, V> {
+    pub(super) fn new(
+        mem_entries: Vec<Entry<K, V>>,
+        sst_iterators: Vec<sst::Iterator<K, V>>,
+        range_tombstones: Vec<RangeTombstone<K>>,
+        merge_operator: Option<Arc<dyn MergeOperator>>,
+    ) -> DBIterator<K, V> {
+        DBIterator {
+            mem_entries,
+            mem_entry_index: 0,
+            sst_iterators,
+            range_tombstones,
+            merge_operator,
+        }
+    }
+
+    fn min_key(&self) -> Option<K> {
+        let mut min_key = self.mem_entries.get(self.mem_entry_index).map(|e| &e.key);
+        for iterator in &self.sst_iterators {
+            let key = match iterator.entry() {
+                Some(entry) => &entry.key,
+                None => continue,
+            };
+            min_key = match min_key {
+                Some(min_key) if min_key <= key => Some(min_key),
+                _ => Some(key),
+            };
+        }
+        min_key.cloned()
+    }
+
+    fn read_versions(&mut self, key: &K) -> EikvResult<Vec<Entry<K, V>>> {
+        let mut versions = vec![];
+        while let Some(entry) = self.mem_entries.get(self.mem_entry_index) {
+            if entry.key != *key {
+                break;
+            }
+            versions.push(entry.clone());
+            self.mem_entry_index += 1;
+        }
+
+        for iterator in self.sst_iterators.iter_mut() {
+            while let Some(entry) = iterator.entry() {
+                if entry.key != *key {
+                    break;
+                }
+                versions.push(entry.clone());
+                iterator.next()?;
+            }
+        }
+
+        versions.sort_unstable_by_key(|entry| Reverse(entry.seq));
+        // The SSTs a flush installed after the memtable snapshot repeat its
+        // versions.
+        versions.dedup_by_key(|entry| entry.seq);
+        Ok(versions)
+    }
+
+    fn next_entry(&mut self) -> EikvResult<Option<(K, V)>> {
+        loop {
+            let key = match self.min_key() {
+                Some(key) => key,
+                None => return Ok(None),
+            };
+            let versions = self.read_versions(&key)?;
+            let tombstone_seq = max_covering_seq(&self.range_tombstones, &key, u64::MAX);
+            let mut chain = vec![];
+            collect_versions(&key, &versions, tombstone_seq, &mut chain);
+            if let Some(value) = merge_versions(self.merge_operator.as_ref(), &key, chain)? {
+                return Ok(Some((key, value)));
+            }
+        }
+    }
+}
+
+impl<K: Key, V: Value> Iterator for DBIterator<K, V> {
+    type Item = EikvResult<(K, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
@@ -0,0 +1,104 @@
+use crate::{EikvError, EikvResult, Key};
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+struct Stripe<K: Key> {
+    owners: Mutex<BTreeMap<K, u64>>,
+    released: Condvar,
+}
+
+pub(super) struct LockManager<K: Key> {
+    stripes: Vec<Stripe<K>>,
+    // Edges from a waiting transaction to the transaction holding its key.
+    wait_for: Mutex<HashMap<u64, u64>>,
+}
+
+impl<K: Key> LockManager<K> {
+    pub(super) fn new(stripe_count: usize) -> LockManager<K> {
+        let mut stripes = Vec::with_capacity(stripe_count);
+        for _ in 0..stripe_count.max(1) {
+            stripes.push(Stripe {
+                owners: Mutex::new(BTreeMap::new()),
+                released: Condvar::new(),
+            });
+        }
+        LockManager {
+            stripes,
+            wait_for: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn stripe(&self, key: &K) -> EikvResult<&Stripe<K>> {
+        let mut hasher = DefaultHasher::new();
+        key.to_bytes()?.hash(&mut hasher);
+        let index = hasher.finish() as usize % self.stripes.len();
+        Ok(&self.stripes[index])
+    }
+
+    pub(super) fn lock(&self, txn_id: u64, key: &K, timeout: Duration) -> EikvResult<()> {
+        let stripe = self.stripe(key)?;
+        let deadline = Instant::now() + timeout;
+        let mut owners = stripe.owners.lock().unwrap();
+        loop {
+            let owner = match owners.get(key) {
+                None => {
+                    owners.insert(key.clone(), txn_id);
+                    self.wait_for.lock().unwrap().remove(&txn_id);
+                    return Ok(());
+                }
+                Some(owner) if *owner == txn_id => return Ok(()),
+                Some(owner) => *owner,
+            };
+
+            if self.wait(txn_id, owner) {
+                self.wait_for.lock().unwrap().remove(&txn_id);
+                let reason = format!("transaction {} waits for itself through {}", txn_id, owner);
+                return Err(EikvError::Deadlock(reason));
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                self.wait_for.lock().unwrap().remove(&txn_id);
+                let reason = format!("transaction {} waited {:?} for a key", txn_id, timeout);
+                return Err(EikvError::LockTimeout(reason));
+            }
+            owners = stripe
+                .released
+                .wait_timeout(owners, deadline - now)
+                .unwrap()
+                .0;
+        }
+    }
+
+    // Records that the transaction waits for the owner, returns whether the
+    // owner already waits for the transaction.
+    fn wait(&self, txn_id: u64, owner: u64) -> bool {
+        let mut wait_for = self.wait_for.lock().unwrap();
+        wait_for.insert(txn_id, owner);
+        let mut next = owner;
+        for _ in 0..wait_for.len() {
+            next = match wait_for.get(&next) {
+                Some(next) => *next,
+                None => return false,
+            };
+            if next == txn_id {
+                return true;
+            }
+        }
+        false
+    }
+
+    pub(super) fn unlock(&self, txn_id: u64, key: &K) -> EikvResult<()> {
+        let stripe = self.stripe(key)?;
+        let mut owners = stripe.owners.lock().unwrap();
+        if owners.get(key) == Some(&txn_id) {
+            owners.remove(key);
+            stripe.released.notify_all();
+        }
+        Ok(())
+    }
+}
@@ -1,75 +1,228 @@
+mod column_family;
+mod iterator;
+mod lock_manager;
+mod optimistic_transaction;
 pub(crate) mod path;
+mod raw_db;
+mod stats;
+mod transaction_db;
 
+pub(crate) use self::column_family::{DEFAULT_CF_ID, DEFAULT_CF_NAME};
 use self::path::{
-    lock_file_path, manifest_dir_path, sst_dir_path, sst_level_dir_path, sst_major_tmp_path,
+    current_tmp_path, lock_file_path, manifest_dir_path, sst_dir_path, sst_major_tmp_path,
     sst_minor_tmp_path, sst_path, sst_tmp_dir_path, wal_dir_path, wal_path,
 };
+pub use self::{
+    column_family::ColumnFamilyHandle,
+    iterator::DBIterator,
+    optimistic_transaction::OptimisticTransaction,
+    raw_db::RawDB,
+    stats::DBStats,
+    transaction_db::{Transaction, TransactionDB, TransactionDBOptions},
+};
 use crate::{
     limit::{LEVEL_MAX, LEVEL_MIN},
-    mem_db::{MemDB, Table},
-    model::{Manifest, SstMeta},
-    sst::{self, Iterator, MergeResult, Merger},
+    mem_db::{FlushJob, MemDB, MemTableFactory, ReadSeq, Table, Turn, WriteOp},
+    model::{collect_versions, max_covering_seq, merge_versions, Entry, Manifest, Op, SstMeta},
+    sst::{self, IOPriority, Iterator, KeyRange, MergeResult, Merger},
     util::time::unix_now,
-    wal::{Reader, Writer},
-    Compressor, EikvResult, FilterFactory, Key, Value, WriteBatch,
+    wal::{Marker, Precondition, Reader, Writer},
+    BytewiseComparator, CompactionFilter, CompactionFilterContext, Comparator, Compressor,
+    EikvError, EikvResult, FilterFactory, Key, MergeOperator, RateLimiter, Value, WriteBatch,
+    WriteBufferManager,
 };
 use fs2::FileExt;
 use std::{
-    cmp::max,
-    fs::{create_dir, remove_file, rename, File},
+    cmp::{max, min, Reverse},
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs::{create_dir, read_dir, remove_file, rename, File},
     path::Path,
-    sync::{atomic::AtomicU64, Arc, Condvar, Mutex},
-    thread,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Condvar, Mutex, RwLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
 #[derive(Clone)]
 pub struct DBOptions {
     pub block_size: usize,
+    pub compaction_filter: Option<Arc<dyn CompactionFilter>>,
+    pub comparator: Arc<dyn Comparator>,
     pub compressor: Option<Arc<dyn Compressor>>,
     pub create_if_missing: bool,
+    // How often the files nobody refers to are looked for and removed, they
+    // are also removed on open.
+    pub delete_obsolete_files_period: Duration,
     pub filter_factory: Option<Arc<dyn FilterFactory>>,
+    // Writes are delayed from the slowdown trigger of level 1 SSTs of a column
+    // family on, and stopped from the stop trigger on.
+    pub level1_slowdown_writes_trigger: usize,
+    pub level1_stop_writes_trigger: usize,
+    // The threads of the compaction and the flush pools, compaction threads
+    // flush queued memtables before going on with their merges.
+    pub max_background_compactions: usize,
+    pub max_background_flushes: usize,
+    // A merge is split into at most this many key ranges merged in parallel.
+    pub max_subcompactions: usize,
+    // The frozen memtables queued for flush before writes stop, writes are
+    // delayed one memtable earlier if more than two can be queued.
+    pub max_write_buffer_number: usize,
+    pub memtable_factory: MemTableFactory,
+    pub merge_operator: Option<Arc<dyn MergeOperator>>,
+    // Caps the I/O of flushes and compactions, flushes go first.
+    pub rate_limiter: Option<Arc<RateLimiter>>,
     pub restart_interval: usize,
     pub wal_size_limit: u64,
+    pub write_buffer_manager: Option<Arc<WriteBufferManager>>,
+    pub write_buffer_size: usize,
 }
 
 impl Default for DBOptions {
     fn default() -> Self {
         Self {
             block_size: 4096,
+            compaction_filter: None,
+            comparator: Arc::new(BytewiseComparator),
             compressor: None,
             create_if_missing: true,
+            delete_obsolete_files_period: Duration::from_secs(600),
             filter_factory: None,
+            level1_slowdown_writes_trigger: 12,
+            level1_stop_writes_trigger: 20,
+            max_background_compactions: 1,
+            max_background_flushes: 1,
+            max_subcompactions: 1,
+            max_write_buffer_number: 4,
+            memtable_factory: MemTableFactory::BTree,
+            merge_operator: None,
+            rate_limiter: None,
             restart_interval: 16,
             wal_size_limit: 2 * 1024 * 1024,
+            write_buffer_manager: None,
+            write_buffer_size: 4 * 1024 * 1024,
         }
     }
 }
 
+// The delay of a write group while flushes or compactions fall behind.
+const WRITE_SLOWDOWN_DELAY: Duration = Duration::from_millis(1);
+
+// Write batches of prepared transactions by their xids.
+type PreparedBatches<K, V> = BTreeMap<String, WriteBatch<K, V>>;
+
+// Options of the column families by their ids.
+type CFOptions = Arc<RwLock<BTreeMap<u32, DBOptions>>>;
+
+// Memtables of the column families recovered from a WAL.
+type CFTables<K, V> = BTreeMap<u32, Table<K, V>>;
+
 pub struct DB<K: Key, V: Value> {
     _lock_file: File,
     db_path: String,
     manifest: Arc<Mutex<Manifest<K, V>>>,
     mem_db: Arc<MemDB<K, V>>,
-    request_close: Arc<Mutex<bool>>,
-    background_thread_exited: Arc<Condvar>,
+    options: DBOptions,
+    cf_options: CFOptions,
+    prepared: Mutex<PreparedBatches<K, V>>,
+    stats: Mutex<DBStats>,
+    background: Arc<Background<K, V>>,
+    background_threads: Vec<JoinHandle<()>>,
 }
 
 impl<K: Key, V: Value> Drop for DB<K, V> {
     fn drop(&mut self) {
-        let mut request_close = self.request_close.lock().unwrap();
-        *request_close = true;
-        while *request_close {
-            request_close = self.background_thread_exited.wait(request_close).unwrap();
+        self.background.close();
+        for background_thread in self.background_threads.drain(..) {
+            let _ = background_thread.join();
         }
     }
 }
 
 impl<K: Key + 'static, V: Value + 'static> DB<K, V> {
     pub fn new(path: &str, options: DBOptions) -> EikvResult<DB<K, V>> {
-        if options.create_if_missing && !Path::new(path).try_exists()? {
-            create_db(path, options)
+        DB::new_with_cfs(path, options, HashMap::new())
+    }
+
+    // Opens the column families with the options given by their names, the
+    // others are opened with the options of the DB. Missing column families
+    // are created.
+    pub fn new_with_cfs(
+        path: &str,
+        options: DBOptions,
+        cf_options: HashMap<String, DBOptions>,
+    ) -> EikvResult<DB<K, V>> {
+        for cf_opts in cf_options.values() {
+            check_comparator(&options, cf_opts)?;
+        }
+        let db = if options.create_if_missing && !Path::new(path).try_exists()? {
+            create_db(path, options)?
         } else {
-            open_db(path, options)
+            open_db(path, options, &cf_options)?
+        };
+        for (name, cf_opts) in cf_options {
+            if db.cf_handle(&name).is_none() {
+                db.create_cf(&name, cf_opts)?;
+            }
+        }
+        Ok(db)
+    }
+
+    pub fn default_cf(&self) -> ColumnFamilyHandle {
+        ColumnFamilyHandle::new(DEFAULT_CF_ID, DEFAULT_CF_NAME)
+    }
+
+    pub fn cf_handle(&self, name: &str) -> Option<ColumnFamilyHandle> {
+        let manifest = self.manifest.lock().unwrap();
+        manifest
+            .cf_id(name)
+            .map(|cf_id| ColumnFamilyHandle::new(cf_id, name))
+    }
+
+    pub fn create_cf(&self, name: &str, options: DBOptions) -> EikvResult<ColumnFamilyHandle> {
+        check_comparator(&self.options, &options)?;
+        let mut manifest = self.manifest.lock().unwrap();
+        if manifest.cf_id(name).is_some() {
+            let reason = format!("column family {} already exists", name);
+            return Err(EikvError::ColumnFamilyError(reason));
+        }
+        let cf_id = manifest.add_cf(name);
+        self.mem_db.add_cf(cf_id, options.clone());
+        self.cf_options.write().unwrap().insert(cf_id, options);
+        manifest.dump(&self.db_path)?;
+        Ok(ColumnFamilyHandle::new(cf_id, name))
+    }
+
+    // Entries of the dropped column family left in the WAL are skipped when
+    // the DB is opened again.
+    pub fn drop_cf(&self, cf: &ColumnFamilyHandle) -> EikvResult<()> {
+        if cf.id() == DEFAULT_CF_ID {
+            let reason = "the default column family can't be dropped".to_owned();
+            return Err(EikvError::ColumnFamilyError(reason));
+        }
+        let mut manifest = self.manifest.lock().unwrap();
+        if !manifest.has_cf(cf.id()) {
+            let reason = format!("column family {} doesn't exist", cf.name());
+            return Err(EikvError::ColumnFamilyError(reason));
+        }
+        let ssts = manifest.remove_cf(cf.id());
+        manifest.dump(&self.db_path)?;
+        self.mem_db.remove_cf(cf.id());
+        self.cf_options.write().unwrap().remove(&cf.id());
+        for (_, file_seq) in ssts {
+            remove_file(sst_path(&self.db_path, file_seq)?)?;
+        }
+        Ok(())
+    }
+
+    fn cf_options(&self, cf_id: u32) -> EikvResult<DBOptions> {
+        match self.cf_options.read().unwrap().get(&cf_id) {
+            Some(options) => Ok(options.clone()),
+            None => {
+                let reason = format!("column family {} doesn't exist", cf_id);
+                Err(EikvError::ColumnFamilyError(reason))
+            }
         }
     }
 
@@ -77,13 +230,237 @@ impl<K: Key + 'static, V: Value + 'static> DB<K, V> {
         if write_batch.is_empty() {
             return Ok(());
         }
-        if self.mem_db.write(write_batch)? {
+        if !self.write_op(WriteOp::new(write_batch))? {
+            let reason = "a precondition of the write batch doesn't hold".to_owned();
+            return Err(EikvError::Conflict(reason));
+        }
+        Ok(())
+    }
+
+    // Returns false if the preconditions of the op don't hold, the write
+    // batch of the op is dropped then.
+    pub(crate) fn write_op(&self, write_op: WriteOp<K, V>) -> EikvResult<bool> {
+        let write_ops = match self.mem_db.line_up(write_op) {
+            Turn::Lead(write_ops) => write_ops,
+            Turn::Done(result) => return result,
+        };
+        let results = self.write_group(write_ops);
+        self.mem_db.write_finished(results)
+    }
+
+    fn write_group(&self, write_ops: Vec<WriteOp<K, V>>) -> EikvResult<Vec<EikvResult<bool>>> {
+        self.stall_writes()?;
+        let mut results = Vec::with_capacity(write_ops.len());
+        let mut wal_buf = vec![];
+        let mut prepares = vec![];
+        {
+            let mut prepared = self.prepared.lock().unwrap();
+            for write_op in write_ops {
+                match self.check_preconditions(&write_op.preconditions) {
+                    Ok(true) => {}
+                    result => {
+                        results.push(result);
+                        continue;
+                    }
+                }
+
+                let write_batch = write_op.write_batch;
+                if let Some(xid) = write_batch.prepared_xid() {
+                    if prepared.contains_key(xid) {
+                        results.push(Ok(false));
+                        continue;
+                    }
+                    prepared.insert(xid.to_owned(), write_batch.clone());
+                    prepares.push(write_batch);
+                } else {
+                    for marker in write_batch.markers() {
+                        if let Marker::Commit(xid) | Marker::Rollback(xid) = marker {
+                            prepared.remove(xid);
+                        }
+                    }
+                    if let Err(err) = self.mem_db.apply(write_batch, &mut wal_buf) {
+                        results.push(Err(err));
+                        continue;
+                    }
+                }
+                results.push(Ok(true));
+            }
+        }
+
+        // Prepared batches are logged as records of their own, they aren't
+        // applied until committed.
+        for write_batch in prepares {
+            write_batch.encode(&mut wal_buf)?;
+        }
+        if wal_buf.is_empty() {
+            return Ok(results);
+        }
+        if self.mem_db.append_wal(&wal_buf)? {
             let wal = new_wal(&self.db_path, self.manifest.clone())?;
             self.mem_db.freeze(wal);
+            // The frozen WAL is removed after the flush, prepared transactions
+            // have to be logged again to survive it.
+            let mut wal_buf = vec![];
+            for write_batch in self.prepared.lock().unwrap().values() {
+                write_batch.encode(&mut wal_buf)?;
+            }
+            self.mem_db.append_wal(&wal_buf)?;
             self.manifest.lock().unwrap().dump(&self.db_path)?;
         }
-        self.mem_db.write_finished();
-        Ok(())
+        Ok(results)
+    }
+
+    // Delays the write group while flushes or level 1 compactions fall
+    // behind, and stops it until they catch up. Fails once they have failed.
+    fn stall_writes(&self) -> EikvResult<()> {
+        let max_immut_count = self.options.max_write_buffer_number;
+        let mut slowed_down = false;
+        loop {
+            self.mem_db.background_error()?;
+            let immut_count = self.mem_db.immut_count();
+            let level1_sst_count = {
+                let manifest = self.manifest.lock().unwrap();
+                manifest
+                    .cf_ids()
+                    .into_iter()
+                    .map(|cf_id| manifest.level_sst_count(cf_id, LEVEL_MIN))
+                    .max()
+                    .unwrap_or(0)
+            };
+
+            if immut_count >= max_immut_count
+                || level1_sst_count >= self.options.level1_stop_writes_trigger
+            {
+                let start = Instant::now();
+                self.mem_db
+                    .wait_background_progress(Duration::from_millis(100));
+                let mut stats = self.stats.lock().unwrap();
+                stats.write_stops += 1;
+                stats.write_stop_duration += start.elapsed();
+                continue;
+            }
+
+            if !slowed_down
+                && ((max_immut_count > 2 && immut_count + 1 >= max_immut_count)
+                    || level1_sst_count >= self.options.level1_slowdown_writes_trigger)
+            {
+                thread::sleep(WRITE_SLOWDOWN_DELAY);
+                let mut stats = self.stats.lock().unwrap();
+                stats.write_slowdowns += 1;
+                stats.write_slowdown_duration += WRITE_SLOWDOWN_DELAY;
+                slowed_down = true;
+                continue;
+            }
+            return Ok(());
+        }
+    }
+
+    pub fn stats(&self) -> DBStats {
+        *self.stats.lock().unwrap()
+    }
+
+    // Returns once every flush and compaction is stopped, merges are kept and
+    // go on where they stopped when the work continues. Pauses nest.
+    pub fn pause_background_work(&self) {
+        self.background.pause();
+    }
+
+    pub fn continue_background_work(&self) {
+        self.background.resume();
+    }
+
+    // Merges the SSTs of the column family down level by level on the calling
+    // thread.
+    pub fn compact(&self) -> EikvResult<()> {
+        self.compact_cf(&self.default_cf())
+    }
+
+    pub fn compact_cf(&self, cf: &ColumnFamilyHandle) -> EikvResult<()> {
+        self.cf_options(cf.id())?;
+        self.background.manual_compaction(cf.id())
+    }
+
+    // Running manual compactions fail with a CompactionCancelled error at the
+    // next merger timeout, new ones fail until compactions are enabled again.
+    pub fn disable_manual_compaction(&self) {
+        self.background
+            .manual_compaction_disabled
+            .store(true, Ordering::Release);
+    }
+
+    pub fn enable_manual_compaction(&self) {
+        self.background
+            .manual_compaction_disabled
+            .store(false, Ordering::Release);
+    }
+
+    pub(crate) fn prepared(&self) -> Vec<(String, WriteBatch<K, V>)> {
+        let prepared = self.prepared.lock().unwrap();
+        let mut batches = Vec::with_capacity(prepared.len());
+        for (xid, write_batch) in prepared.iter() {
+            let mut write_batch = write_batch.clone();
+            write_batch.clear_markers();
+            batches.push((xid.clone(), write_batch));
+        }
+        batches
+    }
+
+    fn check_preconditions(&self, preconditions: &[Precondition<K, V>]) -> EikvResult<bool> {
+        for precondition in preconditions {
+            let holds = match precondition {
+                Precondition::Unchanged { key, seq } => self.unchanged_since(key, *seq),
+                Precondition::Equals { key, value } => self.value_equals(key, value.as_ref())?,
+            };
+            if !holds {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    fn value_equals(&self, key: &K, expected: Option<&V>) -> EikvResult<bool> {
+        match (self.get(key.clone())?, expected) {
+            (None, None) => Ok(true),
+            (Some(value), Some(expected)) => Ok(value.to_bytes()? == expected.to_bytes()?),
+            _ => Ok(false),
+        }
+    }
+
+    fn unchanged_since(&self, key: &K, seq: u64) -> bool {
+        if self
+            .mem_db
+            .latest_seq(DEFAULT_CF_ID, key)
+            .is_ok_and(|latest| latest.is_some_and(|latest| latest > seq))
+        {
+            return false;
+        }
+
+        // Versions flushed out of the memtables are only known by the
+        // metadata of their SSTs, any newer SST covering the key conflicts.
+        let manifest = self.manifest.lock().unwrap();
+        !manifest
+            .sst_metas(DEFAULT_CF_ID)
+            .iter()
+            .any(|(_, _, sst_meta)| {
+                sst_meta.max_seq > seq
+                    && ((sst_meta.min_entry.key <= *key && *key <= sst_meta.max_entry.key)
+                        || sst_meta.range_tombstones.iter().any(|range_tombstone| {
+                            range_tombstone.seq > seq && range_tombstone.contains(key)
+                        }))
+            })
+    }
+
+    pub(crate) fn read_seq(&self) -> ReadSeq<'_, K, V> {
+        self.mem_db.read_seq()
+    }
+
+    // The bytes taken by the memtables of all the column families.
+    pub fn memtable_memory_usage(&self) -> usize {
+        self.mem_db.memory_usage()
+    }
+
+    pub fn begin_optimistic_transaction(&self) -> OptimisticTransaction<'_, K, V> {
+        OptimisticTransaction::new(self)
     }
 
     pub fn put(&self, key: K, value: V) -> EikvResult<()> {
@@ -92,26 +469,375 @@ impl<K: Key + 'static, V: Value + 'static> DB<K, V> {
         self.write(write_batch)
     }
 
+    pub fn put_with_ttl(&self, key: K, value: V, ttl: Duration) -> EikvResult<()> {
+        let mut write_batch = WriteBatch::new();
+        write_batch.put_with_ttl(key, value, ttl);
+        self.write(write_batch)
+    }
+
     pub fn delete(&self, key: K) -> EikvResult<()> {
         let mut write_batch = WriteBatch::new();
         write_batch.delete(key);
         self.write(write_batch)
     }
 
+    pub fn delete_range(&self, start: K, end: K) -> EikvResult<()> {
+        let mut write_batch = WriteBatch::new();
+        write_batch.delete_range(start, end);
+        self.write(write_batch)
+    }
+
+    pub fn compare_and_swap(
+        &self,
+        key: K,
+        expected: Option<V>,
+        new: Option<V>,
+    ) -> EikvResult<bool> {
+        let mut write_batch = WriteBatch::new();
+        match new {
+            Some(value) => write_batch.put(key.clone(), value),
+            None => write_batch.delete(key.clone()),
+        };
+        let write_op = WriteOp {
+            write_batch,
+            preconditions: vec![Precondition::Equals {
+                key,
+                value: expected,
+            }],
+        };
+        self.write_op(write_op)
+    }
+
+    pub fn merge(&self, key: K, operand: V) -> EikvResult<()> {
+        let mut write_batch = WriteBatch::new();
+        write_batch.merge(key, operand);
+        self.write(write_batch)
+    }
+
+    pub fn put_cf(&self, cf: &ColumnFamilyHandle, key: K, value: V) -> EikvResult<()> {
+        let mut write_batch = WriteBatch::new();
+        write_batch.put_cf(cf, key, value);
+        self.write(write_batch)
+    }
+
+    pub fn delete_cf(&self, cf: &ColumnFamilyHandle, key: K) -> EikvResult<()> {
+        let mut write_batch = WriteBatch::new();
+        write_batch.delete_cf(cf, key);
+        self.write(write_batch)
+    }
+
+    pub fn delete_range_cf(&self, cf: &ColumnFamilyHandle, start: K, end: K) -> EikvResult<()> {
+        let mut write_batch = WriteBatch::new();
+        write_batch.delete_range_cf(cf, start, end);
+        self.write(write_batch)
+    }
+
+    pub fn merge_cf(&self, cf: &ColumnFamilyHandle, key: K, operand: V) -> EikvResult<()> {
+        let mut write_batch = WriteBatch::new();
+        write_batch.merge_cf(cf, key, operand);
+        self.write(write_batch)
+    }
+
     pub fn get(&self, key: K) -> EikvResult<Option<V>> {
-        match self.mem_db.get(key) {
-            Some(entry) => Ok(entry.value),
-            None => Ok(None),
+        self.get_cf(&self.default_cf(), key)
+    }
+
+    pub fn get_cf(&self, cf: &ColumnFamilyHandle, key: K) -> EikvResult<Option<V>> {
+        let options = self.cf_options(cf.id())?;
+        let mut chain = vec![];
+        if !self.mem_db.get(cf.id(), &key, u64::MAX, &mut chain)? {
+            self.get_from_sst(cf.id(), &options, &key, &mut chain)?;
+        }
+        merge_versions(options.merge_operator.as_ref(), &key, chain)
+    }
+
+    pub fn multi_get(&self, keys: Vec<K>) -> Vec<EikvResult<Option<V>>> {
+        self.multi_get_cf(&self.default_cf(), keys, false)
+    }
+
+    pub fn multi_get_parallel(&self, keys: Vec<K>) -> Vec<EikvResult<Option<V>>> {
+        self.multi_get_cf(&self.default_cf(), keys, true)
+    }
+
+    // Looks the keys up at one sequence, the keys missing from the memtables
+    // are grouped by SST so each data block is read once. The SSTs are read by
+    // a scoped thread per core in the parallel mode.
+    pub fn multi_get_cf(
+        &self,
+        cf: &ColumnFamilyHandle,
+        keys: Vec<K>,
+        parallel: bool,
+    ) -> Vec<EikvResult<Option<V>>> {
+        let options = match self.cf_options(cf.id()) {
+            Ok(options) => options,
+            Err(_) => {
+                let reason = format!("column family {} doesn't exist", cf.name());
+                return keys
+                    .iter()
+                    .map(|_| Err(EikvError::ColumnFamilyError(reason.clone())))
+                    .collect();
+            }
+        };
+        let read_seq = self.mem_db.read_seq();
+        let seq_guard = read_seq.seq();
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_by(|a, b| keys[*a].cmp(&keys[*b]));
+
+        let mut chains: Vec<Vec<Entry<K, V>>> = keys.iter().map(|_| vec![]).collect();
+        let mut results: Vec<Option<EikvResult<Option<V>>>> = keys.iter().map(|_| None).collect();
+        let mut pending = vec![];
+        for index in order {
+            match self
+                .mem_db
+                .get(cf.id(), &keys[index], seq_guard, &mut chains[index])
+            {
+                Ok(true) => {}
+                Ok(false) => pending.push(index),
+                Err(err) => results[index] = Some(Err(err)),
+            }
+        }
+
+        if !pending.is_empty() {
+            let errors = self.multi_get_from_sst(
+                cf.id(),
+                &options,
+                &keys,
+                &pending,
+                seq_guard,
+                parallel,
+                &mut chains,
+            );
+            for (index, err) in errors {
+                results[index] = Some(Err(err));
+            }
+        }
+
+        let merge_operator = options.merge_operator.as_ref();
+        results
+            .into_iter()
+            .zip(keys.iter().zip(chains))
+            .map(|(result, (key, chain))| {
+                result.unwrap_or_else(|| merge_versions(merge_operator, key, chain))
+            })
+            .collect()
+    }
+
+    // Collects the versions of the pending keys in the SSTs into their chains,
+    // returns the errors by the indexes of the keys.
+    #[allow(clippy::too_many_arguments)]
+    fn multi_get_from_sst(
+        &self,
+        cf_id: u32,
+        options: &DBOptions,
+        keys: &[K],
+        pending: &[usize],
+        seq_guard: u64,
+        parallel: bool,
+        chains: &mut [Vec<Entry<K, V>>],
+    ) -> Vec<(usize, EikvError)> {
+        let mut errors = vec![];
+        let mut tombstone_seqs = vec![];
+        let mut lookups = vec![];
+        {
+            let manifest = self.manifest.lock().unwrap();
+            let sst_metas = manifest.sst_metas(cf_id);
+            for index in pending {
+                let tombstone_seq = max_covering_seq(
+                    sst_metas
+                        .iter()
+                        .flat_map(|(_, _, sst_meta)| &sst_meta.range_tombstones),
+                    &keys[*index],
+                    seq_guard,
+                );
+                tombstone_seqs.push(tombstone_seq);
+            }
+            for (_, file_seq, sst_meta) in sst_metas {
+                // The pending keys are sorted, so are the ones in the file.
+                let file_keys: Vec<usize> = (0..pending.len())
+                    .filter(|i| {
+                        let key = &keys[pending[*i]];
+                        sst_meta.min_entry.key <= *key && *key <= sst_meta.max_entry.key
+                    })
+                    .collect();
+                if file_keys.is_empty() {
+                    continue;
+                }
+                let reader = sst_path(&self.db_path, file_seq).and_then(|sst_path| {
+                    sst::Reader::open(&sst_path, options.clone(), sst_meta.clone())
+                });
+                match reader {
+                    Ok(reader) => lookups.push((reader, file_keys)),
+                    Err(err) => {
+                        let reason = err.to_string();
+                        for i in file_keys {
+                            errors.push((pending[i], EikvError::StdError(reason.clone().into())));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Errors aren't sent across threads, only their messages are.
+        let lookup = |(mut reader, file_keys): (sst::Reader<K, V>, Vec<usize>)| {
+            let file_keys_refs: Vec<&K> = file_keys.iter().map(|i| &keys[pending[*i]]).collect();
+            let versions = reader.multi_get_versions(&file_keys_refs, seq_guard);
+            file_keys
+                .into_iter()
+                .zip(versions)
+                .map(|(i, versions)| (i, versions.map_err(|err| err.to_string())))
+                .collect::<Vec<_>>()
+        };
+        let file_versions: Vec<Vec<_>> = if parallel {
+            // The files are split evenly among at most one thread per core.
+            let thread_count = thread::available_parallelism().map_or(1, |n| n.get());
+            let chunk_size = lookups.len().div_ceil(thread_count).max(1);
+            let lookup = &lookup;
+            thread::scope(|scope| {
+                let mut lookups = lookups.into_iter();
+                let mut handles = vec![];
+                loop {
+                    let chunk: Vec<_> = lookups.by_ref().take(chunk_size).collect();
+                    if chunk.is_empty() {
+                        break;
+                    }
+                    handles.push(
+                        scope.spawn(move || chunk.into_iter().map(lookup).collect::<Vec<_>>()),
+                    );
+                }
+                handles
+                    .into_iter()
+                    .flat_map(|handle| handle.join().unwrap())
+                    .collect()
+            })
+        } else {
+            lookups.into_iter().map(lookup).collect()
+        };
+
+        let mut versions: Vec<Vec<Entry<K, V>>> = pending.iter().map(|_| vec![]).collect();
+        for (i, result) in file_versions.into_iter().flatten() {
+            match result {
+                Ok(file_versions) => versions[i].extend(file_versions),
+                Err(reason) => errors.push((pending[i], EikvError::StdError(reason.into()))),
+            }
+        }
+        for (i, mut versions) in versions.into_iter().enumerate() {
+            let index = pending[i];
+            versions.sort_unstable_by_key(|entry| Reverse(entry.seq));
+            collect_versions(
+                &keys[index],
+                &versions,
+                tombstone_seqs[i],
+                &mut chains[index],
+            );
+        }
+        errors
+    }
+
+    fn get_from_sst(
+        &self,
+        cf_id: u32,
+        options: &DBOptions,
+        key: &K,
+        chain: &mut Vec<Entry<K, V>>,
+    ) -> EikvResult<()> {
+        let mut readers = vec![];
+        let tombstone_seq;
+        {
+            let manifest = self.manifest.lock().unwrap();
+            let sst_metas = manifest.sst_metas(cf_id);
+            tombstone_seq = max_covering_seq(
+                sst_metas
+                    .iter()
+                    .flat_map(|(_, _, sst_meta)| &sst_meta.range_tombstones),
+                key,
+                u64::MAX,
+            );
+            for (_, file_seq, sst_meta) in sst_metas {
+                if *key < sst_meta.min_entry.key || *key > sst_meta.max_entry.key {
+                    continue;
+                }
+                let sst_path = sst_path(&self.db_path, file_seq)?;
+                let reader = sst::Reader::open(&sst_path, options.clone(), sst_meta.clone())?;
+                readers.push(reader);
+            }
+        }
+
+        let mut latest: Option<Entry<K, V>> = None;
+        for reader in readers.iter_mut() {
+            if let Some(entry) = reader.get(key)? {
+                if latest.as_ref().is_none_or(|latest| latest.seq < entry.seq) {
+                    latest = Some(entry);
+                }
+            }
+        }
+
+        // Only a merge operand needs the older versions of the key.
+        if !chain.is_empty()
+            || latest
+                .as_ref()
+                .is_some_and(|e| matches!(e.op, Op::Merge(_)))
+        {
+            let mut versions = vec![];
+            for reader in readers.iter_mut() {
+                versions.extend(reader.get_versions(key)?);
+            }
+            versions.sort_unstable_by_key(|entry| Reverse(entry.seq));
+            collect_versions(key, &versions, tombstone_seq, chain);
+        } else {
+            collect_versions(key, &latest, tombstone_seq, chain);
+        }
+        Ok(())
+    }
+
+    pub fn iter(&self) -> EikvResult<DBIterator<K, V>> {
+        self.iter_cf(&self.default_cf())
+    }
+
+    pub fn iter_cf(&self, cf: &ColumnFamilyHandle) -> EikvResult<DBIterator<K, V>> {
+        let options = self.cf_options(cf.id())?;
+        let (mem_entries, mut range_tombstones) = self.mem_db.snapshot(cf.id())?;
+        let mut sst_iterators = vec![];
+        let manifest = self.manifest.lock().unwrap();
+        for (_, file_seq, sst_meta) in manifest.sst_metas(cf.id()) {
+            let sst_path = sst_path(&self.db_path, file_seq)?;
+            let mut iterator = Iterator::new(&sst_path, options.clone(), sst_meta.clone(), None)?;
+            iterator.seek_to_first()?;
+            sst_iterators.push(iterator);
+            range_tombstones.extend(sst_meta.range_tombstones.iter().cloned());
         }
+        Ok(DBIterator::new(
+            mem_entries,
+            sst_iterators,
+            range_tombstones,
+            options.merge_operator.clone(),
+        ))
     }
 }
 
+// The manifest records a single comparator, the column families share it.
+fn check_comparator(db_options: &DBOptions, cf_options: &DBOptions) -> EikvResult<()> {
+    let name = db_options.comparator.name();
+    if cf_options.comparator.name() != name {
+        let reason = format!(
+            "the column family uses comparator {}, the db uses {}",
+            cf_options.comparator.name(),
+            name
+        );
+        return Err(EikvError::ColumnFamilyError(reason));
+    }
+    Ok(())
+}
+
 fn new_wal<K: Key, V: Value>(
     db_path: &str,
     manifest: Arc<Mutex<Manifest<K, V>>>,
 ) -> EikvResult<Writer> {
-    let file_seq = manifest.lock().unwrap().alloc_wal();
+    // The WAL is recorded before it's written, files the manifest doesn't
+    // refer to are removed on open.
+    let mut manifest = manifest.lock().unwrap();
+    let file_seq = manifest.alloc_wal();
     let writer = Writer::create(&wal_path(db_path, file_seq)?)?;
+    manifest.dump(db_path)?;
     Ok(writer)
 }
 
@@ -121,42 +847,48 @@ fn create_db<K: Key + 'static, V: Value + 'static>(
 ) -> EikvResult<DB<K, V>> {
     init_db_dir(db_path)?;
 
-    let manifest = Arc::new(Mutex::new(Manifest::new()));
+    let manifest = Arc::new(Mutex::new(Manifest::new(options.comparator.name())));
     let wal = new_wal(db_path, manifest.clone())?;
-    let mem_db = Arc::new(MemDB::new(options.clone(), AtomicU64::new(1), wal));
+    let cf_id_options = BTreeMap::from([(DEFAULT_CF_ID, options.clone())]);
+    let mem_db = Arc::new(MemDB::new(
+        options.clone(),
+        AtomicU64::new(1),
+        wal,
+        VecDeque::new(),
+        &cf_id_options,
+    ));
+    let cf_options = Arc::new(RwLock::new(cf_id_options));
     manifest.lock().unwrap().dump(db_path)?;
 
     let lock_file = File::create(lock_file_path(db_path)?)?;
     lock_file.lock_exclusive()?;
 
-    let manifest_copy = manifest.clone();
-    let mem_db_copy = mem_db.clone();
-    let db_path_copy = db_path.to_owned();
-    let options_copy = options.clone();
-    let request_close = Arc::new(Mutex::new(false));
-    let request_close_copy = request_close.clone();
-    let background_thread_exited = Arc::new(Condvar::new());
-    let background_thread_exited_copy = background_thread_exited.clone();
-
-    thread::spawn(move || {
-        background_thread(
-            db_path_copy,
-            options_copy,
-            mem_db_copy,
-            manifest_copy,
-            request_close_copy,
-            background_thread_exited_copy,
-        )
-        .unwrap();
+    let background = Arc::new(Background {
+        db_path: db_path.to_owned(),
+        db_options: options.clone(),
+        cf_options: cf_options.clone(),
+        mem_db: mem_db.clone(),
+        manifest: manifest.clone(),
+        request_close: Mutex::new(false),
+        next_compaction_seq: AtomicU64::new(1),
+        work: Mutex::new(BackgroundWork::default()),
+        work_changed: Condvar::new(),
+        manual_compaction_disabled: AtomicBool::new(false),
+        orphan_files_removed_at: Mutex::new(Instant::now()),
     });
+    let background_threads = spawn_background_threads(background.clone());
 
     let db = DB {
         _lock_file: lock_file,
         db_path: db_path.to_owned(),
         manifest,
         mem_db,
-        request_close,
-        background_thread_exited,
+        options,
+        cf_options,
+        prepared: Mutex::new(BTreeMap::new()),
+        stats: Mutex::new(DBStats::default()),
+        background,
+        background_threads,
     };
     Ok(db)
 }
@@ -167,293 +899,1093 @@ fn init_db_dir(db_path: &str) -> EikvResult<()> {
     create_dir(sst_dir_path(db_path)?)?;
     create_dir(wal_dir_path(db_path)?)?;
     create_dir(sst_tmp_dir_path(db_path)?)?;
-    for level in LEVEL_MIN..=LEVEL_MAX {
-        let sst_level_dir = sst_level_dir_path(db_path, level)?;
-        create_dir(sst_level_dir)?;
-    }
     Ok(())
 }
 
-fn read_wal<K: Key, V: Value>(db_path: &str, file_seq: u64) -> EikvResult<(Table<K, V>, u64)> {
+// Entries of column families without options have been dropped and are
+// skipped.
+fn read_wal<K: Key, V: Value>(
+    db_path: &str,
+    file_seq: u64,
+    cf_options: &BTreeMap<u32, DBOptions>,
+    prepared: &mut PreparedBatches<K, V>,
+) -> EikvResult<(CFTables<K, V>, u64)> {
     let wal_path = wal_path(db_path, file_seq)?;
     let mut reader = Reader::open(&wal_path)?;
-    let mut table = Table::new();
+    let mut tables = BTreeMap::new();
     let mut max_seq = 0;
     loop {
-        let write_batch = match reader.next()? {
+        let write_batch: WriteBatch<K, V> = match reader.next()? {
             Some(write_batch) => write_batch,
             None => break,
         };
-        for entry in write_batch.entries() {
+        if let Some(xid) = write_batch.prepared_xid() {
+            prepared.insert(xid.to_owned(), write_batch);
+            continue;
+        }
+        for marker in write_batch.markers() {
+            if let Marker::Commit(xid) | Marker::Rollback(xid) = marker {
+                prepared.remove(xid);
+            }
+        }
+        for (cf_id, entry) in write_batch.cf_entries() {
             max_seq = max(max_seq, entry.seq);
-            table.insert(entry.clone());
+            if let Some(options) = cf_options.get(&cf_id) {
+                let table = tables.entry(cf_id).or_insert_with(|| Table::new(options));
+                table.insert(entry.clone())?;
+            }
         }
     }
-    Ok((table, max_seq))
+    Ok((tables, max_seq))
+}
+
+// The WALs may be empty after a flush, so the seqs carry on from the newest
+// entry in the files. Files written before the footer recorded the max seq
+// are scanned.
+fn sst_max_seq<K: Key, V: Value>(
+    db_path: &str,
+    manifest: &Manifest<K, V>,
+    cf_options: &BTreeMap<u32, DBOptions>,
+) -> EikvResult<u64> {
+    let mut max_seq = 0;
+    for (cf_id, options) in cf_options {
+        for (_, file_seq, sst_meta) in manifest.sst_metas(*cf_id) {
+            if sst_meta.max_seq != u64::MAX {
+                max_seq = max(max_seq, sst_meta.max_seq);
+                continue;
+            }
+            let sst_path = sst_path(db_path, file_seq)?;
+            let mut iterator = Iterator::new(&sst_path, options.clone(), sst_meta.clone(), None)?;
+            iterator.seek_to_first()?;
+            while let Some(entry) = iterator.entry() {
+                max_seq = max(max_seq, entry.seq);
+                iterator.next()?;
+            }
+        }
+    }
+    Ok(max_seq)
 }
 
 fn load_mem_db<K: Key, V: Value>(
     db_path: &str,
     options: DBOptions,
     manifest: &Manifest<K, V>,
-) -> EikvResult<MemDB<K, V>> {
+    cf_options: &BTreeMap<u32, DBOptions>,
+) -> EikvResult<(MemDB<K, V>, PreparedBatches<K, V>)> {
     let mut file_seqs = vec![];
     for file_seq in manifest.wals().iter() {
         file_seqs.push(*file_seq);
     }
     file_seqs.sort_unstable();
 
-    let mut_wal_file_seq = file_seqs[file_seqs.len() - 1];
-    let (mut_table, max_seq) = read_wal::<K, V>(db_path, mut_wal_file_seq)?;
-    let wal_path = wal_path(db_path, mut_wal_file_seq)?;
-    let mut_wal = Writer::open(&wal_path)?;
+    // All the WALs but the last one belong to frozen memtables, which are
+    // queued for flush again.
+    let mut prepared = BTreeMap::new();
+    let (mut_wal_file_seq, immut_file_seqs) = file_seqs.split_last().unwrap();
+    let mut immut_tables = vec![];
+    let mut immut_wals = VecDeque::new();
+    let mut max_seq = sst_max_seq(db_path, manifest, cf_options)?;
+    for file_seq in immut_file_seqs {
+        let (tables, wal_max_seq) = read_wal(db_path, *file_seq, cf_options, &mut prepared)?;
+        max_seq = max(max_seq, wal_max_seq);
+        immut_tables.push(tables);
+        immut_wals.push_back(Writer::open(&wal_path(db_path, *file_seq)?)?);
+    }
 
-    let immut_table: Table<K, V> = if file_seqs.len() == 1 {
-        Table::new()
-    } else {
-        let (immut_table, _) = read_wal(db_path, file_seqs[0])?;
-        immut_table
-    };
+    let (mut mut_tables, wal_max_seq) =
+        read_wal(db_path, *mut_wal_file_seq, cf_options, &mut prepared)?;
+    max_seq = max(max_seq, wal_max_seq);
+    let wal_path = wal_path(db_path, *mut_wal_file_seq)?;
+    let mut_wal = Writer::open(&wal_path)?;
 
     let next_seq = AtomicU64::new(max_seq + 1);
-    let mut mem_db = MemDB::new(options, next_seq, mut_wal);
-    mem_db.recover_mut_table(mut_table);
-    mem_db.recover_immut_table(immut_table);
-    Ok(mem_db)
+    let mut mem_db = MemDB::new(options, next_seq, mut_wal, immut_wals, cf_options);
+    for (cf_id, options) in cf_options {
+        let new_table = || Table::new(options);
+        let mut_table = mut_tables.remove(cf_id).unwrap_or_else(new_table);
+        let cf_immut_tables = immut_tables
+            .iter_mut()
+            .map(|tables| tables.remove(cf_id).unwrap_or_else(new_table))
+            .collect();
+        mem_db.recover_tables(*cf_id, options.clone(), mut_table, cf_immut_tables);
+    }
+    Ok((mem_db, prepared))
 }
 
 fn open_db<K: Key + 'static, V: Value + 'static>(
     db_path: &str,
     options: DBOptions,
+    cf_options: &HashMap<String, DBOptions>,
 ) -> EikvResult<DB<K, V>> {
     let lock_file_path = lock_file_path(db_path)?;
     let lock_file = File::open(&lock_file_path)?;
     lock_file.lock_exclusive()?;
 
-    let manifest = Manifest::load(db_path, options.block_size)?;
-    let mem_db = Arc::new(load_mem_db(db_path, options.clone(), &manifest)?);
+    // The default column family is opened with the options of the DB.
+    let cf_opts = |name: &str| match cf_options.get(name) {
+        Some(cf_opts) if name != DEFAULT_CF_NAME => cf_opts.clone(),
+        _ => options.clone(),
+    };
+    let manifest = Manifest::load(db_path, options.comparator.name(), |name| {
+        cf_opts(name).block_size
+    })?;
+    remove_orphan_files(db_path, &manifest, true)?;
+    let mut cf_id_options = BTreeMap::new();
+    for cf_id in manifest.cf_ids() {
+        let name = manifest.cf_name(cf_id).unwrap();
+        cf_id_options.insert(cf_id, cf_opts(name));
+    }
+    let (mem_db, prepared) = load_mem_db(db_path, options.clone(), &manifest, &cf_id_options)?;
+    let cf_options = Arc::new(RwLock::new(cf_id_options));
+    let mem_db = Arc::new(mem_db);
     let manifest = Arc::new(Mutex::new(manifest));
 
-    let manifest_copy = manifest.clone();
-    let mem_db_copy = mem_db.clone();
-    let db_path_copy = db_path.to_owned();
-    let options_copy = options.clone();
-    let request_close = Arc::new(Mutex::new(false));
-    let request_close_copy = request_close.clone();
-    let background_thread_exited = Arc::new(Condvar::new());
-    let background_thread_exited_copy = background_thread_exited.clone();
-
-    thread::spawn(move || {
-        background_thread(
-            db_path_copy,
-            options_copy,
-            mem_db_copy,
-            manifest_copy,
-            request_close_copy,
-            background_thread_exited_copy,
-        )
-        .unwrap();
+    let background = Arc::new(Background {
+        db_path: db_path.to_owned(),
+        db_options: options.clone(),
+        cf_options: cf_options.clone(),
+        mem_db: mem_db.clone(),
+        manifest: manifest.clone(),
+        request_close: Mutex::new(false),
+        next_compaction_seq: AtomicU64::new(1),
+        work: Mutex::new(BackgroundWork::default()),
+        work_changed: Condvar::new(),
+        manual_compaction_disabled: AtomicBool::new(false),
+        orphan_files_removed_at: Mutex::new(Instant::now()),
     });
+    let background_threads = spawn_background_threads(background.clone());
 
     let db = DB {
         _lock_file: lock_file,
         db_path: db_path.to_owned(),
         manifest,
         mem_db,
-        request_close,
-        background_thread_exited,
+        options,
+        cf_options,
+        prepared: Mutex::new(prepared),
+        stats: Mutex::new(DBStats::default()),
+        background,
+        background_threads,
     };
     Ok(db)
 }
 
-struct MergerState<K: Key, V: Value> {
+// A merger of a key range of the inputs of a compaction with its outputs.
+struct Subcompaction<K: Key, V: Value> {
     merger: Merger<K, V>,
-    level: usize,
+    compaction_seq: u64,
     major_seqs: Vec<u64>,
+}
+
+enum Compaction<K: Key, V: Value> {
+    // A file overlapping nothing in the next level is moved down as is.
+    TrivialMove {
+        cf_id: u32,
+        level: usize,
+        file_seq: u64,
+    },
+    Merge(Box<MergerState<K, V>>),
+}
+
+struct MergerState<K: Key, V: Value> {
+    subcompactions: Vec<Subcompaction<K, V>>,
+    cf_id: u32,
+    options: DBOptions,
+    level: usize,
     this_level_file_seq: Vec<u64>,
     next_level_file_seq: Vec<u64>,
+    is_manual_compaction: bool,
 }
 
 fn get_merger<K: Key, V: Value>(
     manifest: Arc<Mutex<Manifest<K, V>>>,
     db_path: &str,
-    db_options: DBOptions,
+    cf_options: &CFOptions,
     wal_size_limit: u64,
-) -> EikvResult<Option<MergerState<K, V>>> {
-    let manifest = manifest.lock().unwrap();
+    next_compaction_seq: &AtomicU64,
+    read_seqs: Option<(u64, u64)>,
+) -> EikvResult<Option<Compaction<K, V>>> {
+    let mut manifest = manifest.lock().unwrap();
+    let cf_options = cf_options.read().unwrap();
+    for (cf_id, options) in cf_options.iter() {
+        if !manifest.has_cf(*cf_id) {
+            continue;
+        }
+        let compaction = get_cf_merger(
+            &manifest,
+            db_path,
+            *cf_id,
+            options.clone(),
+            wal_size_limit,
+            next_compaction_seq,
+            read_seqs,
+        )?;
+        if let Some(compaction) = compaction {
+            mark_being_compacted(&mut manifest, *cf_id, &compaction);
+            return Ok(Some(compaction));
+        }
+    }
+    Ok(None)
+}
+
+fn mark_being_compacted<K: Key, V: Value>(
+    manifest: &mut Manifest<K, V>,
+    cf_id: u32,
+    compaction: &Compaction<K, V>,
+) {
+    match compaction {
+        Compaction::TrivialMove { file_seq, .. } => {
+            manifest.mark_being_compacted(cf_id, &[*file_seq]);
+        }
+        Compaction::Merge(merger_state) => {
+            manifest.mark_being_compacted(cf_id, &merger_state.this_level_file_seq);
+            manifest.mark_being_compacted(cf_id, &merger_state.next_level_file_seq);
+        }
+    }
+}
 
+fn get_cf_merger<K: Key, V: Value>(
+    manifest: &Manifest<K, V>,
+    db_path: &str,
+    cf_id: u32,
+    db_options: DBOptions,
+    wal_size_limit: u64,
+    next_compaction_seq: &AtomicU64,
+    read_seqs: Option<(u64, u64)>,
+) -> EikvResult<Option<Compaction<K, V>>> {
     let mut target_level = 0;
     for level in LEVEL_MIN..=LEVEL_MAX {
         let size_max = wal_size_limit * 5_u64.pow(level as u32);
-        if manifest.level_sst_count(level) > 6 || manifest.level_size(level) > size_max {
+        if manifest.level_sst_count(cf_id, level) > 6
+            || manifest.level_size(cf_id, level) > size_max
+        {
             target_level = level;
             break;
         }
     }
-    if target_level == 0 || target_level == LEVEL_MAX {
-        return Ok(None);
+
+    let mut target = None;
+    if target_level != 0 && target_level != LEVEL_MAX {
+        target = manifest
+            .min_file_seq(cf_id, target_level)
+            .map(|file_seq| (target_level, file_seq));
     }
+    // Files holding expired values are pushed down to drop the values, the
+    // files of the last level are rewritten.
+    let expired = target.is_none();
+    let target = match target.or_else(|| manifest.expired_sst(cf_id, unix_now() as u64)) {
+        Some(target) => target,
+        None => return Ok(None),
+    };
+    // Expired values are only dropped by rewriting their file.
+    new_compaction(
+        manifest,
+        db_path,
+        cf_id,
+        db_options,
+        wal_size_limit,
+        next_compaction_seq,
+        read_seqs,
+        target,
+        !expired,
+        false,
+    )
+}
 
-    let file_seq = manifest.min_file_seq(target_level);
-    let (files, this_level_file_seq, next_level_file_seq) =
-        manifest.should_merge(db_path, target_level, file_seq)?;
+// Merges the file with the overlapping files of its level and the next one,
+// or rewrites it in place in the last level, returns None if any of them is
+// being compacted. The oldest and the newest
+// sequences of the live reads keep the versions they see.
+#[allow(clippy::too_many_arguments)]
+fn new_compaction<K: Key, V: Value>(
+    manifest: &Manifest<K, V>,
+    db_path: &str,
+    cf_id: u32,
+    db_options: DBOptions,
+    wal_size_limit: u64,
+    next_compaction_seq: &AtomicU64,
+    read_seqs: Option<(u64, u64)>,
+    (target_level, file_seq): (usize, u64),
+    allow_trivial_move: bool,
+    is_manual_compaction: bool,
+) -> EikvResult<Option<Compaction<K, V>>> {
+    let (files, this_level_file_seq, next_level_file_seq) = if target_level == LEVEL_MAX {
+        manifest.should_rewrite(db_path, cf_id, target_level, file_seq)?
+    } else {
+        manifest.should_merge(db_path, cf_id, target_level, file_seq)?
+    };
+    // Running compactions don't share their inputs.
+    if this_level_file_seq
+        .iter()
+        .chain(&next_level_file_seq)
+        .any(|file_seq| manifest.is_being_compacted(cf_id, *file_seq))
+    {
+        return Ok(None);
+    }
+    // Nothing moves the file again once it's in the last level, so range
+    // tombstones, values with a TTL and the compaction filter need a merge.
+    let needs_merge = target_level + 1 == LEVEL_MAX
+        && (db_options.compaction_filter.is_some()
+            || files.values().any(|sst_meta| {
+                sst_meta.min_expire_at != u64::MAX || !sst_meta.range_tombstones.is_empty()
+            }));
+    if allow_trivial_move
+        && this_level_file_seq.len() == 1
+        && next_level_file_seq.is_empty()
+        && !needs_merge
+    {
+        return Ok(Some(Compaction::TrivialMove {
+            cf_id,
+            level: target_level,
+            file_seq,
+        }));
+    }
 
-    let mut sst_paths = vec![];
-    let mut iterators = vec![];
-    for (sst_path, sst_meta) in files {
-        let sst_meta = (*sst_meta).clone();
-        let mut iterator = Iterator::new(&sst_path, db_options.clone(), sst_meta)?;
-        iterator.seek_to_first()?;
-        iterators.push(iterator);
-        sst_paths.push(sst_path);
+    let mut range_tombstones = vec![];
+    for sst_meta in files.values() {
+        range_tombstones.extend(sst_meta.range_tombstones.iter().cloned());
     }
 
-    let level = target_level + 1;
-    let major_path = sst_major_tmp_path(db_path, 1)?;
+    let (seq_guard, filter_seq) = read_seqs.unwrap_or((u64::MAX, 0));
+    let level = min(target_level + 1, LEVEL_MAX);
+    // In the last level a tombstone only hides versions of the merge once no
+    // other file overlaps it, it isn't written then.
+    let mut written_range_tombstones = range_tombstones.clone();
+    if level == LEVEL_MAX {
+        let other_sst_metas: Vec<&SstMeta<K, V>> = manifest
+            .sst_metas(cf_id)
+            .into_iter()
+            .filter(|(_, file_seq, _)| {
+                !this_level_file_seq.contains(file_seq) && !next_level_file_seq.contains(file_seq)
+            })
+            .map(|(_, _, sst_meta)| sst_meta)
+            .collect();
+        written_range_tombstones.retain(|range_tombstone| {
+            range_tombstone.seq > seq_guard
+                || other_sst_metas.iter().any(|sst_meta| {
+                    range_tombstone.start <= sst_meta.max_entry.key
+                        && sst_meta.min_entry.key < range_tombstone.end
+                })
+        });
+    }
     let size_limit = wal_size_limit * 5_u64.pow(level as u32 - 1);
-    let merger = Merger::new(
-        &major_path,
-        iterators,
-        db_options,
-        u64::MAX,
-        size_limit,
-        100,
-    )?;
+    let mut subcompactions = vec![];
+    for key_range in subcompaction_ranges(&files, db_options.max_subcompactions) {
+        let mut iterators = vec![];
+        for (sst_path, sst_meta) in &files {
+            let overlaps = key_range
+                .start
+                .as_ref()
+                .is_none_or(|start| *start <= sst_meta.max_entry.key)
+                && key_range
+                    .end
+                    .as_ref()
+                    .is_none_or(|end| sst_meta.min_entry.key < *end);
+            // A file hidden by a tombstone of the merge isn't read at all.
+            let covered = range_tombstones.iter().any(|range_tombstone| {
+                range_tombstone.seq <= seq_guard && sst_meta.covered_by(range_tombstone)
+            });
+            if overlaps && !covered {
+                let sst_meta = (*sst_meta).clone();
+                iterators.push(Iterator::new(
+                    sst_path,
+                    db_options.clone(),
+                    sst_meta,
+                    Some(IOPriority::Low),
+                )?);
+            }
+        }
+
+        let compaction_seq = next_compaction_seq.fetch_add(1, Ordering::Relaxed);
+        let major_path = sst_major_tmp_path(db_path, compaction_seq, 1)?;
+        let merger = Merger::new(
+            &major_path,
+            iterators,
+            range_tombstones.clone(),
+            written_range_tombstones.clone(),
+            db_options.clone(),
+            seq_guard,
+            filter_seq,
+            size_limit,
+            100,
+            CompactionFilterContext {
+                level,
+                is_manual_compaction,
+            },
+            key_range,
+        )?;
+        subcompactions.push(Subcompaction {
+            merger,
+            compaction_seq,
+            major_seqs: vec![1],
+        });
+    }
 
     let merger_state = MergerState {
-        merger,
+        subcompactions,
+        cf_id,
+        options: db_options,
         level,
-        major_seqs: vec![1],
         this_level_file_seq,
         next_level_file_seq,
+        is_manual_compaction,
     };
-    Ok(Some(merger_state))
+    Ok(Some(Compaction::Merge(Box::new(merger_state))))
 }
 
-fn minor_compaction<K: Key, V: Value>(
+// Splits the keys of the inputs into at most max_subcompactions ranges at the
+// smallest keys of the files.
+fn subcompaction_ranges<K: Key, V: Value>(
+    files: &HashMap<String, &SstMeta<K, V>>,
+    max_subcompactions: usize,
+) -> Vec<KeyRange<K>> {
+    let mut boundaries: Vec<&K> = files
+        .values()
+        .map(|sst_meta| &sst_meta.min_entry.key)
+        .collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+    // The smallest key doesn't split anything.
+    let boundaries = &boundaries[1.min(boundaries.len())..];
+    let count = max_subcompactions.clamp(1, boundaries.len() + 1);
+
+    let mut ranges = vec![];
+    let mut start = None;
+    for i in 1..count {
+        let end = boundaries[i * boundaries.len() / count].clone();
+        ranges.push(KeyRange {
+            start,
+            end: Some(end.clone()),
+        });
+        start = Some(end);
+    }
+    ranges.push(KeyRange { start, end: None });
+    ranges
+}
+
+fn remove_obsolete_ssts<K: Key, V: Value>(
     db_path: &str,
-    db_options: DBOptions,
-    mem_db: Arc<MemDB<K, V>>,
     manifest: Arc<Mutex<Manifest<K, V>>>,
+    seq_guard: u64,
 ) -> EikvResult<()> {
-    let block_size = db_options.block_size;
-    let minor_path = sst_minor_tmp_path(db_path)?;
-    let writer: sst::Writer<K, V> =
-        sst::Writer::new(&minor_path, db_options.clone(), db_options.wal_size_limit)?;
-    mem_db.dump(writer)?;
-
     let mut manifest = manifest.lock().unwrap();
+    let mut obsolete_ssts = vec![];
+    for cf_id in manifest.cf_ids() {
+        for (level, file_seq) in manifest.obsolete_ssts(cf_id, seq_guard) {
+            manifest.remove_sst(cf_id, level, file_seq);
+            obsolete_ssts.push(file_seq);
+        }
+    }
+    if obsolete_ssts.is_empty() {
+        return Ok(());
+    }
 
-    let file_seq = manifest.alloc_sst(LEVEL_MIN);
-    let sst_path = sst_path(&db_path, LEVEL_MIN, file_seq)?;
-    rename(&minor_path, &sst_path)?;
-    let sst_meta = SstMeta::new(&sst_path, block_size)?;
-    manifest.set_sst_meta(LEVEL_MIN, file_seq, sst_meta);
+    manifest.dump(db_path)?;
 
-    let file_seq = manifest.remove_wal();
-    manifest.dump(&db_path)?;
-    let wal_path = wal_path(&db_path, file_seq)?;
-    remove_file(wal_path)?;
+    for file_seq in obsolete_ssts {
+        remove_file(sst_path(db_path, file_seq)?)?;
+    }
+    Ok(())
+}
 
+// Removes the files the manifest doesn't refer to, left by a crash or by
+// anything else. The temporary SSTs of running flushes and compactions are
+// only removed on open.
+fn remove_orphan_files<K: Key, V: Value>(
+    db_path: &str,
+    manifest: &Manifest<K, V>,
+    on_open: bool,
+) -> EikvResult<()> {
+    let live_files = manifest.live_files(db_path)?;
+    let mut dirs = vec![
+        manifest_dir_path(db_path)?,
+        wal_dir_path(db_path)?,
+        sst_dir_path(db_path)?,
+    ];
+    if on_open {
+        dirs.push(sst_tmp_dir_path(db_path)?);
+    }
+    for dir in dirs {
+        for entry in read_dir(dir)? {
+            let path = entry?.path();
+            if !path.is_file() {
+                continue;
+            }
+            let is_live = path.to_str().is_some_and(|path| live_files.contains(path));
+            if !is_live {
+                remove_file(path)?;
+            }
+        }
+    }
+    let current_tmp_path = current_tmp_path(db_path)?;
+    if on_open && Path::new(&current_tmp_path).try_exists()? {
+        remove_file(current_tmp_path)?;
+    }
     Ok(())
 }
 
-fn background_thread<K: Key, V: Value>(
+// The state shared by the threads of the flush and the compaction pools.
+struct Background<K: Key, V: Value> {
     db_path: String,
     db_options: DBOptions,
+    cf_options: CFOptions,
     mem_db: Arc<MemDB<K, V>>,
     manifest: Arc<Mutex<Manifest<K, V>>>,
-    request_close: Arc<Mutex<bool>>,
-    background_thread_exited: Arc<Condvar>,
-) -> EikvResult<()> {
-    let mut merger_state: Option<MergerState<K, V>> = None;
+    request_close: Mutex<bool>,
+    // Names the temporary SSTs of the running compactions.
+    next_compaction_seq: AtomicU64,
+    work: Mutex<BackgroundWork>,
+    // Notified when the work is paused, continued or stopped and on close.
+    work_changed: Condvar,
+    manual_compaction_disabled: AtomicBool,
+    orphan_files_removed_at: Mutex<Instant>,
+}
 
-    loop {
-        {
-            let mut request_close = request_close.lock().unwrap();
-            if *request_close {
-                *request_close = false;
-                background_thread_exited.notify_one();
-                break;
+#[derive(Default)]
+struct BackgroundWork {
+    paused: usize,
+    // The threads flushing or compacting and not parked by a pause.
+    active: usize,
+}
+
+fn spawn_background_threads<K: Key + 'static, V: Value + 'static>(
+    background: Arc<Background<K, V>>,
+) -> Vec<JoinHandle<()>> {
+    let mut background_threads = vec![];
+    // A failed flush or compaction stops the background work, the writes
+    // fail with its error from then on.
+    for _ in 0..max(background.db_options.max_background_flushes, 1) {
+        let background = background.clone();
+        background_threads.push(thread::spawn(move || {
+            if let Err(err) = background.flush_thread() {
+                background.mem_db.set_background_error(&err);
             }
+        }));
+    }
+    for _ in 0..max(background.db_options.max_background_compactions, 1) {
+        let background = background.clone();
+        background_threads.push(thread::spawn(move || {
+            if let Err(err) = background.compaction_thread() {
+                background.mem_db.set_background_error(&err);
+            }
+        }));
+    }
+    background_threads
+}
+
+impl<K: Key, V: Value> Background<K, V> {
+    fn closing(&self) -> bool {
+        *self.request_close.lock().unwrap()
+    }
+
+    fn close(&self) {
+        *self.request_close.lock().unwrap() = true;
+        let _work = self.work.lock().unwrap();
+        self.work_changed.notify_all();
+    }
+
+    fn pause(&self) {
+        let mut work = self.work.lock().unwrap();
+        work.paused += 1;
+        while work.active > 0 {
+            work = self.work_changed.wait(work).unwrap();
         }
+    }
 
-        if mem_db.has_immut() {
-            minor_compaction(
-                &db_path,
-                db_options.clone(),
-                mem_db.clone(),
-                manifest.clone(),
-            )?;
-            continue;
+    fn resume(&self) {
+        let mut work = self.work.lock().unwrap();
+        work.paused = work.paused.saturating_sub(1);
+        self.work_changed.notify_all();
+    }
+
+    // Waits while the work is paused, returns false if the DB is closed.
+    fn begin_work(&self) -> bool {
+        let mut work = self.work.lock().unwrap();
+        while work.paused > 0 && !self.closing() {
+            work = self.work_changed.wait(work).unwrap();
         }
+        work.active += 1;
+        !self.closing()
+    }
+
+    fn end_work(&self) {
+        let mut work = self.work.lock().unwrap();
+        work.active -= 1;
+        self.work_changed.notify_all();
+    }
+
+    // A point where a running job may be parked by a pause, returns false if
+    // the DB is closed.
+    fn checkpoint(&self) -> bool {
+        if self.work.lock().unwrap().paused > 0 {
+            self.end_work();
+            return self.begin_work();
+        }
+        !self.closing()
+    }
 
-        if merger_state.is_none() {
-            merger_state = get_merger(
-                manifest.clone(),
-                &db_path,
-                db_options.clone(),
-                db_options.wal_size_limit,
+    fn flush_thread(&self) -> EikvResult<()> {
+        while !self.closing() {
+            self.mem_db.background_error()?;
+            self.mem_db.wait_immut(Duration::from_secs(1));
+            if self.begin_work() {
+                let result = self.flush_queued();
+                self.end_work();
+                result?;
+            } else {
+                self.end_work();
+            }
+        }
+        Ok(())
+    }
+
+    fn compaction_thread(&self) -> EikvResult<()> {
+        while !self.closing() {
+            self.mem_db.background_error()?;
+            let busy = if self.begin_work() {
+                self.compact_once()
+            } else {
+                Ok(true)
+            };
+            self.end_work();
+            if !busy? {
+                self.mem_db.wait_background_progress(Duration::from_secs(1));
+            }
+        }
+        Ok(())
+    }
+
+    // Returns false if there was nothing to flush or compact.
+    fn compact_once(&self) -> EikvResult<bool> {
+        if self.flush_queued()? {
+            return Ok(true);
+        }
+
+        self.remove_orphan_files_if_due()?;
+        let seq_guard = self
+            .mem_db
+            .read_seq_bounds()
+            .map_or(u64::MAX, |(oldest, _)| oldest);
+        remove_obsolete_ssts(&self.db_path, self.manifest.clone(), seq_guard)?;
+        let compaction = get_merger(
+            self.manifest.clone(),
+            &self.db_path,
+            &self.cf_options,
+            self.db_options.wal_size_limit,
+            &self.next_compaction_seq,
+            self.mem_db.read_seq_bounds(),
+        )?;
+        match compaction {
+            Some(compaction) => self.run_compaction(compaction)?,
+            None => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    // Files are only added to and removed from the level dirs, the WAL dir
+    // and the manifest dir under the manifest lock.
+    fn remove_orphan_files_if_due(&self) -> EikvResult<()> {
+        let mut removed_at = self.orphan_files_removed_at.lock().unwrap();
+        if removed_at.elapsed() < self.db_options.delete_obsolete_files_period {
+            return Ok(());
+        }
+        *removed_at = Instant::now();
+        drop(removed_at);
+        let manifest = self.manifest.lock().unwrap();
+        remove_orphan_files(&self.db_path, &manifest, false)
+    }
+
+    fn run_compaction(&self, compaction: Compaction<K, V>) -> EikvResult<()> {
+        match compaction {
+            Compaction::TrivialMove {
+                cf_id,
+                level,
+                file_seq,
+            } => self.trivial_move(cf_id, level, file_seq),
+            Compaction::Merge(state) => self.major_compaction(*state),
+        }
+    }
+
+    // Files flushed or moved into a level after its merge started are left
+    // there. Files being compacted by the background threads are waited for,
+    // a failed background flush or compaction fails it.
+    fn manual_compaction(&self, cf_id: u32) -> EikvResult<()> {
+        self.mem_db.background_error()?;
+        if !self.begin_work() {
+            self.end_work();
+            return Ok(());
+        }
+        let result = self.compact_levels(cf_id);
+        self.end_work();
+        result
+    }
+
+    fn compact_levels(&self, cf_id: u32) -> EikvResult<()> {
+        let options = match self.cf_options.read().unwrap().get(&cf_id) {
+            Some(options) => options.clone(),
+            None => return Ok(()),
+        };
+        for level in LEVEL_MIN..LEVEL_MAX {
+            let manifest = self.manifest.lock().unwrap();
+            if !manifest.has_cf(cf_id) {
+                return Ok(());
+            }
+            let mut file_seqs: Vec<u64> =
+                manifest.get_level(cf_id, level).keys().copied().collect();
+            drop(manifest);
+            while !file_seqs.is_empty() {
+                self.mem_db.background_error()?;
+                if self.manual_compaction_disabled.load(Ordering::Acquire) {
+                    let reason = "manual compaction is disabled".to_owned();
+                    return Err(EikvError::CompactionCancelled(reason));
+                }
+                if !self.checkpoint() {
+                    return Ok(());
+                }
+                match self.next_manual_compaction(cf_id, &options, level, &mut file_seqs)? {
+                    Some(compaction) => self.run_compaction(compaction)?,
+                    None => self
+                        .mem_db
+                        .wait_background_progress(Duration::from_millis(100)),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Drops the files gone from the level and picks the smallest one left.
+    fn next_manual_compaction(
+        &self,
+        cf_id: u32,
+        options: &DBOptions,
+        level: usize,
+        file_seqs: &mut Vec<u64>,
+    ) -> EikvResult<Option<Compaction<K, V>>> {
+        let mut manifest = self.manifest.lock().unwrap();
+        if !manifest.has_cf(cf_id) {
+            file_seqs.clear();
+            return Ok(None);
+        }
+        file_seqs.retain(|file_seq| manifest.get_level(cf_id, level).contains_key(file_seq));
+        let file_seq = match file_seqs
+            .iter()
+            .filter(|file_seq| !manifest.is_being_compacted(cf_id, **file_seq))
+            .min()
+        {
+            Some(file_seq) => *file_seq,
+            None => return Ok(None),
+        };
+        let compaction = new_compaction(
+            &manifest,
+            &self.db_path,
+            cf_id,
+            options.clone(),
+            self.db_options.wal_size_limit,
+            &self.next_compaction_seq,
+            self.mem_db.read_seq_bounds(),
+            (level, file_seq),
+            true,
+            true,
+        )?;
+        if let Some(compaction) = &compaction {
+            mark_being_compacted(&mut manifest, cf_id, compaction);
+        }
+        Ok(compaction)
+    }
+
+    // Flushes the memtables nobody flushes yet, returns whether there were
+    // any. Flushes preempt merges this way.
+    fn flush_queued(&self) -> EikvResult<bool> {
+        let mut flushed = false;
+        while let Some(flush_job) = self.mem_db.claim_flush(Duration::ZERO) {
+            self.minor_compaction(flush_job)?;
+            flushed = true;
+        }
+        Ok(flushed)
+    }
+
+    // Flushes the frozen memtables of all the column families sharing a WAL,
+    // the WAL is removed once all of them are in SSTs.
+    fn minor_compaction(&self, flush_job: FlushJob<K, V>) -> EikvResult<()> {
+        let mut minor_ssts = vec![];
+        for (cf_id, table) in flush_job.tables {
+            let options = match self.cf_options.read().unwrap().get(&cf_id) {
+                Some(options) => options.clone(),
+                None => continue,
+            };
+            let minor_path = sst_minor_tmp_path(&self.db_path, flush_job.seq, cf_id)?;
+            let writer: sst::Writer<K, V> = sst::Writer::new(
+                &minor_path,
+                options.clone(),
+                self.db_options.wal_size_limit,
+                IOPriority::High,
             )?;
+            table.dump(writer)?;
+            minor_ssts.push((cf_id, options, minor_path));
         }
 
-        if let Some(mut state) = merger_state {
-            match state.merger.merge()? {
+        // WALs are removed oldest first, so flushes are installed in order.
+        self.mem_db.wait_flush_turn(flush_job.seq)?;
+        let mut manifest = self.manifest.lock().unwrap();
+        for (cf_id, options, minor_path) in minor_ssts {
+            if !manifest.has_cf(cf_id) {
+                remove_file(&minor_path)?;
+                continue;
+            }
+            let file_seq = manifest.alloc_sst(cf_id, LEVEL_MIN);
+            let sst_path = sst_path(&self.db_path, file_seq)?;
+            rename(&minor_path, &sst_path)?;
+            let sst_meta = SstMeta::new(&sst_path, options.block_size)?;
+            manifest.set_sst_meta(cf_id, LEVEL_MIN, file_seq, sst_meta);
+        }
+
+        let file_seq = manifest.remove_wal();
+        manifest.dump(&self.db_path)?;
+        let wal_path = wal_path(&self.db_path, file_seq)?;
+        remove_file(wal_path)?;
+        self.mem_db.drop_immut();
+
+        Ok(())
+    }
+
+    // Only the manifest changes, the file stays where it is.
+    fn trivial_move(&self, cf_id: u32, level: usize, file_seq: u64) -> EikvResult<()> {
+        let mut manifest = self.manifest.lock().unwrap();
+        if !manifest.has_cf(cf_id) {
+            return Ok(());
+        }
+        manifest.move_sst(cf_id, level, file_seq);
+        manifest.unmark_being_compacted(cf_id, &[file_seq]);
+        manifest.dump(&self.db_path)?;
+        drop(manifest);
+        self.mem_db.notify_background_progress();
+        Ok(())
+    }
+
+    // The subcompactions run on scoped threads of their own, each of them
+    // is parked by a pause on its own.
+    fn major_compaction(&self, mut state: MergerState<K, V>) -> EikvResult<()> {
+        let finished = match self.run_subcompactions(&mut state) {
+            Ok(finished) => finished,
+            Err(err) => {
+                self.abandon_major_compaction(state)?;
+                return Err(err);
+            }
+        };
+        if finished {
+            return self.install_major_compaction(state);
+        }
+        if self.closing() {
+            return Ok(());
+        }
+        self.abandon_major_compaction(state)?;
+        let reason = "manual compaction is disabled".to_owned();
+        Err(EikvError::CompactionCancelled(reason))
+    }
+
+    fn run_subcompactions(&self, state: &mut MergerState<K, V>) -> EikvResult<bool> {
+        let finished = if state.subcompactions.len() == 1 {
+            self.subcompaction(
+                &mut state.subcompactions[0],
+                &state.options,
+                state.level,
+                state.is_manual_compaction,
+            )?
+        } else {
+            self.end_work();
+            let results: Vec<Result<bool, String>> = thread::scope(|scope| {
+                let handles: Vec<_> = state
+                    .subcompactions
+                    .iter_mut()
+                    .map(|subcompaction| {
+                        let options = &state.options;
+                        let level = state.level;
+                        let is_manual_compaction = state.is_manual_compaction;
+                        scope.spawn(move || {
+                            let result = if self.begin_work() {
+                                self.subcompaction(
+                                    subcompaction,
+                                    options,
+                                    level,
+                                    is_manual_compaction,
+                                )
+                            } else {
+                                Ok(false)
+                            };
+                            self.end_work();
+                            result.map_err(|err| err.to_string())
+                        })
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().unwrap())
+                    .collect()
+            });
+            let closing = !self.begin_work();
+            let mut finished = !closing;
+            for result in results {
+                finished &= result.map_err(|reason| EikvError::StdError(reason.into()))?;
+            }
+            finished
+        };
+        Ok(finished)
+    }
+
+    // Returns false if the DB is closed or the manual compaction is disabled
+    // before the merge finishes.
+    fn subcompaction(
+        &self,
+        subcompaction: &mut Subcompaction<K, V>,
+        options: &DBOptions,
+        level: usize,
+        is_manual_compaction: bool,
+    ) -> EikvResult<bool> {
+        loop {
+            match subcompaction.merger.merge()? {
                 MergeResult::Full => {
-                    let major_seq = state.major_seqs.len() as u64 + 1;
-                    let major_path = sst_major_tmp_path(&db_path, major_seq)?;
-                    let size_limit = db_options.wal_size_limit * 5_u64.pow(state.level as u32 - 1);
-                    let writer: sst::Writer<K, V> =
-                        sst::Writer::new(&major_path, db_options.clone(), size_limit)?;
-                    state.merger.set_writer(writer)?;
-                    merger_state = Some(state)
+                    let major_seq = subcompaction.major_seqs.len() as u64 + 1;
+                    let major_path =
+                        sst_major_tmp_path(&self.db_path, subcompaction.compaction_seq, major_seq)?;
+                    let size_limit = self.db_options.wal_size_limit * 5_u64.pow(level as u32 - 1);
+                    let writer: sst::Writer<K, V> = sst::Writer::new(
+                        &major_path,
+                        options.clone(),
+                        size_limit,
+                        IOPriority::Low,
+                    )?;
+                    subcompaction.merger.set_writer(writer)?;
+                    subcompaction.major_seqs.push(major_seq);
                 }
-                MergeResult::Finish => {
-                    state.merger.finish()?;
-
-                    let mut manifest = manifest.lock().unwrap();
-                    for major_seq in state.major_seqs {
-                        let major_path = sst_major_tmp_path(&db_path, major_seq)?;
-                        let file_seq = manifest.alloc_sst(state.level);
-                        let sst_path = sst_path(&db_path, state.level, file_seq)?;
-                        rename(&major_path, &sst_path)?;
-                        let sst_meta = SstMeta::new(&sst_path, db_options.block_size)?;
-                        manifest.set_sst_meta(state.level, file_seq, sst_meta);
+                MergeResult::Finish => return Ok(true),
+                MergeResult::Timeout => {
+                    // The merge is kept in memory while parked by a pause.
+                    if !self.checkpoint()
+                        || is_manual_compaction
+                            && self.manual_compaction_disabled.load(Ordering::Acquire)
+                    {
+                        return Ok(false);
                     }
+                    self.flush_queued()?;
+                }
+            }
+        }
+    }
 
-                    for file_seq in &state.this_level_file_seq {
-                        manifest.remove_sst(state.level - 1, *file_seq);
-                    }
-                    for file_seq in &state.next_level_file_seq {
-                        manifest.remove_sst(state.level, *file_seq);
-                    }
-                    manifest.dump(&db_path)?;
+    // The inputs are left as they are and the outputs are removed.
+    fn abandon_major_compaction(&self, state: MergerState<K, V>) -> EikvResult<()> {
+        let mut major_paths = vec![];
+        for subcompaction in state.subcompactions {
+            drop(subcompaction.merger);
+            for major_seq in subcompaction.major_seqs {
+                major_paths.push(sst_major_tmp_path(
+                    &self.db_path,
+                    subcompaction.compaction_seq,
+                    major_seq,
+                )?);
+            }
+        }
+        self.discard_major_compaction(
+            state.cf_id,
+            &state.this_level_file_seq,
+            &state.next_level_file_seq,
+            major_paths,
+        )
+    }
 
-                    for file_seq in state.this_level_file_seq {
-                        let sst_path = sst_path(&db_path, state.level - 1, file_seq)?;
-                        remove_file(sst_path).unwrap();
-                    }
-                    for file_seq in state.next_level_file_seq {
-                        let sst_path = sst_path(&db_path, state.level, file_seq)?;
-                        remove_file(sst_path).unwrap();
-                    }
+    // The inputs are unmarked even if an output can't be removed, a file
+    // left behind is removed as an orphan.
+    fn discard_major_compaction(
+        &self,
+        cf_id: u32,
+        this_level_file_seq: &[u64],
+        next_level_file_seq: &[u64],
+        major_paths: Vec<String>,
+    ) -> EikvResult<()> {
+        let mut manifest = self.manifest.lock().unwrap();
+        manifest.unmark_being_compacted(cf_id, this_level_file_seq);
+        manifest.unmark_being_compacted(cf_id, next_level_file_seq);
+        drop(manifest);
+        self.mem_db.notify_background_progress();
+        for major_path in major_paths {
+            remove_file(major_path)?;
+        }
+        Ok(())
+    }
 
-                    merger_state = None
+    // The outputs of all the subcompactions are installed by one manifest
+    // dump.
+    fn install_major_compaction(&self, state: MergerState<K, V>) -> EikvResult<()> {
+        let db_path = &self.db_path;
+        let mut major_paths = vec![];
+        let mut result = Ok(());
+        for subcompaction in state.subcompactions {
+            let compaction_seq = subcompaction.compaction_seq;
+            let mut major_seqs = subcompaction.major_seqs;
+            match subcompaction.merger.finish() {
+                Ok(true) => (),
+                // The last output is empty.
+                Ok(false) => {
+                    let major_seq = major_seqs.pop().unwrap();
+                    let major_path = sst_major_tmp_path(db_path, compaction_seq, major_seq)?;
+                    if let Err(err) = remove_file(&major_path) {
+                        major_paths.push(major_path);
+                        result = result.and(Err(err.into()));
+                    }
                 }
-                MergeResult::Timeout => merger_state = Some(state),
+                Err(err) => result = result.and(Err(err)),
+            }
+            for major_seq in major_seqs {
+                major_paths.push(sst_major_tmp_path(db_path, compaction_seq, major_seq)?);
             }
-            continue;
         }
-
-        if mem_db.wait_immut() {
-            merger_state = get_merger(
-                manifest.clone(),
-                &db_path,
-                db_options.clone(),
-                db_options.wal_size_limit,
+        if let Err(err) = result {
+            self.discard_major_compaction(
+                state.cf_id,
+                &state.this_level_file_seq,
+                &state.next_level_file_seq,
+                major_paths,
             )?;
+            return Err(err);
         }
-    }
 
-    Ok(())
+        let mut manifest = self.manifest.lock().unwrap();
+        // The inputs are gone with a column family dropped during the merge.
+        if !manifest.has_cf(state.cf_id) {
+            for major_path in major_paths {
+                remove_file(major_path)?;
+            }
+            return Ok(());
+        }
+        for major_path in major_paths {
+            let file_seq = manifest.alloc_sst(state.cf_id, state.level);
+            let sst_path = sst_path(db_path, file_seq)?;
+            rename(&major_path, &sst_path)?;
+            let sst_meta = SstMeta::new(&sst_path, state.options.block_size)?;
+            manifest.set_sst_meta(state.cf_id, state.level, file_seq, sst_meta);
+        }
+
+        for file_seq in &state.this_level_file_seq {
+            manifest.remove_sst(state.cf_id, state.level - 1, *file_seq);
+        }
+        for file_seq in &state.next_level_file_seq {
+            manifest.remove_sst(state.cf_id, state.level, *file_seq);
+        }
+        manifest.unmark_being_compacted(state.cf_id, &state.this_level_file_seq);
+        manifest.unmark_being_compacted(state.cf_id, &state.next_level_file_seq);
+        manifest.dump(db_path)?;
+
+        for file_seq in state.this_level_file_seq {
+            let sst_path = sst_path(db_path, file_seq)?;
+            remove_file(sst_path)?;
+        }
+        for file_seq in state.next_level_file_seq {
+            let sst_path = sst_path(db_path, file_seq)?;
+            remove_file(sst_path)?;
+        }
+        drop(manifest);
+        self.mem_db.notify_background_progress();
+
+        Ok(())
+    }
 }
@@ -0,0 +1,73 @@
+use super::DB;
+use crate::{
+    mem_db::{ReadSeq, WriteOp},
+    wal::Precondition,
+    EikvError, EikvResult, Key, Value, WriteBatchWithIndex,
+};
+use std::collections::{btree_map, BTreeMap};
+
+pub struct OptimisticTransaction<'a, K: Key + 'static, V: Value + 'static> {
+    db: &'a DB<K, V>,
+    write_batch: WriteBatchWithIndex<K, V>,
+    tracked_seqs: BTreeMap<K, ReadSeq<'a, K, V>>,
+}
+
+impl<'a, K: Key + 'static, V: Value + 'static> OptimisticTransaction<'a, K, V> {
+    pub(super) fn new(db: &'a DB<K, V>) -> OptimisticTransaction<'a, K, V> {
+        OptimisticTransaction {
+            db,
+            write_batch: WriteBatchWithIndex::new(),
+            tracked_seqs: BTreeMap::new(),
+        }
+    }
+
+    // Remembers the last sequence visible when the key was first touched, a
+    // version of the key newer than it fails the commit. Compactions leave the
+    // versions it sees alone until the transaction ends.
+    fn track(&mut self, key: &K) {
+        if let btree_map::Entry::Vacant(entry) = self.tracked_seqs.entry(key.clone()) {
+            entry.insert(self.db.read_seq());
+        }
+    }
+
+    pub fn get(&mut self, key: K) -> EikvResult<Option<V>> {
+        self.track(&key);
+        self.write_batch.get_from_batch_and_db(self.db, key)
+    }
+
+    pub fn put(&mut self, key: K, value: V) -> &mut Self {
+        self.track(&key);
+        self.write_batch.put(key, value);
+        self
+    }
+
+    pub fn delete(&mut self, key: K) -> &mut Self {
+        self.track(&key);
+        self.write_batch.delete(key);
+        self
+    }
+
+    // The tracked sequences stay live until the preconditions are checked.
+    pub fn commit(self) -> EikvResult<()> {
+        let preconditions = self
+            .tracked_seqs
+            .iter()
+            .map(|(key, read_seq)| Precondition::Unchanged {
+                key: key.clone(),
+                seq: read_seq.seq(),
+            })
+            .collect();
+        let write_op = WriteOp {
+            write_batch: self.write_batch.into_write_batch(),
+            preconditions,
+        };
+        if self.db.write_op(write_op)? {
+            Ok(())
+        } else {
+            let reason = "a key of the transaction was written after it was read".to_owned();
+            Err(EikvError::Conflict(reason))
+        }
+    }
+
+    pub fn rollback(self) {}
+}
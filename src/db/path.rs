@@ -47,21 +47,32 @@ pub(crate) fn sst_tmp_dir_path(db_path: &str) -> EikvResult<String> {
     join_path(&sst_dir_path, "tmp")
 }
 
-pub(crate) fn sst_minor_tmp_path(db_path: &str) -> EikvResult<String> {
+pub(crate) fn sst_minor_tmp_path(db_path: &str, flush_seq: u64, cf_id: u32) -> EikvResult<String> {
     let sst_tmp_dir_path = sst_tmp_dir_path(db_path)?;
-    join_path(&sst_tmp_dir_path, "minor.sst")
+    let file_name = format!("minor_{:06}_{}.sst", flush_seq, cf_id);
+    join_path(&sst_tmp_dir_path, &file_name)
 }
 
-pub(crate) fn sst_major_tmp_path(db_path: &str, major_seq: u64) -> EikvResult<String> {
+pub(crate) fn sst_major_tmp_path(
+    db_path: &str,
+    compaction_seq: u64,
+    major_seq: u64,
+) -> EikvResult<String> {
     let sst_tmp_dir_path = sst_tmp_dir_path(db_path)?;
-    let file_name = format!("major_{:06}.sst", major_seq);
+    let file_name = format!("major_{:06}_{:06}.sst", compaction_seq, major_seq);
     join_path(&sst_tmp_dir_path, &file_name)
 }
 
-pub(crate) fn sst_path(db_path: &str, level: usize, file_seq: u64) -> EikvResult<String> {
-    let sst_level_dir_path = join_path(&sst_dir_path(db_path)?, &level.to_string())?;
+// The level of an SST is only kept in the manifest.
+pub(crate) fn sst_path(db_path: &str, file_seq: u64) -> EikvResult<String> {
+    let file_name = format!("{:06}.sst", file_seq);
+    join_path(&sst_dir_path(db_path)?, &file_name)
+}
+
+// SSTs used to be kept in a dir of their level.
+pub(crate) fn sst_legacy_path(db_path: &str, level: usize, file_seq: u64) -> EikvResult<String> {
     let file_name = format!("{:06}.sst", file_seq);
-    join_path(&sst_level_dir_path, &file_name)
+    join_path(&sst_level_dir_path(db_path, level)?, &file_name)
 }
 
 pub(crate) fn wal_dir_path(db_path: &str) -> EikvResult<String> {
@@ -79,7 +90,9 @@ mod tests {
     use super::lock_file_path;
     use super::manifest_dir_path;
     use super::sst_dir_path;
+    use super::sst_legacy_path;
     use super::sst_level_dir_path;
+    use super::sst_path;
     use super::wal_dir_path;
 
     #[test]
@@ -114,6 +127,22 @@ mod tests {
         assert_eq!(want, res);
     }
 
+    #[test]
+    fn test_sst_path() {
+        let db_path = "/tmp/eikv";
+        let want = "/tmp/eikv/sst/000012.sst";
+        let res = sst_path(db_path, 12).unwrap();
+        assert_eq!(want, res);
+    }
+
+    #[test]
+    fn test_sst_legacy_path() {
+        let db_path = "/tmp/eikv";
+        let want = "/tmp/eikv/sst/3/000012.sst";
+        let res = sst_legacy_path(db_path, 3, 12).unwrap();
+        assert_eq!(want, res);
+    }
+
     #[test]
     fn test_wal_dir_path() {
         let db_path = "/tmp/eikv";
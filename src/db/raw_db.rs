@@ -0,0 +1,46 @@
+use super::{DBIterator, DB};
+use crate::{DBOptions, EikvResult, WriteBatch};
+
+// A DB of plain bytes, keys and values are taken as slices.
+pub struct RawDB {
+    db: DB<Vec<u8>, Vec<u8>>,
+}
+
+impl RawDB {
+    pub fn new(path: &str, options: DBOptions) -> EikvResult<RawDB> {
+        let db = DB::new(path, options)?;
+        Ok(RawDB { db })
+    }
+
+    pub fn db(&self) -> &DB<Vec<u8>, Vec<u8>> {
+        &self.db
+    }
+
+    pub fn write(&self, write_batch: WriteBatch<Vec<u8>, Vec<u8>>) -> EikvResult<()> {
+        self.db.write(write_batch)
+    }
+
+    pub fn put(&self, key: &[u8], value: &[u8]) -> EikvResult<()> {
+        self.db.put(key.to_vec(), value.to_vec())
+    }
+
+    pub fn delete(&self, key: &[u8]) -> EikvResult<()> {
+        self.db.delete(key.to_vec())
+    }
+
+    pub fn delete_range(&self, start: &[u8], end: &[u8]) -> EikvResult<()> {
+        self.db.delete_range(start.to_vec(), end.to_vec())
+    }
+
+    pub fn merge(&self, key: &[u8], operand: &[u8]) -> EikvResult<()> {
+        self.db.merge(key.to_vec(), operand.to_vec())
+    }
+
+    pub fn get(&self, key: &[u8]) -> EikvResult<Option<Vec<u8>>> {
+        self.db.get(key.to_vec())
+    }
+
+    pub fn iter(&self) -> EikvResult<DBIterator<Vec<u8>, Vec<u8>>> {
+        self.db.iter()
+    }
+}
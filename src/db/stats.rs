@@ -0,0 +1,13 @@
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DBStats {
+    // The write groups delayed while flushes or level 1 compactions fall
+    // behind, and the time spent delayed.
+    pub write_slowdowns: u64,
+    pub write_slowdown_duration: Duration,
+    // The write groups stopped until flushes or level 1 compactions catch up,
+    // and the time spent stopped.
+    pub write_stops: u64,
+    pub write_stop_duration: Duration,
+}
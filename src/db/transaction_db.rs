@@ -0,0 +1,219 @@
+use super::{lock_manager::LockManager, DB};
+use crate::{
+    mem_db::WriteOp, wal::Marker, DBOptions, EikvError, EikvResult, Key, Value, WriteBatch,
+    WriteBatchWithIndex,
+};
+use std::{
+    collections::BTreeSet,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+#[derive(Clone)]
+pub struct TransactionDBOptions {
+    pub lock_stripes: usize,
+    pub lock_timeout: Duration,
+}
+
+impl Default for TransactionDBOptions {
+    fn default() -> Self {
+        Self {
+            lock_stripes: 16,
+            lock_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+pub struct TransactionDB<K: Key + 'static, V: Value + 'static> {
+    db: DB<K, V>,
+    lock_manager: LockManager<K>,
+    next_txn_id: AtomicU64,
+    options: TransactionDBOptions,
+    recovered: Mutex<Vec<(String, WriteBatch<K, V>)>>,
+}
+
+impl<K: Key + 'static, V: Value + 'static> TransactionDB<K, V> {
+    pub fn new(
+        path: &str,
+        options: DBOptions,
+        txn_db_options: TransactionDBOptions,
+    ) -> EikvResult<TransactionDB<K, V>> {
+        let db = DB::new(path, options)?;
+        let recovered = db.prepared();
+        let txn_db = TransactionDB {
+            db,
+            lock_manager: LockManager::new(txn_db_options.lock_stripes),
+            next_txn_id: AtomicU64::new(1),
+            options: txn_db_options,
+            recovered: Mutex::new(recovered),
+        };
+        Ok(txn_db)
+    }
+
+    pub fn db(&self) -> &DB<K, V> {
+        &self.db
+    }
+
+    pub fn get(&self, key: K) -> EikvResult<Option<V>> {
+        self.db.get(key)
+    }
+
+    pub fn put(&self, key: K, value: V) -> EikvResult<()> {
+        let mut txn = self.begin_transaction();
+        txn.put(key, value)?;
+        txn.commit()
+    }
+
+    pub fn delete(&self, key: K) -> EikvResult<()> {
+        let mut txn = self.begin_transaction();
+        txn.delete(key)?;
+        txn.commit()
+    }
+
+    pub fn begin_transaction(&self) -> Transaction<'_, K, V> {
+        let id = self.next_txn_id.fetch_add(1, Ordering::Relaxed);
+        Transaction {
+            txn_db: self,
+            id,
+            name: None,
+            write_batch: WriteBatchWithIndex::new(),
+            locked_keys: BTreeSet::new(),
+            prepared: false,
+        }
+    }
+
+    // Hands out the transactions found prepared in the WAL when the DB was
+    // opened, each has to be committed or rolled back.
+    pub fn recovered_transactions(&self) -> EikvResult<Vec<Transaction<'_, K, V>>> {
+        let recovered: Vec<(String, WriteBatch<K, V>)> =
+            self.recovered.lock().unwrap().drain(..).collect();
+        let mut txns = Vec::with_capacity(recovered.len());
+        for (name, write_batch) in recovered {
+            let mut txn = self.begin_transaction();
+            for entry in write_batch.entries() {
+                txn.lock(&entry.key)?;
+            }
+            txn.name = Some(name);
+            txn.write_batch = WriteBatchWithIndex::try_from(write_batch)?;
+            txn.prepared = true;
+            txns.push(txn);
+        }
+        Ok(txns)
+    }
+}
+
+pub struct Transaction<'a, K: Key + 'static, V: Value + 'static> {
+    txn_db: &'a TransactionDB<K, V>,
+    id: u64,
+    name: Option<String>,
+    write_batch: WriteBatchWithIndex<K, V>,
+    locked_keys: BTreeSet<K>,
+    prepared: bool,
+}
+
+impl<K: Key + 'static, V: Value + 'static> Transaction<'_, K, V> {
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn set_name(&mut self, name: String) -> EikvResult<()> {
+        if self.prepared {
+            let reason = "can't rename a prepared transaction".to_owned();
+            return Err(EikvError::TransactionError(reason));
+        }
+        self.name = Some(name);
+        Ok(())
+    }
+
+    fn lock(&mut self, key: &K) -> EikvResult<()> {
+        if self.locked_keys.contains(key) {
+            return Ok(());
+        }
+        let timeout = self.txn_db.options.lock_timeout;
+        self.txn_db.lock_manager.lock(self.id, key, timeout)?;
+        self.locked_keys.insert(key.clone());
+        Ok(())
+    }
+
+    fn check_not_prepared(&self) -> EikvResult<()> {
+        if self.prepared {
+            let reason = "the transaction is already prepared".to_owned();
+            return Err(EikvError::TransactionError(reason));
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: K) -> EikvResult<Option<V>> {
+        self.write_batch.get_from_batch_and_db(&self.txn_db.db, key)
+    }
+
+    pub fn get_for_update(&mut self, key: K) -> EikvResult<Option<V>> {
+        self.lock(&key)?;
+        self.get(key)
+    }
+
+    pub fn put(&mut self, key: K, value: V) -> EikvResult<()> {
+        self.check_not_prepared()?;
+        self.lock(&key)?;
+        self.write_batch.put(key, value);
+        Ok(())
+    }
+
+    pub fn delete(&mut self, key: K) -> EikvResult<()> {
+        self.check_not_prepared()?;
+        self.lock(&key)?;
+        self.write_batch.delete(key);
+        Ok(())
+    }
+
+    pub fn prepare(&mut self) -> EikvResult<()> {
+        self.check_not_prepared()?;
+        let name = match &self.name {
+            Some(name) => name.clone(),
+            None => {
+                let reason = "a transaction must be named before it's prepared".to_owned();
+                return Err(EikvError::TransactionError(reason));
+            }
+        };
+
+        let mut write_batch = self.write_batch.write_batch().clone();
+        write_batch.add_marker(Marker::Prepare(name.clone()));
+        if !self.txn_db.db.write_op(WriteOp::new(write_batch))? {
+            let reason = format!("a transaction named {} is already prepared", name);
+            return Err(EikvError::TransactionError(reason));
+        }
+        self.prepared = true;
+        Ok(())
+    }
+
+    pub fn commit(self) -> EikvResult<()> {
+        let mut write_batch = self.write_batch.write_batch().clone();
+        if self.prepared {
+            write_batch.add_marker(Marker::Commit(self.name.clone().unwrap()));
+        } else if write_batch.is_empty() {
+            return Ok(());
+        }
+        self.txn_db.db.write_op(WriteOp::new(write_batch))?;
+        Ok(())
+    }
+
+    pub fn rollback(self) -> EikvResult<()> {
+        if self.prepared {
+            let mut write_batch = WriteBatch::new();
+            write_batch.add_marker(Marker::Rollback(self.name.clone().unwrap()));
+            self.txn_db.db.write_op(WriteOp::new(write_batch))?;
+        }
+        Ok(())
+    }
+}
+
+impl<K: Key + 'static, V: Value + 'static> Drop for Transaction<'_, K, V> {
+    fn drop(&mut self) {
+        for key in &self.locked_keys {
+            let _ = self.txn_db.lock_manager.unlock(self.id, key);
+        }
+    }
+}
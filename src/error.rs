@@ -15,6 +15,28 @@ pub enum EikvError {
     PathError(String),
     #[error("manifest error: {0}")]
     ManifestError(String),
+    #[error("save point error: {0}")]
+    SavePointError(String),
+    #[error("merge error: {0}")]
+    MergeError(String),
+    #[error("write error: {0}")]
+    WriteError(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("lock timeout: {0}")]
+    LockTimeout(String),
+    #[error("deadlock: {0}")]
+    Deadlock(String),
+    #[error("transaction error: {0}")]
+    TransactionError(String),
+    #[error("column family error: {0}")]
+    ColumnFamilyError(String),
+    #[error("compaction cancelled: {0}")]
+    CompactionCancelled(String),
+    #[error("comparator error: {0}")]
+    ComparatorError(String),
+    #[error("background error: {0}")]
+    BackgroundError(String),
 }
 
 pub type EikvResult<T> = Result<T, EikvError>;
@@ -1,3 +1,5 @@
+#[cfg(feature = "serde")]
+mod codec;
 mod db;
 mod error;
 pub mod limit;
@@ -7,8 +9,17 @@ mod sst;
 mod util;
 mod wal;
 
-pub use db::{DBOptions, DB};
+#[cfg(feature = "serde")]
+pub use codec::{Bincode, Cbor, Json, OrderedKey, SerdeFormat, SerdeValue};
+pub use db::{
+    ColumnFamilyHandle, DBIterator, DBOptions, DBStats, OptimisticTransaction, RawDB, Transaction,
+    TransactionDB, TransactionDBOptions, DB,
+};
 pub use error::{EikvError, EikvResult};
-pub use model::{Key, Value};
-pub use sst::{Compressor, Filter, FilterFactory};
-pub use wal::WriteBatch;
+pub use mem_db::{MemTableFactory, WriteBufferManager};
+pub use model::{
+    BytewiseComparator, CompactionDecision, CompactionFilter, CompactionFilterContext, Comparator,
+    Key, MergeOperator, Value,
+};
+pub use sst::{Compressor, Filter, FilterFactory, RateLimiter};
+pub use wal::{WriteBatch, WriteBatchHandler, WriteBatchWithIndex, WriteBatchWithIndexIterator};
@@ -1,90 +1,300 @@
+use super::skiplist::SkipList;
 use crate::{
-    model::{Entry, Manifest},
+    model::{collect_versions, max_covering_seq, Entry, Op, RangeTombstone},
     sst::Writer,
-    EikvResult, Key, Value, WriteBatch,
+    DBOptions, EikvResult, Key, Value,
 };
 use std::{
-    collections::BTreeSet,
-    mem,
-    sync::{Arc, Condvar, Mutex, RwLock},
+    cmp::max,
+    collections::{BTreeSet, VecDeque},
+    mem::size_of,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
 };
 
-pub(crate) type Table<K, V> = BTreeSet<Entry<K, V>>;
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum MemTableFactory {
+    // Entries are kept decoded in a BTreeSet behind a lock.
+    #[default]
+    BTree,
+    // Entries are kept encoded in an arena, reads don't take locks.
+    SkipList,
+}
 
-pub(super) struct MemTable<K: Key, V: Value> {
-    mut_table: Mutex<Table<K, V>>,
-    immut_table: RwLock<Arc<Table<K, V>>>,
+// The sorted entries and the range tombstones of a memtable.
+pub(super) type TableSnapshot<K, V> = (Vec<Entry<K, V>>, Vec<RangeTombstone<K>>);
+
+enum Entries<K: Key, V: Value> {
+    BTree(RwLock<BTreeSet<Entry<K, V>>>),
+    SkipList(SkipList),
 }
 
-impl<K: Key, V: Value> MemTable<K, V> {
-    pub(super) fn new() -> MemTable<K, V> {
-        MemTable {
-            mut_table: Mutex::new(Table::new()),
-            immut_table: RwLock::new(Arc::new(Table::new())),
+pub(crate) struct Table<K: Key, V: Value> {
+    entries: Entries<K, V>,
+    range_tombstones: RwLock<Vec<RangeTombstone<K>>>,
+    // The bytes taken by the entries of the BTreeSet, skiplists count their
+    // arenas.
+    memory_usage: AtomicUsize,
+}
+
+impl<K: Key, V: Value> Table<K, V> {
+    pub(crate) fn new(options: &DBOptions) -> Table<K, V> {
+        let entries = match options.memtable_factory {
+            MemTableFactory::BTree => Entries::BTree(RwLock::new(BTreeSet::new())),
+            MemTableFactory::SkipList => {
+                Entries::SkipList(SkipList::new(options.comparator.clone()))
+            }
+        };
+        Table {
+            entries,
+            range_tombstones: RwLock::new(vec![]),
+            memory_usage: AtomicUsize::new(0),
         }
     }
 
-    pub(super) fn update(&self, write_batch: &WriteBatch<K, V>) {
-        let mut guard = self.mut_table.lock().unwrap();
-        for entry in write_batch.entries() {
-            guard.insert(entry.clone());
+    pub(crate) fn insert(&self, entry: Entry<K, V>) -> EikvResult<()> {
+        let mut buf = vec![];
+        entry.encode(&mut buf)?;
+        match entry.op {
+            Op::DeleteRange(end) => {
+                let range_tombstone = RangeTombstone {
+                    start: entry.key,
+                    end,
+                    seq: entry.seq,
+                };
+                self.memory_usage.fetch_add(buf.len(), Ordering::Relaxed);
+                self.range_tombstones.write().unwrap().push(range_tombstone);
+            }
+            _ => match &self.entries {
+                Entries::BTree(entries) => {
+                    let size = buf.len() + size_of::<Entry<K, V>>();
+                    self.memory_usage.fetch_add(size, Ordering::Relaxed);
+                    entries.write().unwrap().insert(entry);
+                }
+                Entries::SkipList(skiplist) => {
+                    let (key_off, key_len) = Entry::<K, V>::encoded_key_range(&buf)?;
+                    skiplist.insert(&buf, key_off, key_len, entry.seq);
+                }
+            },
         }
+        Ok(())
     }
 
-    pub(super) fn freeze(&self) {
-        let mut mut_table = self.mut_table.lock().unwrap();
-        let mut immut_table = self.immut_table.write().unwrap();
-        let mut tmp_table = Table::new();
-        mem::swap(&mut *mut_table, &mut tmp_table);
-        *immut_table = Arc::new(tmp_table);
-    }
-
-    pub(super) fn get(&self, key: K, seq_guard: u64) -> Option<Entry<K, V>> {
-        let max_entry = Entry {
-            key,
-            seq: seq_guard,
-            value: None,
+    pub(crate) fn memory_usage(&self) -> usize {
+        let arena_usage = match &self.entries {
+            Entries::BTree(_) => 0,
+            Entries::SkipList(skiplist) => skiplist.memory_usage(),
+        };
+        self.memory_usage.load(Ordering::Relaxed) + arena_usage
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        let entries_empty = match &self.entries {
+            Entries::BTree(entries) => entries.read().unwrap().is_empty(),
+            Entries::SkipList(skiplist) => skiplist.is_empty(),
         };
+        entries_empty && self.range_tombstones.read().unwrap().is_empty()
+    }
 
-        {
-            let mut_table = self.mut_table.lock().unwrap();
-            if let Some(entry) = mut_table.range(..=&max_entry).next_back() {
-                if entry.key == max_entry.key {
-                    return Some(entry.clone());
+    fn get(&self, key: &K, seq_guard: u64, chain: &mut Vec<Entry<K, V>>) -> EikvResult<bool> {
+        let tombstone_seq = {
+            let range_tombstones = self.range_tombstones.read().unwrap();
+            max_covering_seq(range_tombstones.iter(), key, seq_guard)
+        };
+        match &self.entries {
+            Entries::BTree(entries) => {
+                let min_entry = Entry {
+                    key: key.clone(),
+                    seq: 0,
+                    op: Op::Delete,
+                };
+                let max_entry = Entry {
+                    key: key.clone(),
+                    seq: seq_guard,
+                    op: Op::Delete,
+                };
+                let entries = entries.read().unwrap();
+                let versions = entries.range(&min_entry..=&max_entry).rev();
+                Ok(collect_versions(key, versions, tombstone_seq, chain))
+            }
+            Entries::SkipList(skiplist) => {
+                let mut versions = vec![];
+                for buf in skiplist.get(&key.to_bytes()?, seq_guard) {
+                    let (entry, _) = Entry::decode(buf)?;
+                    versions.push(entry);
                 }
+                Ok(collect_versions(key, &versions, tombstone_seq, chain))
             }
         }
+    }
 
-        let immut_table = { self.immut_table.read().unwrap().clone() };
-        match immut_table.range(..=&max_entry).next_back() {
-            Some(entry) => {
-                if entry.key == max_entry.key {
-                    Some(entry.clone())
-                } else {
-                    None
+    fn latest_seq(&self, key: &K) -> EikvResult<Option<u64>> {
+        let entry_seq = match &self.entries {
+            Entries::BTree(entries) => {
+                let max_entry = Entry {
+                    key: key.clone(),
+                    seq: u64::MAX,
+                    op: Op::Delete,
+                };
+                entries
+                    .read()
+                    .unwrap()
+                    .range(..=&max_entry)
+                    .next_back()
+                    .filter(|entry| entry.key == *key)
+                    .map(|entry| entry.seq)
+            }
+            Entries::SkipList(skiplist) => skiplist.latest_seq(&key.to_bytes()?),
+        };
+        let range_tombstones = self.range_tombstones.read().unwrap();
+        let tombstone_seq = max_covering_seq(range_tombstones.iter(), key, u64::MAX);
+        Ok(max(entry_seq, tombstone_seq))
+    }
+
+    // Returns the entries in order.
+    fn entries(&self) -> EikvResult<Vec<Entry<K, V>>> {
+        match &self.entries {
+            Entries::BTree(entries) => Ok(entries.read().unwrap().iter().cloned().collect()),
+            Entries::SkipList(skiplist) => {
+                let mut entries = vec![];
+                for buf in skiplist.iter() {
+                    let (entry, _) = Entry::decode(buf)?;
+                    entries.push(entry);
                 }
+                Ok(entries)
             }
-            None => None,
         }
     }
 
-    pub(super) fn recover_mut_table(&mut self, table: Table<K, V>) {
-        let mut guard = self.mut_table.lock().unwrap();
-        *guard = table;
+    fn range_tombstones(&self) -> Vec<RangeTombstone<K>> {
+        self.range_tombstones.read().unwrap().clone()
     }
 
-    pub(super) fn recover_immut_table(&mut self, table: Table<K, V>) {
-        let mut guard = self.immut_table.write().unwrap();
-        *guard = Arc::new(table);
+    pub(crate) fn dump(&self, mut writer: Writer<K, V>) -> EikvResult<()> {
+        for entry in self.entries()? {
+            writer.append(entry)?;
+        }
+        for range_tombstone in self.range_tombstones() {
+            writer.add_range_tombstone(range_tombstone);
+        }
+        writer.finish()
     }
+}
+
+pub(super) struct MemTable<K: Key, V: Value> {
+    options: DBOptions,
+    mut_table: RwLock<Arc<Table<K, V>>>,
+    // The frozen tables queued for flush, oldest first.
+    immut_tables: RwLock<VecDeque<Arc<Table<K, V>>>>,
+}
 
-    pub(super) fn dump(&self, mut writer: Writer<K, V>) -> EikvResult<()> {
-        let table = self.immut_table.read().unwrap().clone();
-        for entry in table.iter() {
-            writer.append(entry.clone())?;
+impl<K: Key, V: Value> MemTable<K, V> {
+    pub(super) fn new(options: DBOptions) -> MemTable<K, V> {
+        MemTable {
+            mut_table: RwLock::new(Arc::new(Table::new(&options))),
+            immut_tables: RwLock::new(VecDeque::new()),
+            options,
+        }
+    }
+
+    pub(super) fn update(&self, entries: Vec<Entry<K, V>>) -> EikvResult<()> {
+        let mut_table = self.mut_table.read().unwrap().clone();
+        for entry in entries {
+            mut_table.insert(entry)?;
         }
-        writer.finish()?;
-        *self.immut_table.write().unwrap() = Arc::new(Table::new());
         Ok(())
     }
+
+    pub(super) fn freeze(&self) {
+        let mut mut_table = self.mut_table.write().unwrap();
+        let mut immut_tables = self.immut_tables.write().unwrap();
+        let table = Arc::new(Table::new(&self.options));
+        immut_tables.push_back(std::mem::replace(&mut *mut_table, table));
+    }
+
+    fn immut_tables(&self) -> Vec<Arc<Table<K, V>>> {
+        self.immut_tables.read().unwrap().iter().cloned().collect()
+    }
+
+    pub(super) fn get(
+        &self,
+        key: &K,
+        seq_guard: u64,
+        chain: &mut Vec<Entry<K, V>>,
+    ) -> EikvResult<bool> {
+        let mut_table = self.mut_table.read().unwrap().clone();
+        if mut_table.get(key, seq_guard, chain)? {
+            return Ok(true);
+        }
+
+        for immut_table in self.immut_tables().iter().rev() {
+            if immut_table.get(key, seq_guard, chain)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub(super) fn latest_seq(&self, key: &K) -> EikvResult<Option<u64>> {
+        let mut_table = self.mut_table.read().unwrap().clone();
+        let mut latest_seq = mut_table.latest_seq(key)?;
+        for immut_table in self.immut_tables() {
+            latest_seq = max(latest_seq, immut_table.latest_seq(key)?);
+        }
+        Ok(latest_seq)
+    }
+
+    pub(super) fn snapshot(&self) -> EikvResult<TableSnapshot<K, V>> {
+        let mut_table = self.mut_table.read().unwrap().clone();
+        let immut_tables = self.immut_tables();
+        let mut entries = mut_table.entries()?;
+        let mut range_tombstones = mut_table.range_tombstones();
+        for immut_table in immut_tables {
+            entries.extend(immut_table.entries()?);
+            range_tombstones.extend(immut_table.range_tombstones());
+        }
+        entries.sort_unstable();
+        Ok((entries, range_tombstones))
+    }
+
+    pub(super) fn recover(
+        options: DBOptions,
+        mut_table: Table<K, V>,
+        immut_tables: Vec<Table<K, V>>,
+    ) -> MemTable<K, V> {
+        MemTable {
+            options,
+            mut_table: RwLock::new(Arc::new(mut_table)),
+            immut_tables: RwLock::new(immut_tables.into_iter().map(Arc::new).collect()),
+        }
+    }
+
+    // The frozen table at the index of the flush queue.
+    pub(super) fn immut_table(&self, index: usize) -> Option<Arc<Table<K, V>>> {
+        self.immut_tables.read().unwrap().get(index).cloned()
+    }
+
+    pub(super) fn memory_usage(&self) -> usize {
+        let immut_usage: usize = self
+            .immut_tables()
+            .iter()
+            .map(|table| table.memory_usage())
+            .sum();
+        self.mut_memory_usage() + immut_usage
+    }
+
+    pub(super) fn mut_memory_usage(&self) -> usize {
+        self.mut_table.read().unwrap().memory_usage()
+    }
+
+    pub(super) fn has_mut_entries(&self) -> bool {
+        !self.mut_table.read().unwrap().is_empty()
+    }
+
+    // Drops the oldest frozen table once it's flushed.
+    pub(super) fn pop_immut(&self) {
+        self.immut_tables.write().unwrap().pop_front();
+    }
 }
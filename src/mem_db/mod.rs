@@ -1,102 +1,452 @@
 mod mem_table;
+mod skiplist;
+mod write_buffer_manager;
 mod write_queue;
 
-use self::{mem_table::MemTable, write_queue::WriteQueue};
-use crate::{model::Entry, sst, wal::Writer, DBOptions, EikvResult, Key, Value, WriteBatch};
+pub(crate) use self::write_queue::{Turn, WriteOp};
+pub use self::{mem_table::MemTableFactory, write_buffer_manager::WriteBufferManager};
+use self::{
+    mem_table::{MemTable, TableSnapshot},
+    write_queue::WriteQueue,
+};
+use crate::{model::Entry, wal::Writer, DBOptions, EikvError, EikvResult, Key, Value, WriteBatch};
 pub(crate) use mem_table::Table;
 use std::{
+    collections::{btree_map, BTreeMap, VecDeque},
     mem,
-    sync::{atomic::AtomicU64, Condvar, Mutex},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, RwLock,
+    },
     time::Duration,
 };
 
+// The WALs of the frozen memtables queued for flush, oldest first.
+struct ImmutQueue {
+    wals: VecDeque<Writer>,
+    // The leading WALs whose memtables are being flushed.
+    flushing: usize,
+    // The flush seq of the oldest WAL, flushes are installed in their order.
+    front_seq: u64,
+    // The first error of a flush or a compaction, nothing is written after it.
+    background_error: Option<String>,
+}
+
+// The non-empty frozen memtables of the column families sharing a WAL.
+pub(crate) struct FlushJob<K: Key, V: Value> {
+    pub(crate) seq: u64,
+    pub(crate) tables: Vec<(u32, Arc<Table<K, V>>)>,
+}
+
+// Keeps the sequence a read sees live until dropped.
+pub(crate) struct ReadSeq<'a, K: Key, V: Value> {
+    mem_db: &'a MemDB<K, V>,
+    seq: u64,
+}
+
+impl<K: Key, V: Value> ReadSeq<'_, K, V> {
+    pub(crate) fn seq(&self) -> u64 {
+        self.seq
+    }
+}
+
+impl<K: Key, V: Value> Drop for ReadSeq<'_, K, V> {
+    fn drop(&mut self) {
+        let mut read_seqs = self.mem_db.read_seqs.lock().unwrap();
+        if let btree_map::Entry::Occupied(mut entry) = read_seqs.entry(self.seq) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+}
+
 pub(crate) struct MemDB<K: Key, V: Value> {
-    immut_wal: Mutex<Option<Writer>>,
-    mem_table: MemTable<K, V>,
+    immut_queue: Mutex<ImmutQueue>,
+    // The memtables of the column families by their ids, they all share the
+    // WAL and are frozen together.
+    mem_tables: RwLock<BTreeMap<u32, Arc<MemTable<K, V>>>>,
     mut_wal: Mutex<Writer>,
+    next_seq: AtomicU64,
+    last_seq: AtomicU64,
     options: DBOptions,
+    // The sequences seen by the live reads with their counts.
+    read_seqs: Mutex<BTreeMap<u64, usize>>,
+    // The memory of all the memtables and of the mutable ones reserved in the
+    // write buffer manager.
+    reserved_memory: Mutex<(usize, usize)>,
     write_queue: WriteQueue<K, V>,
-    minor_compaction: Condvar,
+    // Notified when a flush or a compaction finishes.
+    background_progress: Condvar,
     has_immut: Condvar,
 }
 
+impl<K: Key, V: Value> Drop for MemDB<K, V> {
+    fn drop(&mut self) {
+        if let Some(write_buffer_manager) = &self.options.write_buffer_manager {
+            let (reserved, reserved_mutable) = *self.reserved_memory.lock().unwrap();
+            write_buffer_manager.free(reserved);
+            write_buffer_manager.free_mutable(reserved_mutable);
+        }
+    }
+}
+
 impl<K: Key, V: Value> MemDB<K, V> {
-    pub(crate) fn new(options: DBOptions, next_seq: AtomicU64, mut_wal: Writer) -> MemDB<K, V> {
+    pub(crate) fn new(
+        options: DBOptions,
+        next_seq: AtomicU64,
+        mut_wal: Writer,
+        immut_wals: VecDeque<Writer>,
+        cf_options: &BTreeMap<u32, DBOptions>,
+    ) -> MemDB<K, V> {
+        let mut mem_tables = BTreeMap::new();
+        for (cf_id, options) in cf_options {
+            mem_tables.insert(*cf_id, Arc::new(MemTable::new(options.clone())));
+        }
         MemDB {
-            immut_wal: Mutex::new(None),
-            mem_table: MemTable::new(),
+            immut_queue: Mutex::new(ImmutQueue {
+                wals: immut_wals,
+                flushing: 0,
+                front_seq: 0,
+                background_error: None,
+            }),
+            mem_tables: RwLock::new(mem_tables),
             mut_wal: Mutex::new(mut_wal),
+            last_seq: AtomicU64::new(next_seq.load(Ordering::Relaxed) - 1),
+            next_seq,
             options,
-            write_queue: WriteQueue::new(next_seq),
-            minor_compaction: Condvar::new(),
+            read_seqs: Mutex::new(BTreeMap::new()),
+            reserved_memory: Mutex::new((0, 0)),
+            write_queue: WriteQueue::new(),
+            background_progress: Condvar::new(),
             has_immut: Condvar::new(),
         }
     }
 
-    pub(crate) fn get(&self, key: K) -> Option<Entry<K, V>> {
-        self.mem_table.get(key, u64::MAX)
+    fn mem_table(&self, cf_id: u32) -> EikvResult<Arc<MemTable<K, V>>> {
+        match self.mem_tables.read().unwrap().get(&cf_id) {
+            Some(mem_table) => Ok(mem_table.clone()),
+            None => {
+                let reason = format!("column family {} doesn't exist", cf_id);
+                Err(EikvError::ColumnFamilyError(reason))
+            }
+        }
+    }
+
+    // The new memtable gets an empty frozen table for every WAL queued for
+    // flush, so the flushes find the frozen tables of all the column
+    // families at the same index.
+    pub(crate) fn add_cf(&self, cf_id: u32, options: DBOptions) {
+        let immut_queue = self.immut_queue.lock().unwrap();
+        let immut_tables = (0..immut_queue.wals.len())
+            .map(|_| Table::new(&options))
+            .collect();
+        let mem_table = MemTable::recover(options.clone(), Table::new(&options), immut_tables);
+        let mut mem_tables = self.mem_tables.write().unwrap();
+        mem_tables.insert(cf_id, Arc::new(mem_table));
     }
 
-    pub(crate) fn write(&self, write_batch: WriteBatch<K, V>) -> EikvResult<bool> {
-        let write_batch = match self.write_queue.line_up(write_batch) {
-            Some(write_batch) => write_batch,
-            None => return Ok(false),
-        };
+    pub(crate) fn remove_cf(&self, cf_id: u32) {
+        self.mem_tables.write().unwrap().remove(&cf_id);
+        self.sync_memory_usage();
+    }
 
-        self.mem_table.update(&write_batch);
-        self.mut_wal.lock().unwrap().append(write_batch)?;
-        let full = self.mut_wal.lock().unwrap().file_offset()? > self.options.wal_size_limit;
-        Ok(full)
+    pub(crate) fn get(
+        &self,
+        cf_id: u32,
+        key: &K,
+        seq_guard: u64,
+        chain: &mut Vec<Entry<K, V>>,
+    ) -> EikvResult<bool> {
+        self.mem_table(cf_id)?.get(key, seq_guard, chain)
     }
 
-    pub(crate) fn recover_mut_table(&mut self, table: Table<K, V>) {
-        self.mem_table.recover_mut_table(table);
+    pub(crate) fn snapshot(&self, cf_id: u32) -> EikvResult<TableSnapshot<K, V>> {
+        self.mem_table(cf_id)?.snapshot()
     }
 
-    pub(crate) fn recover_immut_table(&mut self, table: Table<K, V>) {
-        self.mem_table.recover_immut_table(table);
+    pub(crate) fn latest_seq(&self, cf_id: u32, key: &K) -> EikvResult<Option<u64>> {
+        self.mem_table(cf_id)?.latest_seq(key)
     }
 
-    pub(crate) fn freeze(&self, mut wal: Writer) {
-        let mut immut_wal = self.immut_wal.lock().unwrap();
-        while immut_wal.is_some() {
-            immut_wal = self.minor_compaction.wait(immut_wal).unwrap();
+    pub(crate) fn last_seq(&self) -> u64 {
+        self.last_seq.load(Ordering::Acquire)
+    }
+
+    // The sequence is read under the lock, so a compaction either sees the
+    // read or started before it.
+    pub(crate) fn read_seq(&self) -> ReadSeq<'_, K, V> {
+        let mut read_seqs = self.read_seqs.lock().unwrap();
+        let seq = self.last_seq();
+        *read_seqs.entry(seq).or_insert(0) += 1;
+        ReadSeq { mem_db: self, seq }
+    }
+
+    // The oldest and the newest sequences seen by live reads.
+    pub(crate) fn read_seq_bounds(&self) -> Option<(u64, u64)> {
+        let read_seqs = self.read_seqs.lock().unwrap();
+        let oldest = *read_seqs.keys().next()?;
+        let newest = *read_seqs.keys().next_back()?;
+        Some((oldest, newest))
+    }
+
+    pub(crate) fn line_up(&self, write_op: WriteOp<K, V>) -> Turn<K, V> {
+        self.write_queue.line_up(write_op)
+    }
+
+    // Assigns sequences to the batch, logs it to the WAL buffer of the group
+    // and moves its entries into the memtable.
+    pub(crate) fn apply(
+        &self,
+        mut write_batch: WriteBatch<K, V>,
+        wal_buf: &mut Vec<u8>,
+    ) -> EikvResult<()> {
+        let wal_len = wal_buf.len();
+        if write_batch.is_empty() {
+            if write_batch.markers().is_empty() {
+                return Ok(());
+            }
+            return write_batch
+                .encode(wal_buf)
+                .inspect_err(|_| wal_buf.truncate(wal_len));
         }
 
-        self.mem_table.freeze();
-        mem::swap(&mut wal, &mut self.mut_wal.lock().unwrap());
-        *immut_wal = Some(wal);
-        self.has_immut.notify_one();
+        // The column families are looked up before the batch is logged, so a
+        // batch writing a dropped one is rejected as a whole.
+        let mem_tables = self.mem_tables.read().unwrap();
+        let mut cf_entries = BTreeMap::new();
+        for (cf_id, _) in write_batch.cf_entries() {
+            if !mem_tables.contains_key(&cf_id) {
+                let reason = format!("column family {} doesn't exist", cf_id);
+                return Err(EikvError::ColumnFamilyError(reason));
+            }
+            cf_entries.entry(cf_id).or_insert_with(Vec::new);
+        }
+
+        let len = write_batch.len() as u64;
+        let start_seq = self.next_seq.fetch_add(len, Ordering::Relaxed);
+        write_batch.set_seqs(start_seq);
+        write_batch
+            .encode(wal_buf)
+            .inspect_err(|_| wal_buf.truncate(wal_len))?;
+        for (cf_id, entry) in write_batch.into_cf_entries() {
+            cf_entries.get_mut(&cf_id).unwrap().push(entry);
+        }
+        for (cf_id, entries) in cf_entries {
+            mem_tables[&cf_id].update(entries)?;
+        }
+        self.last_seq.store(start_seq + len - 1, Ordering::Release);
+        Ok(())
     }
 
-    pub(crate) fn write_finished(&self) {
-        self.write_queue.notify_waiters();
+    pub(crate) fn append_wal(&self, wal_buf: &[u8]) -> EikvResult<bool> {
+        let mut_wal = self.mut_wal.lock().unwrap();
+        mut_wal.append(wal_buf)?;
+        let full = mut_wal.file_offset()? > self.options.wal_size_limit;
+        self.sync_memory_usage();
+        Ok(full || self.memory_full())
     }
 
-    pub(crate) fn has_immut(&self) -> bool {
-        let immut_wal = self.immut_wal.lock().unwrap();
-        immut_wal.is_some()
+    // The memtables of all the column families are frozen together, so they
+    // share the write buffer size of the DB options.
+    fn memory_full(&self) -> bool {
+        let mem_tables = self.mem_tables.read().unwrap();
+        if !mem_tables
+            .values()
+            .any(|mem_table| mem_table.has_mut_entries())
+        {
+            return false;
+        }
+        mutable_memory_usage(&mem_tables) > self.options.write_buffer_size
+            || self
+                .options
+                .write_buffer_manager
+                .as_ref()
+                .is_some_and(|write_buffer_manager| write_buffer_manager.should_flush())
     }
 
-    pub(crate) fn wait_immut(&self) -> bool {
-        let mut immut_wal = self.immut_wal.lock().unwrap();
-        while immut_wal.is_none() {
-            let res = self
-                .has_immut
-                .wait_timeout(immut_wal, Duration::from_secs(1))
-                .unwrap();
+    // Brings the memory reserved in the write buffer manager up to date.
+    fn sync_memory_usage(&self) {
+        let write_buffer_manager = match &self.options.write_buffer_manager {
+            Some(write_buffer_manager) => write_buffer_manager,
+            None => return,
+        };
+        let mut reserved_memory = self.reserved_memory.lock().unwrap();
+        let (reserved, reserved_mutable) = *reserved_memory;
+        let (memory_usage, mutable_usage) = {
+            let mem_tables = self.mem_tables.read().unwrap();
+            let memory_usage = mem_tables
+                .values()
+                .map(|mem_table| mem_table.memory_usage())
+                .sum();
+            (memory_usage, mutable_memory_usage(&mem_tables))
+        };
+        if memory_usage > reserved {
+            write_buffer_manager.reserve(memory_usage - reserved);
+        } else {
+            write_buffer_manager.free(reserved - memory_usage);
+        }
+        if mutable_usage > reserved_mutable {
+            write_buffer_manager.reserve_mutable(mutable_usage - reserved_mutable);
+        } else {
+            write_buffer_manager.free_mutable(reserved_mutable - mutable_usage);
+        }
+        *reserved_memory = (memory_usage, mutable_usage);
+    }
+
+    pub(crate) fn write_finished(
+        &self,
+        results: EikvResult<Vec<EikvResult<bool>>>,
+    ) -> EikvResult<bool> {
+        self.write_queue.finish(results)
+    }
+
+    pub(crate) fn recover_tables(
+        &mut self,
+        cf_id: u32,
+        options: DBOptions,
+        mut_table: Table<K, V>,
+        immut_tables: Vec<Table<K, V>>,
+    ) {
+        let mem_table = MemTable::recover(options, mut_table, immut_tables);
+        self.mem_tables
+            .get_mut()
+            .unwrap()
+            .insert(cf_id, Arc::new(mem_table));
+    }
+
+    pub(crate) fn freeze(&self, mut wal: Writer) {
+        let mut immut_queue = self.immut_queue.lock().unwrap();
+        for mem_table in self.mem_tables.read().unwrap().values() {
+            mem_table.freeze();
+        }
+        mem::swap(&mut wal, &mut self.mut_wal.lock().unwrap());
+        immut_queue.wals.push_back(wal);
+        self.has_immut.notify_all();
+        drop(immut_queue);
+        // The frozen memtables don't count as mutable anymore.
+        self.sync_memory_usage();
+    }
+
+    // The number of frozen memtables queued for flush.
+    pub(crate) fn immut_count(&self) -> usize {
+        self.immut_queue.lock().unwrap().wals.len()
+    }
+
+    // Takes the oldest frozen memtables nobody flushes yet, waiting for them
+    // at most for the timeout.
+    pub(crate) fn claim_flush(&self, timeout: Duration) -> Option<FlushJob<K, V>> {
+        let mut immut_queue = self.immut_queue.lock().unwrap();
+        while immut_queue.flushing == immut_queue.wals.len() {
+            if timeout.is_zero() {
+                return None;
+            }
+            let res = self.has_immut.wait_timeout(immut_queue, timeout).unwrap();
             if res.1.timed_out() {
-                return true;
+                return None;
             }
-            immut_wal = res.0;
+            immut_queue = res.0;
         }
-        false
+
+        let index = immut_queue.flushing;
+        immut_queue.flushing += 1;
+        let mut tables = vec![];
+        for (cf_id, mem_table) in self.mem_tables.read().unwrap().iter() {
+            if let Some(table) = mem_table.immut_table(index) {
+                if !table.is_empty() {
+                    tables.push((*cf_id, table));
+                }
+            }
+        }
+        Some(FlushJob {
+            seq: immut_queue.front_seq + index as u64,
+            tables,
+        })
     }
 
-    pub(crate) fn dump(&self, writer: sst::Writer<K, V>) -> EikvResult<()> {
-        let mut immut_wal = self.immut_wal.lock().unwrap();
-        self.mem_table.dump(writer)?;
-        *immut_wal = None;
+    // Waits for frozen memtables nobody flushes yet, at most for the timeout.
+    pub(crate) fn wait_immut(&self, timeout: Duration) {
+        let immut_queue = self.immut_queue.lock().unwrap();
+        if immut_queue.flushing == immut_queue.wals.len() {
+            let _ = self.has_immut.wait_timeout(immut_queue, timeout).unwrap();
+        }
+    }
+
+    // Waits until the flushes claimed earlier are installed, an earlier flush
+    // failing fails the wait.
+    pub(crate) fn wait_flush_turn(&self, flush_seq: u64) -> EikvResult<()> {
+        let mut immut_queue = self.immut_queue.lock().unwrap();
+        while immut_queue.front_seq != flush_seq {
+            if let Some(reason) = &immut_queue.background_error {
+                return Err(EikvError::BackgroundError(reason.clone()));
+            }
+            immut_queue = self.background_progress.wait(immut_queue).unwrap();
+        }
         Ok(())
     }
+
+    // Keeps the first error and wakes up everyone waiting for the flushes
+    // and the compactions.
+    pub(crate) fn set_background_error(&self, err: &EikvError) {
+        let mut immut_queue = self.immut_queue.lock().unwrap();
+        if immut_queue.background_error.is_none() {
+            immut_queue.background_error = Some(err.to_string());
+        }
+        self.background_progress.notify_all();
+        self.has_immut.notify_all();
+    }
+
+    pub(crate) fn background_error(&self) -> EikvResult<()> {
+        match &self.immut_queue.lock().unwrap().background_error {
+            Some(reason) => Err(EikvError::BackgroundError(reason.clone())),
+            None => Ok(()),
+        }
+    }
+
+    // Waits for a flush or a compaction to finish, at most for the timeout.
+    pub(crate) fn wait_background_progress(&self, timeout: Duration) {
+        let immut_queue = self.immut_queue.lock().unwrap();
+        let _ = self
+            .background_progress
+            .wait_timeout(immut_queue, timeout)
+            .unwrap();
+    }
+
+    pub(crate) fn notify_background_progress(&self) {
+        let _immut_queue = self.immut_queue.lock().unwrap();
+        self.background_progress.notify_all();
+    }
+
+    // The bytes taken by the memtables of all the column families.
+    pub(crate) fn memory_usage(&self) -> usize {
+        let mem_tables = self.mem_tables.read().unwrap();
+        mem_tables
+            .values()
+            .map(|mem_table| mem_table.memory_usage())
+            .sum()
+    }
+
+    // Drops the oldest frozen memtables once they're flushed.
+    pub(crate) fn drop_immut(&self) {
+        let mut immut_queue = self.immut_queue.lock().unwrap();
+        for mem_table in self.mem_tables.read().unwrap().values() {
+            mem_table.pop_immut();
+        }
+        immut_queue.wals.pop_front();
+        immut_queue.flushing -= 1;
+        immut_queue.front_seq += 1;
+        self.background_progress.notify_all();
+        drop(immut_queue);
+        self.sync_memory_usage();
+    }
+}
+
+fn mutable_memory_usage<K: Key, V: Value>(
+    mem_tables: &BTreeMap<u32, Arc<MemTable<K, V>>>,
+) -> usize {
+    mem_tables
+        .values()
+        .map(|mem_table| mem_table.mut_memory_usage())
+        .sum()
 }
@@ -0,0 +1,325 @@
+use crate::Comparator;
+use std::{
+    cmp::{max, Ordering},
+    mem::size_of,
+    ptr, slice,
+    sync::{
+        atomic::{AtomicPtr, AtomicUsize, Ordering as AtomicOrdering},
+        Arc, Mutex,
+    },
+};
+
+const MAX_HEIGHT: usize = 12;
+const BRANCHING: u64 = 4;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// A node is followed by its tower of `height` next pointers in the arena.
+#[repr(C)]
+struct Node {
+    seq: u64,
+    entry: *const u8,
+    entry_len: usize,
+    key_off: usize,
+    key_len: usize,
+    height: usize,
+}
+
+impl Node {
+    fn key(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.entry.add(self.key_off), self.key_len) }
+    }
+
+    fn entry(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.entry, self.entry_len) }
+    }
+
+    fn next(&self, level: usize) -> &AtomicPtr<Node> {
+        debug_assert!(level < self.height);
+        unsafe {
+            let tower = (self as *const Node).add(1) as *const AtomicPtr<Node>;
+            &*tower.add(level)
+        }
+    }
+}
+
+// Bump allocator of the nodes and the encoded entries, the memory is only
+// freed with the skiplist so readers can hold on to it without locks.
+struct Arena {
+    chunks: Vec<(*mut u64, usize)>,
+    offset: usize,
+}
+
+impl Arena {
+    // Returns the memory, zeroed and aligned to 8 bytes, and the bytes newly
+    // taken from the allocator.
+    fn alloc(&mut self, size: usize) -> (*mut u8, usize) {
+        let size = size.next_multiple_of(8);
+        let mut allocated = 0;
+        let fits = self
+            .chunks
+            .last()
+            .is_some_and(|(_, words)| self.offset + size <= words * 8);
+        if !fits {
+            let words = max(size, CHUNK_SIZE) / 8;
+            let chunk = Box::into_raw(vec![0_u64; words].into_boxed_slice()) as *mut u64;
+            self.chunks.push((chunk, words));
+            self.offset = 0;
+            allocated = words * 8;
+        }
+
+        let (chunk, _) = self.chunks.last().unwrap();
+        let ptr = unsafe { (*chunk as *mut u8).add(self.offset) };
+        self.offset += size;
+        (ptr, allocated)
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        for (chunk, words) in self.chunks.drain(..) {
+            unsafe {
+                drop(Box::from_raw(ptr::slice_from_raw_parts_mut(chunk, words)));
+            }
+        }
+    }
+}
+
+struct Writer {
+    arena: Arena,
+    rng: u64,
+}
+
+impl Writer {
+    fn random_height(&mut self) -> usize {
+        let mut height = 1;
+        loop {
+            // xorshift64
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            if height == MAX_HEIGHT || !self.rng.is_multiple_of(BRANCHING) {
+                return height;
+            }
+            height += 1;
+        }
+    }
+}
+
+// A skiplist of encoded entries ordered by their keys and then by their seqs.
+// Inserts are serialized by the writer lock, reads don't take any lock since
+// nodes are published with release stores and never freed or moved.
+pub(super) struct SkipList {
+    head: *const Node,
+    height: AtomicUsize,
+    writer: Mutex<Writer>,
+    memory_usage: AtomicUsize,
+    comparator: Arc<dyn Comparator>,
+}
+
+unsafe impl Send for SkipList {}
+unsafe impl Sync for SkipList {}
+
+impl SkipList {
+    pub(super) fn new(comparator: Arc<dyn Comparator>) -> SkipList {
+        let mut writer = Writer {
+            arena: Arena {
+                chunks: vec![],
+                offset: 0,
+            },
+            rng: 0x2545_f491_4f6c_dd1d,
+        };
+        let (head, allocated) = alloc_node(&mut writer.arena, MAX_HEIGHT);
+        unsafe {
+            ptr::write(
+                head,
+                Node {
+                    seq: 0,
+                    entry: ptr::null(),
+                    entry_len: 0,
+                    key_off: 0,
+                    key_len: 0,
+                    height: MAX_HEIGHT,
+                },
+            );
+        }
+        SkipList {
+            head,
+            height: AtomicUsize::new(1),
+            writer: Mutex::new(writer),
+            memory_usage: AtomicUsize::new(allocated),
+            comparator,
+        }
+    }
+
+    // The bytes taken by the arena.
+    pub(super) fn memory_usage(&self) -> usize {
+        self.memory_usage.load(AtomicOrdering::Relaxed)
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.head().next(0).load(AtomicOrdering::Acquire).is_null()
+    }
+
+    fn head(&self) -> &Node {
+        unsafe { &*self.head }
+    }
+
+    fn compare(&self, node: &Node, key: &[u8], seq: u64) -> Ordering {
+        self.comparator
+            .compare(node.key(), key)
+            .then(node.seq.cmp(&seq))
+    }
+
+    // Returns the first node not less than the key and the seq, the last
+    // nodes less than them on each level are kept in prev.
+    fn find_greater_or_equal(
+        &self,
+        key: &[u8],
+        seq: u64,
+        mut prev: Option<&mut [*const Node; MAX_HEIGHT]>,
+    ) -> Option<&Node> {
+        let mut node = self.head();
+        let mut level = self.height.load(AtomicOrdering::Relaxed) - 1;
+        loop {
+            let next = node.next(level).load(AtomicOrdering::Acquire);
+            let next = unsafe { next.as_ref() };
+            match next {
+                Some(next) if self.compare(next, key, seq).is_lt() => node = next,
+                _ => {
+                    if let Some(prev) = prev.as_mut() {
+                        prev[level] = node;
+                    }
+                    if level == 0 {
+                        return next;
+                    }
+                    level -= 1;
+                }
+            }
+        }
+    }
+
+    // The key is the slice of the encoded entry at the offset.
+    pub(super) fn insert(&self, entry: &[u8], key_off: usize, key_len: usize, seq: u64) {
+        let mut writer = self.writer.lock().unwrap();
+        let key = &entry[key_off..key_off + key_len];
+        let mut prev = [self.head; MAX_HEIGHT];
+        self.find_greater_or_equal(key, seq, Some(&mut prev));
+
+        let height = writer.random_height();
+        let list_height = self.height.load(AtomicOrdering::Relaxed);
+        if height > list_height {
+            // Readers seeing the new height before the node find nulls from
+            // the head on the new levels, which is fine.
+            self.height.store(height, AtomicOrdering::Relaxed);
+        }
+
+        let (entry_ptr, entry_allocated) = writer.arena.alloc(entry.len());
+        let (node, node_allocated) = alloc_node(&mut writer.arena, height);
+        unsafe {
+            ptr::copy_nonoverlapping(entry.as_ptr(), entry_ptr, entry.len());
+            ptr::write(
+                node,
+                Node {
+                    seq,
+                    entry: entry_ptr,
+                    entry_len: entry.len(),
+                    key_off,
+                    key_len,
+                    height,
+                },
+            );
+            let node_ref = &*node;
+            for (level, prev_node) in prev.iter().enumerate().take(height) {
+                let prev_node = &**prev_node;
+                let next = prev_node.next(level).load(AtomicOrdering::Relaxed);
+                node_ref.next(level).store(next, AtomicOrdering::Relaxed);
+                prev_node.next(level).store(node, AtomicOrdering::Release);
+            }
+        }
+        self.memory_usage
+            .fetch_add(entry_allocated + node_allocated, AtomicOrdering::Relaxed);
+    }
+
+    // Returns the encoded entries of the key not newer than the seq guard,
+    // newest first.
+    pub(super) fn get(&self, key: &[u8], seq_guard: u64) -> Vec<&[u8]> {
+        let mut entries = vec![];
+        let mut node = self.find_greater_or_equal(key, 0, None);
+        while let Some(n) = node {
+            if n.seq > seq_guard || self.comparator.compare(n.key(), key).is_ne() {
+                break;
+            }
+            entries.push(n.entry());
+            node = unsafe { n.next(0).load(AtomicOrdering::Acquire).as_ref() };
+        }
+        entries.reverse();
+        entries
+    }
+
+    pub(super) fn latest_seq(&self, key: &[u8]) -> Option<u64> {
+        let mut latest = None;
+        let mut node = self.find_greater_or_equal(key, 0, None);
+        while let Some(n) = node {
+            if self.comparator.compare(n.key(), key).is_ne() {
+                break;
+            }
+            latest = Some(n.seq);
+            node = unsafe { n.next(0).load(AtomicOrdering::Acquire).as_ref() };
+        }
+        latest
+    }
+
+    // Returns the encoded entries in order.
+    pub(super) fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        let mut node = self.head().next(0).load(AtomicOrdering::Acquire) as *const Node;
+        std::iter::from_fn(move || {
+            let n = unsafe { node.as_ref()? };
+            node = n.next(0).load(AtomicOrdering::Acquire);
+            Some(n.entry())
+        })
+    }
+}
+
+fn alloc_node(arena: &mut Arena, height: usize) -> (*mut Node, usize) {
+    let size = size_of::<Node>() + height * size_of::<AtomicPtr<Node>>();
+    let (ptr, allocated) = arena.alloc(size);
+    (ptr as *mut Node, allocated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SkipList;
+    use crate::BytewiseComparator;
+    use std::sync::Arc;
+
+    fn insert(list: &SkipList, key: &[u8], seq: u64) {
+        let mut entry = vec![key.len() as u8];
+        entry.extend(key);
+        entry.push(seq as u8);
+        list.insert(&entry, 1, key.len(), seq);
+    }
+
+    #[test]
+    fn test_skiplist() {
+        let list = SkipList::new(Arc::new(BytewiseComparator));
+        assert!(list.is_empty());
+        for i in (0..200_u64).rev() {
+            insert(&list, format!("k{:03}", i % 50).as_bytes(), i);
+        }
+        assert!(!list.is_empty());
+        assert_eq!(200, list.iter().count());
+
+        let versions = list.get(b"k007", 150);
+        let seqs: Vec<u8> = versions.iter().map(|entry| entry[5]).collect();
+        assert_eq!(vec![107, 57, 7], seqs);
+        assert_eq!(Some(157), list.latest_seq(b"k007"));
+        assert_eq!(None, list.latest_seq(b"k0070"));
+        assert!(list.get(b"k100", u64::MAX).is_empty());
+
+        let keys: Vec<&[u8]> = list.iter().map(|entry| &entry[1..5]).collect();
+        let mut sorted = keys.clone();
+        sorted.sort();
+        assert_eq!(sorted, keys);
+        assert!(list.memory_usage() >= 64 * 1024);
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Caps the memory of the memtables of all the DBs sharing it, a DB writing
+// when the cap is exceeded flushes its memtables.
+pub struct WriteBufferManager {
+    buffer_size: usize,
+    memory_usage: AtomicUsize,
+    mutable_memory_usage: AtomicUsize,
+}
+
+impl WriteBufferManager {
+    pub fn new(buffer_size: usize) -> WriteBufferManager {
+        WriteBufferManager {
+            buffer_size,
+            memory_usage: AtomicUsize::new(0),
+            mutable_memory_usage: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.memory_usage.load(Ordering::Relaxed)
+    }
+
+    pub fn mutable_memory_usage(&self) -> usize {
+        self.mutable_memory_usage.load(Ordering::Relaxed)
+    }
+
+    // Frozen memtables are already being flushed, so only the mutable ones
+    // count: past 7/8 of the buffer, or past half of it once the whole buffer
+    // is used, as RocksDB does.
+    pub fn should_flush(&self) -> bool {
+        let mutable_usage = self.mutable_memory_usage();
+        mutable_usage > self.buffer_size - self.buffer_size / 8
+            || (self.memory_usage() >= self.buffer_size && mutable_usage >= self.buffer_size / 2)
+    }
+
+    pub(crate) fn reserve(&self, size: usize) {
+        self.memory_usage.fetch_add(size, Ordering::Relaxed);
+    }
+
+    pub(crate) fn free(&self, size: usize) {
+        self.memory_usage.fetch_sub(size, Ordering::Relaxed);
+    }
+
+    pub(crate) fn reserve_mutable(&self, size: usize) {
+        self.mutable_memory_usage.fetch_add(size, Ordering::Relaxed);
+    }
+
+    pub(crate) fn free_mutable(&self, size: usize) {
+        self.mutable_memory_usage.fetch_sub(size, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WriteBufferManager;
+
+    #[test]
+    fn test_should_flush() {
+        let manager = WriteBufferManager::new(100);
+        manager.reserve(200);
+        assert!(!manager.should_flush());
+        manager.reserve(40);
+        manager.reserve_mutable(40);
+        assert!(!manager.should_flush());
+        manager.reserve(10);
+        manager.reserve_mutable(10);
+        assert!(manager.should_flush());
+        manager.free(200);
+        assert_eq!(50, manager.memory_usage());
+        assert!(!manager.should_flush());
+        manager.reserve(40);
+        manager.reserve_mutable(40);
+        assert_eq!(90, manager.mutable_memory_usage());
+        assert!(manager.should_flush());
+    }
+}
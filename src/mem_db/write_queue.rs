@@ -1,74 +1,101 @@
-use crate::{Key, Value, WriteBatch};
+use crate::{wal::Precondition, EikvError, EikvResult, Key, Value, WriteBatch};
 use std::{
-    collections::VecDeque,
-    sync::{
-        atomic::{AtomicU64, Ordering},
-        Condvar, Mutex,
-    },
-    thread::{self, ThreadId},
+    collections::{HashMap, VecDeque},
+    sync::{Condvar, Mutex},
 };
 
-struct WriteOp<K: Key, V: Value> {
-    thread_id: ThreadId,
-    write_batch: WriteBatch<K, V>,
+pub(crate) struct WriteOp<K: Key, V: Value> {
+    pub(crate) write_batch: WriteBatch<K, V>,
+    pub(crate) preconditions: Vec<Precondition<K, V>>,
 }
 
 impl<K: Key, V: Value> WriteOp<K, V> {
-    fn new(write_batch: WriteBatch<K, V>) -> WriteOp<K, V> {
+    pub(crate) fn new(mut write_batch: WriteBatch<K, V>) -> WriteOp<K, V> {
         WriteOp {
+            preconditions: write_batch.take_preconditions(),
             write_batch,
-            thread_id: thread::current().id(),
         }
     }
 }
 
+pub(crate) enum Turn<K: Key, V: Value> {
+    Lead(Vec<WriteOp<K, V>>),
+    Done(EikvResult<bool>),
+}
+
+struct State<K: Key, V: Value> {
+    next_id: u64,
+    queue: VecDeque<(u64, WriteOp<K, V>)>,
+    leading: Vec<u64>,
+    // Errors are kept as messages, EikvError can't be sent across threads.
+    results: HashMap<u64, Result<bool, String>>,
+}
+
 pub(super) struct WriteQueue<K: Key, V: Value> {
     finished: Condvar,
-    queue: Mutex<VecDeque<WriteOp<K, V>>>,
-    next_seq: AtomicU64,
+    state: Mutex<State<K, V>>,
 }
 
 impl<K: Key, V: Value> WriteQueue<K, V> {
-    pub(super) fn new(next_seq: AtomicU64) -> WriteQueue<K, V> {
+    pub(super) fn new() -> WriteQueue<K, V> {
+        let state = State {
+            next_id: 0,
+            queue: VecDeque::new(),
+            leading: vec![],
+            results: HashMap::new(),
+        };
         WriteQueue {
-            queue: Mutex::new(VecDeque::new()),
             finished: Condvar::new(),
-            next_seq,
+            state: Mutex::new(state),
         }
     }
 
-    pub(super) fn line_up(&self, write_batch: WriteBatch<K, V>) -> Option<WriteBatch<K, V>> {
-        let write_op = WriteOp::new(write_batch);
-        let mut guard = self.queue.lock().unwrap();
-        guard.push_back(write_op);
+    // The first op in the queue leads the write of every queued op, the
+    // others wait until the leader hands them their results.
+    pub(super) fn line_up(&self, write_op: WriteOp<K, V>) -> Turn<K, V> {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.queue.push_back((id, write_op));
+
         loop {
-            if guard.front().unwrap().thread_id == thread::current().id() {
-                break;
+            if let Some(result) = state.results.remove(&id) {
+                return Turn::Done(result.map_err(EikvError::WriteError));
             }
-            let queue = self.finished.wait(guard).unwrap();
-            for write_op in queue.iter() {
-                if write_op.thread_id == thread::current().id() {
-                    continue;
+            if state.leading.is_empty() && state.queue.front().is_some_and(|(i, _)| *i == id) {
+                let mut write_ops = Vec::with_capacity(state.queue.len());
+                while let Some((id, write_op)) = state.queue.pop_front() {
+                    state.leading.push(id);
+                    write_ops.push(write_op);
                 }
+                return Turn::Lead(write_ops);
             }
-            return None;
-        }
-
-        let len = guard.len();
-        let mut write_batch = WriteBatch::new();
-        for _ in 0..len {
-            write_batch.extend(guard.pop_front().unwrap().write_batch);
+            state = self.finished.wait(state).unwrap();
         }
-
-        let start_seq = self
-            .next_seq
-            .fetch_add(write_batch.len() as u64, Ordering::Relaxed);
-        write_batch.set_seqs(start_seq);
-
-        Some(write_batch)
     }
 
-    pub(super) fn notify_waiters(&self) {
+    pub(super) fn finish(&self, results: EikvResult<Vec<EikvResult<bool>>>) -> EikvResult<bool> {
+        let mut state = self.state.lock().unwrap();
+        let leading: Vec<u64> = state.leading.drain(..).collect();
+        let result = match results {
+            Ok(results) => {
+                let mut results = results.into_iter();
+                let result = results.next().unwrap();
+                for (id, result) in leading[1..].iter().zip(results) {
+                    let result = result.map_err(|err| err.to_string());
+                    state.results.insert(*id, result);
+                }
+                result
+            }
+            Err(err) => {
+                for id in &leading[1..] {
+                    let reason = format!("the write group failed: {}", err);
+                    state.results.insert(*id, Err(reason));
+                }
+                Err(err)
+            }
+        };
         self.finished.notify_all();
+        result
     }
 }
@@ -0,0 +1,19 @@
+pub struct CompactionFilterContext {
+    pub level: usize,
+    pub is_manual_compaction: bool,
+}
+
+pub enum CompactionDecision {
+    Keep,
+    Remove,
+    ChangeValue(Vec<u8>),
+}
+
+pub trait CompactionFilter: Send + Sync {
+    fn filter(
+        &self,
+        context: &CompactionFilterContext,
+        key: &[u8],
+        value: &[u8],
+    ) -> CompactionDecision;
+}
@@ -0,0 +1,87 @@
+use std::cmp::{min, Ordering};
+
+// Orders encoded keys, the order has to agree with the Ord of the decoded
+// keys since memtables and merges still sort the decoded ones.
+pub trait Comparator: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering;
+
+    // Returns a key in [start, limit), as short as possible.
+    fn find_shortest_separator(&self, start: &[u8], _limit: &[u8]) -> Vec<u8> {
+        start.to_vec()
+    }
+
+    // Returns a key not less than the given one, as short as possible.
+    fn find_short_successor(&self, key: &[u8]) -> Vec<u8> {
+        key.to_vec()
+    }
+}
+
+pub struct BytewiseComparator;
+
+impl Comparator for BytewiseComparator {
+    fn name(&self) -> &str {
+        "eikv.BytewiseComparator"
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        a.cmp(b)
+    }
+
+    fn find_shortest_separator(&self, start: &[u8], limit: &[u8]) -> Vec<u8> {
+        let min_len = min(start.len(), limit.len());
+        let mut diff_index = 0;
+        while diff_index < min_len && start[diff_index] == limit[diff_index] {
+            diff_index += 1;
+        }
+
+        if diff_index < min_len {
+            let diff_byte = start[diff_index];
+            if diff_byte < 0xff && diff_byte + 1 < limit[diff_index] {
+                let mut separator = start[..=diff_index].to_vec();
+                separator[diff_index] += 1;
+                return separator;
+            }
+        }
+        start.to_vec()
+    }
+
+    fn find_short_successor(&self, key: &[u8]) -> Vec<u8> {
+        for (i, byte) in key.iter().enumerate() {
+            if *byte != 0xff {
+                let mut successor = key[..=i].to_vec();
+                successor[i] += 1;
+                return successor;
+            }
+        }
+        key.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BytewiseComparator, Comparator};
+
+    #[test]
+    fn test_find_shortest_separator() {
+        let comparator = BytewiseComparator;
+        let res = comparator.find_shortest_separator(b"abcd", b"abzz");
+        assert_eq!(b"abd".to_vec(), res);
+        let res = comparator.find_shortest_separator(b"abcd", b"abde");
+        assert_eq!(b"abcd".to_vec(), res);
+        let res = comparator.find_shortest_separator(b"ab", b"abcd");
+        assert_eq!(b"ab".to_vec(), res);
+    }
+
+    #[test]
+    fn test_find_short_successor() {
+        let comparator = BytewiseComparator;
+        let res = comparator.find_short_successor(b"abcd");
+        assert_eq!(b"b".to_vec(), res);
+        let res = comparator.find_short_successor(&[0xff, 0xff, 0x01]);
+        assert_eq!(vec![0xff, 0xff, 0x02], res);
+        let res = comparator.find_short_successor(&[0xff, 0xff]);
+        assert_eq!(vec![0xff, 0xff], res);
+    }
+}
@@ -1,15 +1,64 @@
 use std::cmp::Ordering;
 
 use crate::{
-    util::coding::{append_var_u32, append_var_u64, decode_bytes_with_len, decode_var_u64},
+    util::coding::{
+        append_var_u64, append_with_len, decode_slice_with_len, decode_var_u32, decode_var_u64,
+    },
     EikvError, EikvResult, Key, Value,
 };
 
+#[derive(Clone)]
+pub(crate) enum Op<K: Key, V: Value> {
+    Put(V),
+    // The value and the unix time in milliseconds at which it expires.
+    PutWithExpiry(V, u64),
+    Delete,
+    DeleteRange(K),
+    Merge(V),
+}
+
+impl<K: Key, V: Value> Op<K, V> {
+    pub(crate) fn encode_to(&self, buf: &mut Vec<u8>) -> EikvResult<()> {
+        match self {
+            Op::Put(value) => {
+                buf.push(1);
+                append_with_len(buf, |buf| value.encode(buf))?;
+            }
+            Op::PutWithExpiry(value, expire_at) => {
+                buf.push(5);
+                append_var_u64(buf, *expire_at);
+                append_with_len(buf, |buf| value.encode(buf))?;
+            }
+            Op::Delete => buf.push(2),
+            Op::DeleteRange(end) => {
+                buf.push(3);
+                append_with_len(buf, |buf| end.encode(buf))?;
+            }
+            Op::Merge(operand) => {
+                buf.push(4);
+                append_with_len(buf, |buf| operand.encode(buf))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn decode(op: &Op<Vec<u8>, Vec<u8>>) -> EikvResult<Self> {
+        let op = match op {
+            Op::Put(value) => Op::Put(V::decode(value)?),
+            Op::PutWithExpiry(value, expire_at) => Op::PutWithExpiry(V::decode(value)?, *expire_at),
+            Op::Delete => Op::Delete,
+            Op::DeleteRange(end) => Op::DeleteRange(K::decode(end)?),
+            Op::Merge(operand) => Op::Merge(V::decode(operand)?),
+        };
+        Ok(op)
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct Entry<K: Key, V: Value> {
     pub(crate) key: K,
     pub(crate) seq: u64,
-    pub(crate) value: Option<V>,
+    pub(crate) op: Op<K, V>,
 }
 
 impl<K: Key, V: Value> PartialEq for Entry<K, V> {
@@ -22,10 +71,7 @@ impl<K: Key, V: Value> Eq for Entry<K, V> {}
 
 impl<K: Key, V: Value> PartialOrd for Entry<K, V> {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match self.key.partial_cmp(&other.key) {
-            Some(core::cmp::Ordering::Equal) => self.seq.partial_cmp(&other.seq),
-            ord => return ord,
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -39,91 +85,88 @@ impl<K: Key, V: Value> Ord for Entry<K, V> {
 }
 
 impl<K: Key, V: Value> Entry<K, V> {
-    pub(crate) fn encode(self, buf: &mut Vec<u8>) -> EikvResult<()> {
-        let key_bytes = self.key.encode()?;
-        let key_len = key_bytes.len() as u32;
-        append_var_u32(buf, key_len);
-        buf.extend(key_bytes);
-
-        append_var_u64(buf, self.seq);
-
-        match self.value {
-            Some(value) => {
-                buf.push(1);
-                let value_bytes = value.encode()?;
-                let value_len = value_bytes.len() as u32;
-                append_var_u32(buf, value_len);
-                buf.extend(value_bytes);
-            }
-            None => buf.push(2),
+    pub(crate) fn value(&self) -> Option<&V> {
+        match &self.op {
+            Op::Put(value) | Op::PutWithExpiry(value, _) => Some(value),
+            _ => None,
         }
-
-        Ok(())
     }
 
-    fn decode_to_vec_u8(buf: &[u8]) -> Option<(Entry<Vec<u8>, Vec<u8>>, usize)> {
-        let (key, mut buf_off) = match decode_bytes_with_len(buf) {
-            Some((key, n)) => (key, n),
-            None => return None,
-        };
+    pub(crate) fn is_expired(&self, now: u64) -> bool {
+        matches!(self.op, Op::PutWithExpiry(_, expire_at) if expire_at <= now)
+    }
 
-        let seq = match decode_var_u64(&buf[buf_off..]) {
-            Some((seq, n)) => {
-                buf_off += n;
-                seq
-            }
-            None => return None,
-        };
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) -> EikvResult<()> {
+        append_with_len(buf, |buf| self.key.encode(buf))?;
+        append_var_u64(buf, self.seq);
+        self.op.encode_to(buf)
+    }
 
-        if buf_off == buf.len() {
-            return None;
+    fn decode_op(buf: &[u8]) -> EikvResult<Option<(Op<K, V>, usize)>> {
+        if buf.is_empty() {
+            return Ok(None);
         }
-        let mut entry = Entry {
-            key,
-            seq,
-            value: None,
-        };
-        buf_off += 1;
-
-        match buf[buf_off - 1] {
-            1 => {
-                let value = match decode_bytes_with_len(&buf[buf_off..]) {
+        let mut buf_off = 1;
+        let op = match buf[0] {
+            1 => match decode_slice_with_len(&buf[buf_off..]) {
+                Some((value, n)) => {
+                    buf_off += n;
+                    Op::Put(V::decode(value)?)
+                }
+                None => return Ok(None),
+            },
+            2 => Op::Delete,
+            3 => match decode_slice_with_len(&buf[buf_off..]) {
+                Some((end, n)) => {
+                    buf_off += n;
+                    Op::DeleteRange(K::decode(end)?)
+                }
+                None => return Ok(None),
+            },
+            4 => match decode_slice_with_len(&buf[buf_off..]) {
+                Some((operand, n)) => {
+                    buf_off += n;
+                    Op::Merge(V::decode(operand)?)
+                }
+                None => return Ok(None),
+            },
+            5 => {
+                let (expire_at, n) = match decode_var_u64(&buf[buf_off..]) {
+                    Some(expire_at) => expire_at,
+                    None => return Ok(None),
+                };
+                buf_off += n;
+                match decode_slice_with_len(&buf[buf_off..]) {
                     Some((value, n)) => {
                         buf_off += n;
-                        value
+                        Op::PutWithExpiry(V::decode(value)?, expire_at)
                     }
-                    None => return None,
-                };
-
-                entry.value = Some(value);
-                Some((entry, buf_off))
+                    None => return Ok(None),
+                }
             }
-            2 => Some((entry, buf_off)),
-            _ => None,
-        }
+            _ => return Ok(None),
+        };
+        Ok(Some((op, buf_off)))
+    }
+
+    // Returns the offset and the length of the key in the encoded entry.
+    pub(crate) fn encoded_key_range(buf: &[u8]) -> EikvResult<(usize, usize)> {
+        let corrupt = || EikvError::WalCorrpution("entry is corrupt".to_owned());
+        let (key_len, n) = decode_var_u32(buf).ok_or_else(corrupt)?;
+        Ok((n, key_len as usize))
     }
 
     pub(crate) fn decode(buf: &[u8]) -> EikvResult<(Self, usize)> {
-        match Self::decode_to_vec_u8(buf) {
-            Some((vec_u8_entry, n)) => {
-                let key = K::decode(vec_u8_entry.key)?;
-                let mut entry = Self {
-                    key,
-                    seq: vec_u8_entry.seq,
-                    value: None,
-                };
+        let corrupt = || EikvError::WalCorrpution("entry is corrupt".to_owned());
 
-                if let Some(value) = vec_u8_entry.value {
-                    let value = Value::decode(value)?;
-                    entry.value = Some(value)
-                }
+        let (key, mut buf_off) = decode_slice_with_len(buf).ok_or_else(corrupt)?;
+        let key = K::decode(key)?;
+        let (seq, n) = decode_var_u64(&buf[buf_off..]).ok_or_else(corrupt)?;
+        buf_off += n;
+        let (op, n) = Self::decode_op(&buf[buf_off..])?.ok_or_else(corrupt)?;
+        buf_off += n;
 
-                Ok((entry, n))
-            }
-            None => {
-                let reason = "entry is corrupt".to_owned();
-                Err(EikvError::WalCorrpution(reason))
-            }
-        }
+        let entry = Entry { key, seq, op };
+        Ok((entry, buf_off))
     }
 }
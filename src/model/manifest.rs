@@ -1,78 +1,233 @@
-use super::{Entry, SstMeta};
+use super::{Entry, Op, RangeTombstone, SstMeta};
 use crate::{
-    db::path::{current_path, current_tmp_path, manifest_path, sst_level_dir_path, sst_path},
+    db::{
+        path::{
+            current_path, current_tmp_path, manifest_path, sst_legacy_path, sst_level_dir_path,
+            sst_path, wal_path,
+        },
+        DEFAULT_CF_ID, DEFAULT_CF_NAME,
+    },
     limit::{LEVEL_MAX, LEVEL_MIN},
+    sst::Footer,
     EikvError, EikvResult, Key, Value,
 };
 use std::{
-    cmp::min,
-    collections::{HashMap, HashSet},
-    fs::{self, remove_file, rename, File},
+    cmp::{max, min},
+    collections::{hash_map, BTreeMap, HashMap, HashSet},
+    fs::{self, metadata, remove_file, rename, File},
     io::{BufRead, BufReader, Read, Write},
+    num::ParseIntError,
     path::Path,
 };
 
-pub(crate) struct Manifest<K: Key, V: Value> {
-    next_file_seq: u64,
-    wals: HashSet<u64>,
+// The input files of a compaction by their paths, with the seqs of the ones
+// in its level and in the next one.
+type CompactionInputs<'a, K, V> = (HashMap<String, &'a SstMeta<K, V>>, Vec<u64>, Vec<u64>);
+
+struct ColumnFamily<K: Key, V: Value> {
+    name: String,
     sstables: Vec<HashMap<u64, Option<SstMeta<K, V>>>>,
+    // Inputs of running compactions, they aren't picked by other ones.
+    being_compacted: HashSet<u64>,
 }
 
-impl<K: Key, V: Value> Manifest<K, V> {
-    pub(crate) fn new() -> Manifest<K, V> {
-        let mut sstables = Vec::new();
-        sstables.reserve(LEVEL_MAX);
+impl<K: Key, V: Value> ColumnFamily<K, V> {
+    fn new(name: &str) -> ColumnFamily<K, V> {
+        let mut sstables = Vec::with_capacity(LEVEL_MAX);
         for _ in LEVEL_MIN..=LEVEL_MAX {
             sstables.push(HashMap::new());
         }
+        ColumnFamily {
+            name: name.to_owned(),
+            sstables,
+            being_compacted: HashSet::new(),
+        }
+    }
+}
+
+pub(crate) struct Manifest<K: Key, V: Value> {
+    comparator: String,
+    // Ids of dropped column families aren't reused, the WAL may still hold
+    // their entries.
+    next_cf_id: u32,
+    next_file_seq: u64,
+    wals: HashSet<u64>,
+    column_families: BTreeMap<u32, ColumnFamily<K, V>>,
+}
+
+impl<K: Key, V: Value> Manifest<K, V> {
+    pub(crate) fn new(comparator: &str) -> Manifest<K, V> {
+        let mut column_families = BTreeMap::new();
+        column_families.insert(DEFAULT_CF_ID, ColumnFamily::new(DEFAULT_CF_NAME));
         Manifest {
+            comparator: comparator.to_owned(),
+            next_cf_id: DEFAULT_CF_ID + 1,
             next_file_seq: 1,
             wals: HashSet::new(),
-            sstables,
+            column_families,
         }
     }
 
-    pub(crate) fn get_level(&self, level: usize) -> &HashMap<u64, Option<SstMeta<K, V>>> {
-        &self.sstables[level - 1]
+    pub(crate) fn cf_ids(&self) -> Vec<u32> {
+        self.column_families.keys().copied().collect()
+    }
+
+    pub(crate) fn cf_name(&self, cf_id: u32) -> Option<&str> {
+        self.column_families
+            .get(&cf_id)
+            .map(|column_family| column_family.name.as_str())
+    }
+
+    pub(crate) fn cf_id(&self, name: &str) -> Option<u32> {
+        self.column_families
+            .iter()
+            .find(|(_, column_family)| column_family.name == name)
+            .map(|(cf_id, _)| *cf_id)
+    }
+
+    pub(crate) fn has_cf(&self, cf_id: u32) -> bool {
+        self.column_families.contains_key(&cf_id)
+    }
+
+    pub(crate) fn add_cf(&mut self, name: &str) -> u32 {
+        let cf_id = self.next_cf_id;
+        self.next_cf_id += 1;
+        self.column_families.insert(cf_id, ColumnFamily::new(name));
+        cf_id
+    }
+
+    // Returns the SSTs of the removed column family.
+    pub(crate) fn remove_cf(&mut self, cf_id: u32) -> Vec<(usize, u64)> {
+        let mut ssts = vec![];
+        if let Some(column_family) = self.column_families.remove(&cf_id) {
+            for (level, sst_level) in (LEVEL_MIN..=LEVEL_MAX).zip(column_family.sstables) {
+                ssts.extend(sst_level.into_keys().map(|file_seq| (level, file_seq)));
+            }
+        }
+        ssts
+    }
+
+    pub(crate) fn get_level(
+        &self,
+        cf_id: u32,
+        level: usize,
+    ) -> &HashMap<u64, Option<SstMeta<K, V>>> {
+        &self.column_families[&cf_id].sstables[level - 1]
     }
 
     pub(crate) fn get_mut_level(
         &mut self,
+        cf_id: u32,
         level: usize,
     ) -> &mut HashMap<u64, Option<SstMeta<K, V>>> {
-        &mut self.sstables[level - 1]
+        &mut self.column_families.get_mut(&cf_id).unwrap().sstables[level - 1]
+    }
+
+    pub(crate) fn sst_metas(&self, cf_id: u32) -> Vec<(usize, u64, &SstMeta<K, V>)> {
+        let mut sst_metas = vec![];
+        if !self.has_cf(cf_id) {
+            return sst_metas;
+        }
+        for level in LEVEL_MIN..=LEVEL_MAX {
+            for (file_seq, sst_meta) in self.get_level(cf_id, level) {
+                if let Some(sst_meta) = sst_meta {
+                    sst_metas.push((level, *file_seq, sst_meta));
+                }
+            }
+        }
+        sst_metas
     }
 
-    pub(crate) fn level_sst_count(&self, level: usize) -> usize {
-        self.get_level(level).len()
+    // Tombstones newer than the seq guard leave the files to the reads older
+    // than them.
+    pub(crate) fn obsolete_ssts(&self, cf_id: u32, seq_guard: u64) -> Vec<(usize, u64)> {
+        let sst_metas = self.sst_metas(cf_id);
+        let range_tombstones: Vec<&RangeTombstone<K>> = sst_metas
+            .iter()
+            .flat_map(|(_, _, sst_meta)| &sst_meta.range_tombstones)
+            .collect();
+
+        let mut obsolete_ssts = vec![];
+        for (level, file_seq, sst_meta) in &sst_metas {
+            if self.is_being_compacted(cf_id, *file_seq) {
+                continue;
+            }
+            let covered = range_tombstones.iter().any(|range_tombstone| {
+                range_tombstone.seq <= seq_guard && sst_meta.covered_by(range_tombstone)
+            });
+            if covered {
+                obsolete_ssts.push((*level, *file_seq));
+            }
+        }
+        obsolete_ssts
+    }
+
+    pub(crate) fn expired_sst(&self, cf_id: u32, now: u64) -> Option<(usize, u64)> {
+        self.sst_metas(cf_id)
+            .into_iter()
+            .filter(|(_, file_seq, sst_meta)| {
+                sst_meta.min_expire_at <= now && !self.is_being_compacted(cf_id, *file_seq)
+            })
+            .min_by_key(|(_, _, sst_meta)| sst_meta.min_expire_at)
+            .map(|(level, file_seq, _)| (level, file_seq))
+    }
+
+    pub(crate) fn level_sst_count(&self, cf_id: u32, level: usize) -> usize {
+        self.get_level(cf_id, level).len()
     }
 
-    pub(crate) fn level_size(&self, level: usize) -> u64 {
+    pub(crate) fn level_size(&self, cf_id: u32, level: usize) -> u64 {
         let mut size = 0;
-        for sst_meta in self.get_level(level).values() {
+        for sst_meta in self.get_level(cf_id, level).values() {
             size += sst_meta.as_ref().unwrap().file_size;
         }
         size
     }
 
-    pub(crate) fn min_file_seq(&self, level: usize) -> u64 {
-        let mut min_seq = u64::MAX;
-        for seq in self.get_level(level).keys() {
-            min_seq = min(min_seq, *seq);
+    // Files being compacted are skipped.
+    pub(crate) fn min_file_seq(&self, cf_id: u32, level: usize) -> Option<u64> {
+        self.get_level(cf_id, level)
+            .keys()
+            .filter(|file_seq| !self.is_being_compacted(cf_id, **file_seq))
+            .min()
+            .copied()
+    }
+
+    pub(crate) fn is_being_compacted(&self, cf_id: u32, file_seq: u64) -> bool {
+        self.column_families
+            .get(&cf_id)
+            .is_some_and(|column_family| column_family.being_compacted.contains(&file_seq))
+    }
+
+    pub(crate) fn mark_being_compacted(&mut self, cf_id: u32, file_seqs: &[u64]) {
+        if let Some(column_family) = self.column_families.get_mut(&cf_id) {
+            column_family.being_compacted.extend(file_seqs);
+        }
+    }
+
+    pub(crate) fn unmark_being_compacted(&mut self, cf_id: u32, file_seqs: &[u64]) {
+        if let Some(column_family) = self.column_families.get_mut(&cf_id) {
+            for file_seq in file_seqs {
+                column_family.being_compacted.remove(file_seq);
+            }
         }
-        min_seq
     }
 
-    fn sst_meta(&self, level: usize, file_seq: u64) -> &SstMeta<K, V> {
-        self.get_level(level)
+    fn sst_meta(&self, cf_id: u32, level: usize, file_seq: u64) -> &SstMeta<K, V> {
+        self.get_level(cf_id, level)
             .get(&file_seq)
             .unwrap()
             .as_ref()
             .unwrap()
     }
 
-    fn min_and_max_entries(&self, level: usize, file_seq: u64) -> (&Entry<K, V>, &Entry<K, V>) {
-        let sst_meta = self.sst_meta(level, file_seq);
+    fn min_and_max_entries(
+        &self,
+        cf_id: u32,
+        level: usize,
+        file_seq: u64,
+    ) -> (&Entry<K, V>, &Entry<K, V>) {
+        let sst_meta = self.sst_meta(cf_id, level, file_seq);
         let min_entry = &sst_meta.min_entry;
         let max_entry = &sst_meta.max_entry;
         (min_entry, max_entry)
@@ -81,51 +236,42 @@ impl<K: Key, V: Value> Manifest<K, V> {
     pub(crate) fn should_merge(
         &self,
         db_path: &str,
+        cf_id: u32,
         level: usize,
         file_seq: u64,
-    ) -> EikvResult<(HashMap<String, &SstMeta<K, V>>, Vec<u64>, Vec<u64>)> {
-        let (mut min_entry, mut max_entry) = self.min_and_max_entries(level, file_seq);
+    ) -> EikvResult<CompactionInputs<'_, K, V>> {
+        let (mut min_entry, mut max_entry) = self.min_and_max_entries(cf_id, level, file_seq);
         let mut files = HashMap::new();
-        let sstable_path = sst_path(db_path, level, file_seq)?;
-        files.insert(sstable_path, self.sst_meta(level, file_seq));
+        let sstable_path = sst_path(db_path, file_seq)?;
+        files.insert(sstable_path, self.sst_meta(cf_id, level, file_seq));
         let next_level = level + 1;
         let mut this_level_file_seqs = vec![file_seq];
         let mut next_level_file_seqs = vec![];
 
+        // The key range grows with every file added until no other file of
+        // the two levels overlaps it.
         loop {
             let mut changed = false;
 
-            for file_seq in self.get_level(next_level).keys() {
-                let (sst_min_entry, sst_max_entry) =
-                    self.min_and_max_entries(next_level, *file_seq);
-                if (*sst_min_entry >= *min_entry && *sst_min_entry <= *max_entry)
-                    || (*sst_max_entry >= *min_entry && *sst_max_entry <= *max_entry)
-                {
-                    let sstable_path = sst_path(db_path, next_level, *file_seq)?;
-                    if files.get(&sstable_path).is_none() {
-                        changed = true;
-                        files.insert(sstable_path, self.sst_meta(next_level, *file_seq));
-                        next_level_file_seqs.push(*file_seq);
+            for (level, file_seqs) in [
+                (next_level, &mut next_level_file_seqs),
+                (level, &mut this_level_file_seqs),
+            ] {
+                for file_seq in self.get_level(cf_id, level).keys() {
+                    let (sst_min_entry, sst_max_entry) =
+                        self.min_and_max_entries(cf_id, level, *file_seq);
+                    if sst_min_entry.key > max_entry.key || sst_max_entry.key < min_entry.key {
+                        continue;
                     }
-                }
-                min_entry = min(min_entry, sst_min_entry);
-                max_entry = min(max_entry, sst_max_entry);
-            }
-
-            for file_seq in self.get_level(level).keys() {
-                let (sst_min_entry, sst_max_entry) = self.min_and_max_entries(level, *file_seq);
-                if (*sst_min_entry >= *min_entry && *sst_min_entry <= *max_entry)
-                    || (*sst_max_entry >= *min_entry && *sst_max_entry <= *max_entry)
-                {
-                    let sstable_path = sst_path(db_path, level, *file_seq)?;
-                    if files.get(&sstable_path).is_none() {
+                    let sstable_path = sst_path(db_path, *file_seq)?;
+                    if let hash_map::Entry::Vacant(entry) = files.entry(sstable_path) {
                         changed = true;
-                        files.insert(sstable_path, self.sst_meta(level, *file_seq));
-                        this_level_file_seqs.push(*file_seq);
+                        entry.insert(self.sst_meta(cf_id, level, *file_seq));
+                        file_seqs.push(*file_seq);
+                        min_entry = min(min_entry, sst_min_entry);
+                        max_entry = max(max_entry, sst_max_entry);
                     }
                 }
-                min_entry = min(min_entry, sst_min_entry);
-                max_entry = min(max_entry, sst_max_entry);
             }
 
             if !changed {
@@ -136,10 +282,44 @@ impl<K: Key, V: Value> Manifest<K, V> {
         Ok((files, this_level_file_seqs, next_level_file_seqs))
     }
 
+    // The files of the last level don't overlap, one of them is rewritten on
+    // its own as an input of the level.
+    pub(crate) fn should_rewrite(
+        &self,
+        db_path: &str,
+        cf_id: u32,
+        level: usize,
+        file_seq: u64,
+    ) -> EikvResult<CompactionInputs<'_, K, V>> {
+        let mut files = HashMap::new();
+        files.insert(
+            sst_path(db_path, file_seq)?,
+            self.sst_meta(cf_id, level, file_seq),
+        );
+        Ok((files, vec![], vec![file_seq]))
+    }
+
     pub(crate) fn wals(&self) -> &HashSet<u64> {
         &self.wals
     }
 
+    // The files the manifest refers to, the current manifest included.
+    pub(crate) fn live_files(&self, db_path: &str) -> EikvResult<HashSet<String>> {
+        let manifest_seq = Manifest::<K, V>::read_current(db_path)?;
+        let mut live_files = HashSet::from([manifest_path(db_path, manifest_seq)?]);
+        for file_seq in &self.wals {
+            live_files.insert(wal_path(db_path, *file_seq)?);
+        }
+        for column_family in self.column_families.values() {
+            for sst_level in &column_family.sstables {
+                for file_seq in sst_level.keys() {
+                    live_files.insert(sst_path(db_path, *file_seq)?);
+                }
+            }
+        }
+        Ok(live_files)
+    }
+
     pub(crate) fn alloc_wal(&mut self) -> u64 {
         let file_seq = self.next_file_seq;
         self.next_file_seq += 1;
@@ -147,10 +327,10 @@ impl<K: Key, V: Value> Manifest<K, V> {
         file_seq
     }
 
-    pub(crate) fn alloc_sst(&mut self, level: usize) -> u64 {
+    pub(crate) fn alloc_sst(&mut self, cf_id: u32, level: usize) -> u64 {
         let file_seq = self.next_file_seq;
         self.next_file_seq += 1;
-        self.get_mut_level(level).insert(file_seq, None);
+        self.get_mut_level(cf_id, level).insert(file_seq, None);
         file_seq
     }
 
@@ -162,8 +342,16 @@ impl<K: Key, V: Value> Manifest<K, V> {
         file_seq
     }
 
-    pub(crate) fn remove_sst(&mut self, level: usize, file_seq: u64) {
-        self.get_mut_level(level).remove(&file_seq);
+    // Moves an SST to the next level, the file keeps its seq.
+    pub(crate) fn move_sst(&mut self, cf_id: u32, level: usize, file_seq: u64) {
+        if let Some(sst_meta) = self.get_mut_level(cf_id, level).remove(&file_seq) {
+            self.get_mut_level(cf_id, level + 1)
+                .insert(file_seq, sst_meta);
+        }
+    }
+
+    pub(crate) fn remove_sst(&mut self, cf_id: u32, level: usize, file_seq: u64) {
+        self.get_mut_level(cf_id, level).remove(&file_seq);
     }
 
     pub(crate) fn dump(&self, db_path: &str) -> EikvResult<()> {
@@ -176,14 +364,58 @@ impl<K: Key, V: Value> Manifest<K, V> {
         let old_manifest_path = manifest_path(db_path, manifest_seq)?;
         let manifest_path = manifest_path(db_path, manifest_seq + 1)?;
         let mut file = File::create(manifest_path)?;
+        let line = format!("comparator:{}\n", self.comparator);
+        file.write_all(line.as_bytes())?;
+        let line = format!("next_cf_id:{}\n", self.next_cf_id);
+        file.write_all(line.as_bytes())?;
+        for (cf_id, column_family) in &self.column_families {
+            let line = format!("cf:{}:{}\n", cf_id, column_family.name);
+            file.write_all(line.as_bytes())?;
+        }
         for file_seq in &self.wals {
             let line = format!("{:06}.wal\n", file_seq);
-            file.write(line.as_bytes())?;
+            file.write_all(line.as_bytes())?;
         }
-        for sst_level in &self.sstables {
-            for file_seq in sst_level.keys() {
-                let line = format!("{:06}.sst\n", file_seq);
-                file.write(line.as_bytes())?;
+        for (cf_id, column_family) in &self.column_families {
+            for (level, sst_level) in (LEVEL_MIN..=LEVEL_MAX).zip(&column_family.sstables) {
+                for (file_seq, sst_meta) in sst_level {
+                    let sst_meta = match sst_meta {
+                        Some(sst_meta) => sst_meta,
+                        None => {
+                            let reason = format!("sstable {file_seq} isn't written yet");
+                            return Err(EikvError::ManifestError(reason));
+                        }
+                    };
+                    let mut range_tombstones = vec![];
+                    for range_tombstone in &sst_meta.range_tombstones {
+                        range_tombstones.push(format!(
+                            "{}-{}-{}",
+                            to_hex(&range_tombstone.start.to_bytes()?),
+                            to_hex(&range_tombstone.end.to_bytes()?),
+                            range_tombstone.seq
+                        ));
+                    }
+                    // The footer of the file is recorded as well, so it isn't
+                    // read on open.
+                    let line = format!(
+                        "sst:{}:{}:{:06}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}:{}\n",
+                        cf_id,
+                        level,
+                        file_seq,
+                        sst_meta.file_size,
+                        to_hex(&sst_meta.min_entry.key.to_bytes()?),
+                        to_hex(&sst_meta.max_entry.key.to_bytes()?),
+                        sst_meta.footer_version,
+                        sst_meta.data_block_count,
+                        sst_meta.data_block_end,
+                        sst_meta.range_del_block_end,
+                        sst_meta.index_key_block_end,
+                        sst_meta.max_seq,
+                        sst_meta.min_expire_at,
+                        range_tombstones.join(","),
+                    );
+                    file.write_all(line.as_bytes())?;
+                }
             }
         }
 
@@ -198,7 +430,7 @@ impl<K: Key, V: Value> Manifest<K, V> {
     fn write_current(current_path: &str, manifest_seq: u64) -> EikvResult<()> {
         let mut file = File::create(current_path)?;
         let content = format!("{:06}", manifest_seq);
-        file.write(content.as_bytes())?;
+        file.write_all(content.as_bytes())?;
         Ok(())
     }
 
@@ -229,13 +461,57 @@ impl<K: Key, V: Value> Manifest<K, V> {
         Ok(())
     }
 
-    pub(crate) fn load(db_path: &str, block_size: usize) -> EikvResult<Manifest<K, V>> {
+    // The block size of a column family is looked up by its name.
+    pub(crate) fn load(
+        db_path: &str,
+        comparator: &str,
+        cf_block_size: impl Fn(&str) -> usize,
+    ) -> EikvResult<Manifest<K, V>> {
         let manifest_seq = Manifest::<K, V>::read_current(db_path)?;
         let manifest_path = manifest_path(db_path, manifest_seq)?;
         let file = File::open(&manifest_path).unwrap();
-        let mut manifest = Manifest::new();
+        let mut manifest = Manifest::new(comparator);
+        let mut legacy_levels = HashMap::new();
+        let mut moves = vec![];
+        let mut migrated = false;
         for line in BufReader::new(file).lines() {
             let line = line?;
+            if let Some(name) = line.strip_prefix("comparator:") {
+                if name != comparator {
+                    let reason = format!(
+                        "the db was created with comparator {}, opened with {}",
+                        name, comparator
+                    );
+                    return Err(EikvError::ManifestError(reason));
+                }
+                continue;
+            }
+
+            let parse_err = |err: ParseIntError| {
+                let reason = format!("failed to parse manifest line: line={line}, err={err}");
+                EikvError::ManifestError(reason)
+            };
+
+            if let Some(next_cf_id) = line.strip_prefix("next_cf_id:") {
+                manifest.next_cf_id = next_cf_id.parse().map_err(parse_err)?;
+                continue;
+            }
+
+            if let Some(cf) = line.strip_prefix("cf:") {
+                let (cf_id, name) = match cf.split_once(':') {
+                    Some(cf) => cf,
+                    None => {
+                        let reason = format!("failed to parse manifest line: line={line}");
+                        return Err(EikvError::ManifestError(reason));
+                    }
+                };
+                let cf_id = cf_id.parse().map_err(parse_err)?;
+                manifest
+                    .column_families
+                    .insert(cf_id, ColumnFamily::new(name));
+                continue;
+            }
+
             if line.ends_with(".wal") {
                 let file_seq = match line[..line.len() - 4].parse() {
                     Ok(file_seq) => file_seq,
@@ -246,11 +522,107 @@ impl<K: Key, V: Value> Manifest<K, V> {
                     }
                 };
                 manifest.wals.insert(file_seq);
+                manifest.next_file_seq = max(manifest.next_file_seq, file_seq + 1);
                 continue;
             }
 
+            if let Some(sst) = line.strip_prefix("sst:") {
+                let fields: Vec<&str> = sst.split(':').collect();
+                if fields.len() != 6 && fields.len() != 14 {
+                    let reason = format!("failed to parse manifest line: line={line}");
+                    return Err(EikvError::ManifestError(reason));
+                }
+                let cf_id = fields[0].parse().map_err(parse_err)?;
+                let level = fields[1].parse().map_err(parse_err)?;
+                let file_seq = fields[2].parse().map_err(parse_err)?;
+                let file_size: u64 = fields[3].parse().map_err(parse_err)?;
+                if !(LEVEL_MIN..=LEVEL_MAX).contains(&level) {
+                    let reason = format!("invalid level {level}: line={line}");
+                    return Err(EikvError::ManifestError(reason));
+                }
+                let block_size = manifest.cf_block_size(cf_id, &cf_block_size, &line)?;
+
+                // A migration may have stopped before the file was moved.
+                let mut sst_path = sst_path(db_path, file_seq)?;
+                let legacy_path = sst_legacy_path(db_path, level, file_seq)?;
+                if !Path::new(&sst_path).try_exists()? && Path::new(&legacy_path).try_exists()? {
+                    moves.push((legacy_path.clone(), sst_path));
+                    sst_path = legacy_path;
+                }
+                if metadata(&sst_path)?.len() != file_size {
+                    let reason = format!("{sst_path} doesn't match the manifest: line={line}");
+                    return Err(EikvError::SstCorrpution(reason));
+                }
+
+                // Lines without the footer are rewritten with the one read
+                // from the file.
+                if fields.len() == 6 {
+                    let sst_meta: SstMeta<K, V> = SstMeta::new(&sst_path, block_size)?;
+                    if to_hex(&sst_meta.min_entry.key.to_bytes()?) != fields[4]
+                        || to_hex(&sst_meta.max_entry.key.to_bytes()?) != fields[5]
+                    {
+                        let reason = format!("{sst_path} doesn't match the manifest: line={line}");
+                        return Err(EikvError::SstCorrpution(reason));
+                    }
+                    manifest.insert_loaded_sst(cf_id, level, file_seq, sst_meta);
+                    migrated = true;
+                    continue;
+                }
+
+                let corrupt = || {
+                    let reason = format!("failed to parse manifest line: line={line}");
+                    EikvError::ManifestError(reason)
+                };
+                // Only the keys of the smallest and the largest entries are
+                // recorded.
+                let bound = |hex: &str| -> EikvResult<Entry<K, V>> {
+                    let key = K::decode(&from_hex(hex).ok_or_else(corrupt)?)?;
+                    Ok(Entry {
+                        key,
+                        seq: 0,
+                        op: Op::Delete,
+                    })
+                };
+                let footer = Footer {
+                    version: fields[6].parse().map_err(parse_err)?,
+                    min_entry: bound(fields[4])?,
+                    max_entry: bound(fields[5])?,
+                    data_block_count: fields[7].parse().map_err(parse_err)?,
+                    data_block_end: fields[8].parse().map_err(parse_err)?,
+                    range_del_block_end: fields[9].parse().map_err(parse_err)?,
+                    index_key_block_end: fields[10].parse().map_err(parse_err)?,
+                    max_seq: fields[11].parse().map_err(parse_err)?,
+                    min_expire_at: fields[12].parse().map_err(parse_err)?,
+                };
+                let mut range_tombstones = vec![];
+                for range_tombstone in fields[13].split(',').filter(|field| !field.is_empty()) {
+                    let parts: Vec<&str> = range_tombstone.split('-').collect();
+                    if parts.len() != 3 {
+                        return Err(corrupt());
+                    }
+                    range_tombstones.push(RangeTombstone {
+                        start: K::decode(&from_hex(parts[0]).ok_or_else(corrupt)?)?,
+                        end: K::decode(&from_hex(parts[1]).ok_or_else(corrupt)?)?,
+                        seq: parts[2].parse().map_err(parse_err)?,
+                    });
+                }
+                let sst_meta =
+                    SstMeta::from_footer(footer, block_size, file_size, range_tombstones);
+                manifest.insert_loaded_sst(cf_id, level, file_seq, sst_meta);
+                continue;
+            }
+
+            // SSTs used to be listed without their levels, which were found
+            // by the dirs holding them. They are moved out of those dirs once
+            // the manifest records their levels.
             if line.ends_with(".sst") {
-                let file_seq = match line[..line.len() - 4].parse() {
+                // Lines without a column family are of the default one.
+                let (cf_id, file_name) = match line.split_once(':') {
+                    Some((cf_id, file_name)) => (cf_id.parse().map_err(parse_err)?, file_name),
+                    None => (DEFAULT_CF_ID, line.as_str()),
+                };
+                let block_size = manifest.cf_block_size(cf_id, &cf_block_size, &line)?;
+                let file_seq = match file_name[..file_name.len() - 4].parse() {
                     Ok(file_seq) => file_seq,
                     Err(err) => {
                         let reason =
@@ -258,44 +630,141 @@ impl<K: Key, V: Value> Manifest<K, V> {
                         return Err(EikvError::ManifestError(reason));
                     }
                 };
-                let level = get_level(db_path, file_seq)?;
-                let sst_path = sst_path(db_path, level, file_seq)?;
-                let sst_meta = SstMeta::new(&sst_path, block_size)?;
-                manifest
-                    .get_mut_level(level)
-                    .insert(file_seq, Some(sst_meta));
+                if legacy_levels.is_empty() {
+                    legacy_levels = legacy_sst_levels(db_path)?;
+                }
+                let level = match legacy_levels.get(&file_seq) {
+                    Some(level) => *level,
+                    None => {
+                        let reason = format!("can't find the file seq {file_seq}");
+                        return Err(EikvError::ManifestError(reason));
+                    }
+                };
+                let legacy_path = sst_legacy_path(db_path, level, file_seq)?;
+                let sst_meta = SstMeta::new(&legacy_path, block_size)?;
+                moves.push((legacy_path, sst_path(db_path, file_seq)?));
+                manifest.insert_loaded_sst(cf_id, level, file_seq, sst_meta);
+                migrated = true;
                 continue;
             }
         }
 
+        if migrated {
+            manifest.dump(db_path)?;
+        }
+        for (legacy_path, sst_path) in moves {
+            rename(legacy_path, sst_path)?;
+        }
+        remove_legacy_level_dirs(db_path, &manifest.live_files(db_path)?)?;
+
         Ok(manifest)
     }
 
-    pub(crate) fn set_sst_meta(&mut self, level: usize, file_seq: u64, sst_meta: SstMeta<K, V>) {
-        self.get_mut_level(level).insert(file_seq, Some(sst_meta));
+    fn cf_block_size(
+        &self,
+        cf_id: u32,
+        cf_block_size: impl Fn(&str) -> usize,
+        line: &str,
+    ) -> EikvResult<usize> {
+        match self.cf_name(cf_id) {
+            Some(name) => Ok(cf_block_size(name)),
+            None => {
+                let reason = format!("unknown column family {cf_id}: line={line}");
+                Err(EikvError::ManifestError(reason))
+            }
+        }
+    }
+
+    fn insert_loaded_sst(
+        &mut self,
+        cf_id: u32,
+        level: usize,
+        file_seq: u64,
+        sst_meta: SstMeta<K, V>,
+    ) {
+        self.get_mut_level(cf_id, level)
+            .insert(file_seq, Some(sst_meta));
+        self.next_file_seq = max(self.next_file_seq, file_seq + 1);
+    }
+
+    pub(crate) fn set_sst_meta(
+        &mut self,
+        cf_id: u32,
+        level: usize,
+        file_seq: u64,
+        sst_meta: SstMeta<K, V>,
+    ) {
+        self.get_mut_level(cf_id, level)
+            .insert(file_seq, Some(sst_meta));
+    }
+}
+
+// The levels of the SSTs by their seqs, found by the level dirs holding them.
+fn legacy_sst_levels(db_path: &str) -> EikvResult<HashMap<u64, usize>> {
+    let mut levels = HashMap::new();
+    for level in LEVEL_MIN..=LEVEL_MAX {
+        let sst_level_dir = sst_level_dir_path(db_path, level)?;
+        if !Path::new(&sst_level_dir).try_exists()? {
+            continue;
+        }
+        for entry in fs::read_dir(sst_level_dir)? {
+            let file_name = entry?.file_name();
+            let file_seq = file_name
+                .to_str()
+                .and_then(|file_name| file_name.strip_suffix(".sst"))
+                .and_then(|file_seq| file_seq.parse().ok());
+            if let Some(file_seq) = file_seq {
+                levels.insert(file_seq, level);
+            }
+        }
     }
+    Ok(levels)
 }
 
-fn get_level(db_path: &str, file_seq: u64) -> EikvResult<usize> {
+// A file left in an old level dir is removed if the manifest doesn't refer to
+// it or it was moved already. A dir still holding a file to move is kept.
+fn remove_legacy_level_dirs(db_path: &str, live_files: &HashSet<String>) -> EikvResult<()> {
     for level in LEVEL_MIN..=LEVEL_MAX {
-        let sst_dir = sst_level_dir_path(db_path, level)?;
-        let sst_name = format!("{:06}.sst", file_seq);
-        for entry in fs::read_dir(sst_dir)? {
-            let entry = entry?;
-            match entry.file_name().to_str() {
-                Some(file_name) => {
-                    if file_name == sst_name {
-                        return Ok(level);
-                    }
-                }
-                None => {
-                    return Err(EikvError::PathError(
-                        "failed to read sstable dir".to_owned(),
-                    ))
+        let sst_level_dir = sst_level_dir_path(db_path, level)?;
+        if !Path::new(&sst_level_dir).try_exists()? {
+            continue;
+        }
+        let mut has_unmoved = false;
+        for entry in fs::read_dir(&sst_level_dir)? {
+            let path = entry?.path();
+            let file_seq = path
+                .file_name()
+                .and_then(|file_name| file_name.to_str())
+                .and_then(|file_name| file_name.strip_suffix(".sst"))
+                .and_then(|file_seq| file_seq.parse().ok());
+            if let Some(file_seq) = file_seq {
+                let sst_path = sst_path(db_path, file_seq)?;
+                if live_files.contains(&sst_path) && !Path::new(&sst_path).try_exists()? {
+                    has_unmoved = true;
+                    continue;
                 }
             }
+            if path.is_file() {
+                remove_file(path)?;
+            }
         }
+        if !has_unmoved && fs::read_dir(&sst_level_dir)?.next().is_none() {
+            fs::remove_dir(sst_level_dir)?;
+        }
+    }
+    Ok(())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
     }
-    let reason = format!("can't find the file seq {file_seq}");
-    Err(EikvError::ManifestError(reason))
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
 }
@@ -0,0 +1,172 @@
+use super::{Entry, Op};
+use crate::{util::time::unix_now, EikvError, EikvResult, Key, Value};
+use std::{error::Error, sync::Arc};
+
+pub trait MergeOperator: Send + Sync {
+    fn full_merge(
+        &self,
+        key: &[u8],
+        existing_value: Option<&[u8]>,
+        operands: &[Vec<u8>],
+    ) -> Result<Vec<u8>, Box<dyn Error>>;
+
+    fn partial_merge(
+        &self,
+        _key: &[u8],
+        _left_operand: &[u8],
+        _right_operand: &[u8],
+    ) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        Ok(None)
+    }
+}
+
+// Appends the versions of a key, newest first, until one of them hides all
+// older versions. Returns whether such a version was found.
+pub(crate) fn collect_versions<'a, K: Key + 'a, V: Value + 'a>(
+    key: &K,
+    versions: impl IntoIterator<Item = &'a Entry<K, V>>,
+    tombstone_seq: Option<u64>,
+    chain: &mut Vec<Entry<K, V>>,
+) -> bool {
+    for entry in versions {
+        if let Some(seq) = tombstone_seq.filter(|seq| *seq > entry.seq) {
+            chain.push(Entry {
+                key: key.clone(),
+                seq,
+                op: Op::Delete,
+            });
+            return true;
+        }
+        chain.push(entry.clone());
+        if !matches!(entry.op, Op::Merge(_)) {
+            return true;
+        }
+    }
+
+    if let Some(seq) = tombstone_seq {
+        chain.push(Entry {
+            key: key.clone(),
+            seq,
+            op: Op::Delete,
+        });
+        return true;
+    }
+    false
+}
+
+pub(crate) fn full_merge<K: Key, V: Value>(
+    merge_operator: Option<&Arc<dyn MergeOperator>>,
+    key: &K,
+    existing_value: Option<V>,
+    operands: Vec<V>,
+) -> EikvResult<V> {
+    let merge_operator = match merge_operator {
+        Some(merge_operator) => merge_operator,
+        None => {
+            let reason = "merge operator is not configured".to_owned();
+            return Err(EikvError::MergeError(reason));
+        }
+    };
+
+    let key_bytes = key.to_bytes()?;
+    let existing_value = match existing_value {
+        Some(value) => Some(value.to_bytes()?),
+        None => None,
+    };
+    let mut operand_bytes = Vec::with_capacity(operands.len());
+    for operand in operands {
+        operand_bytes.push(operand.to_bytes()?);
+    }
+
+    let value = merge_operator.full_merge(&key_bytes, existing_value.as_deref(), &operand_bytes)?;
+    let value = V::decode(&value)?;
+    Ok(value)
+}
+
+// Resolves a chain built by `collect_versions` to the value of the key.
+pub(crate) fn merge_versions<K: Key, V: Value>(
+    merge_operator: Option<&Arc<dyn MergeOperator>>,
+    key: &K,
+    chain: Vec<Entry<K, V>>,
+) -> EikvResult<Option<V>> {
+    let now = unix_now() as u64;
+    let mut operands = vec![];
+    let mut existing_value = None;
+    let mut last_seq = u64::MAX;
+    for entry in chain {
+        // A flush installed between reading the memtables and the SSTs hands
+        // the same versions over twice.
+        if entry.seq >= last_seq {
+            continue;
+        }
+        last_seq = entry.seq;
+        match entry.op {
+            Op::Merge(operand) => operands.push(operand),
+            Op::Put(value) => {
+                existing_value = Some(value);
+                break;
+            }
+            Op::PutWithExpiry(value, expire_at) => {
+                if expire_at > now {
+                    existing_value = Some(value);
+                }
+                break;
+            }
+            _ => break,
+        }
+    }
+
+    if operands.is_empty() {
+        return Ok(existing_value);
+    }
+    operands.reverse();
+    let value = full_merge(merge_operator, key, existing_value, operands)?;
+    Ok(Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_versions, Entry, MergeOperator, Op};
+    use std::{error::Error, sync::Arc};
+
+    struct Concat;
+
+    impl MergeOperator for Concat {
+        fn full_merge(
+            &self,
+            _key: &[u8],
+            existing_value: Option<&[u8]>,
+            operands: &[Vec<u8>],
+        ) -> Result<Vec<u8>, Box<dyn Error>> {
+            let mut value = existing_value.unwrap_or_default().to_vec();
+            for operand in operands {
+                value.extend_from_slice(operand);
+            }
+            Ok(value)
+        }
+    }
+
+    fn entry(seq: u64, op: Op<Vec<u8>, Vec<u8>>) -> Entry<Vec<u8>, Vec<u8>> {
+        Entry {
+            key: b"key".to_vec(),
+            seq,
+            op,
+        }
+    }
+
+    #[test]
+    fn test_merge_repeated_versions() {
+        let merge_operator: Arc<dyn MergeOperator> = Arc::new(Concat);
+        // The memtable versions followed by the same versions read from a
+        // freshly flushed SST.
+        let chain = vec![
+            entry(3, Op::Merge(b"c".to_vec())),
+            entry(2, Op::Merge(b"b".to_vec())),
+            entry(3, Op::Merge(b"c".to_vec())),
+            entry(2, Op::Merge(b"b".to_vec())),
+            entry(1, Op::Put(b"a".to_vec())),
+        ];
+        let value = merge_versions(Some(&merge_operator), &b"key".to_vec(), chain).unwrap();
+        assert_eq!(Some(b"abc".to_vec()), value);
+    }
+}
@@ -1,11 +1,20 @@
+mod compaction_filter;
+mod comparator;
 mod entry;
 mod key;
 mod manifest;
+mod merge_operator;
+mod range_tombstone;
 mod sst_meta;
 mod value;
 
-pub(crate) use entry::Entry;
+pub use compaction_filter::{CompactionDecision, CompactionFilter, CompactionFilterContext};
+pub use comparator::{BytewiseComparator, Comparator};
+pub(crate) use entry::{Entry, Op};
 pub use key::Key;
 pub(crate) use manifest::Manifest;
+pub use merge_operator::MergeOperator;
+pub(crate) use merge_operator::{collect_versions, full_merge, merge_versions};
+pub(crate) use range_tombstone::{max_covering_seq, RangeTombstone};
 pub(crate) use sst_meta::SstMeta;
 pub use value::Value;
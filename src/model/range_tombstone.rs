@@ -0,0 +1,35 @@
+use super::{entry::Op, Entry};
+use crate::{Key, Value};
+
+#[derive(Clone)]
+pub(crate) struct RangeTombstone<K: Key> {
+    pub(crate) start: K,
+    pub(crate) end: K,
+    pub(crate) seq: u64,
+}
+
+impl<K: Key> RangeTombstone<K> {
+    pub(crate) fn contains(&self, key: &K) -> bool {
+        self.start <= *key && *key < self.end
+    }
+
+    pub(crate) fn to_entry<V: Value>(&self) -> Entry<K, V> {
+        Entry {
+            key: self.start.clone(),
+            seq: self.seq,
+            op: Op::DeleteRange(self.end.clone()),
+        }
+    }
+}
+
+pub(crate) fn max_covering_seq<'a, K: Key + 'a>(
+    range_tombstones: impl IntoIterator<Item = &'a RangeTombstone<K>>,
+    key: &K,
+    seq_guard: u64,
+) -> Option<u64> {
+    range_tombstones
+        .into_iter()
+        .filter(|range_tombstone| range_tombstone.seq <= seq_guard && range_tombstone.contains(key))
+        .map(|range_tombstone| range_tombstone.seq)
+        .max()
+}
@@ -1,25 +1,65 @@
-use std::fs::metadata;
+use std::{
+    fs::{metadata, File},
+    io::{Read, Seek, SeekFrom},
+    sync::{Arc, OnceLock},
+};
 
-use super::Entry;
-use crate::{sst::Footer, EikvResult, Key, Value};
+use super::{entry::Op, Entry, RangeTombstone};
+use crate::{
+    sst::{read_index_keys, Footer},
+    util::{
+        checksum::crc32_checksum,
+        coding::{decode_bytes_with_len, decode_fixed_u32},
+    },
+    EikvError, EikvResult, Key, Value,
+};
 
 #[derive(Clone)]
 pub(crate) struct SstMeta<K: Key, V: Value> {
     pub(crate) file_size: u64,
     pub(crate) block_size: usize,
+    pub(crate) footer_version: u32,
     pub(crate) data_block_count: u32,
     pub(crate) data_block_end: u64,
     pub(crate) index_block_start: u64,
     pub(crate) index_block_end: u64,
+    pub(crate) range_del_block_end: u64,
+    pub(crate) index_key_block_end: u64,
+    // The index key of every data block followed by an upper bound of the
+    // keys in the file, read on the first lookup of a file loaded from the
+    // manifest.
+    index_keys: Arc<OnceLock<Arc<Vec<Vec<u8>>>>>,
     pub(crate) min_entry: Entry<K, V>,
     pub(crate) max_entry: Entry<K, V>,
+    pub(crate) max_seq: u64,
+    pub(crate) min_expire_at: u64,
+    pub(crate) range_tombstones: Vec<RangeTombstone<K>>,
 }
 
 impl<K: Key, V: Value> SstMeta<K, V> {
     pub(crate) fn new(path: &str, block_size: usize) -> EikvResult<SstMeta<K, V>> {
         let footer = Footer::load(path)?;
+        let file_size = metadata(path)?.len();
+        let mut sst_meta = SstMeta::from_footer(footer, block_size, file_size, vec![]);
+        if sst_meta.footer_version != 0 {
+            sst_meta.range_tombstones = load_range_tombstones::<K, V>(
+                path,
+                sst_meta.index_block_end,
+                sst_meta.range_del_block_end,
+            )?;
+        }
+        sst_meta.index_keys(path)?;
+        Ok(sst_meta)
+    }
 
-        let padding_size = if footer.data_block_end % block_size as u64 == 0 {
+    // The footer may come from the manifest, the file isn't read then.
+    pub(crate) fn from_footer(
+        footer: Footer<K, V>,
+        block_size: usize,
+        file_size: u64,
+        range_tombstones: Vec<RangeTombstone<K>>,
+    ) -> SstMeta<K, V> {
+        let padding_size = if footer.data_block_end.is_multiple_of(block_size as u64) {
             0
         } else {
             let block_size = block_size as u64;
@@ -27,23 +67,128 @@ impl<K: Key, V: Value> SstMeta<K, V> {
         };
         let index_block_start = footer.data_block_end + padding_size;
 
-        let offset_count_one_block = block_size as usize / 8 - 1;
-        let index_block_count = (footer.data_block_count as usize + offset_count_one_block - 1)
-            / offset_count_one_block;
+        let offset_count_one_block = block_size / 8 - 1;
+        let index_block_count = (footer.data_block_count as usize).div_ceil(offset_count_one_block);
         let index_block_end = index_block_start + index_block_count as u64 * block_size as u64;
 
-        let file_size = metadata(path)?.len();
-
-        let sst_meta = SstMeta {
+        SstMeta {
+            file_size,
             block_size,
+            footer_version: footer.version,
             data_block_count: footer.data_block_count,
             data_block_end: footer.data_block_end,
             index_block_start,
             index_block_end,
+            range_del_block_end: footer.range_del_block_end,
+            index_key_block_end: footer.index_key_block_end,
+            index_keys: Arc::new(OnceLock::new()),
             min_entry: footer.min_entry,
             max_entry: footer.max_entry,
-            file_size,
+            max_seq: footer.max_seq,
+            min_expire_at: footer.min_expire_at,
+            range_tombstones,
+        }
+    }
+
+    pub(crate) fn index_keys(&self, path: &str) -> EikvResult<Arc<Vec<Vec<u8>>>> {
+        if let Some(index_keys) = self.index_keys.get() {
+            return Ok(index_keys.clone());
+        }
+        let index_keys = if self.footer_version == 0 {
+            read_index_keys(path, self.clone())?
+        } else {
+            load_index_keys(path, self.range_del_block_end, self.index_key_block_end)?
         };
-        Ok(sst_meta)
+        if index_keys.len() != self.data_block_count as usize + 1 {
+            let reason = format!(
+                "{} index keys for {} data blocks",
+                index_keys.len(),
+                self.data_block_count
+            );
+            return Err(EikvError::SstCorrpution(reason));
+        }
+        Ok(self.index_keys.get_or_init(|| Arc::new(index_keys)).clone())
+    }
+
+    // Whether the tombstone hides everything in the file, the tombstones of
+    // the file included.
+    pub(crate) fn covered_by(&self, range_tombstone: &RangeTombstone<K>) -> bool {
+        range_tombstone.seq > self.max_seq
+            && range_tombstone.contains(&self.min_entry.key)
+            && range_tombstone.contains(&self.max_entry.key)
+            && self
+                .range_tombstones
+                .iter()
+                .all(|own| range_tombstone.start <= own.start && own.end <= range_tombstone.end)
+    }
+}
+
+fn read_block(path: &str, start: u64, end: u64, name: &str) -> EikvResult<Vec<u8>> {
+    let block_size = (end - start) as usize;
+    if block_size < 4 {
+        let reason = format!("{} block size is {}", name, block_size);
+        return Err(EikvError::SstCorrpution(reason));
+    }
+
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut block = vec![0; block_size];
+    let n = file.read(&mut block)?;
+    if n != block_size {
+        let reason = format!("{} block size is {}, read {} bytes", name, block_size, n);
+        return Err(EikvError::SstCorrpution(reason));
+    }
+
+    let checksum = decode_fixed_u32(&block[block_size - 4..]);
+    block.truncate(block_size - 4);
+    if crc32_checksum(&block) != checksum {
+        let reason = format!("the checksum of the {} block doesn't match", name);
+        return Err(EikvError::SstCorrpution(reason));
+    }
+    Ok(block)
+}
+
+fn load_index_keys(path: &str, start: u64, end: u64) -> EikvResult<Vec<Vec<u8>>> {
+    let buf = read_block(path, start, end, "index key")?;
+    let mut index_keys = vec![];
+    let mut buf_off = 0;
+    while buf_off < buf.len() {
+        match decode_bytes_with_len(&buf[buf_off..]) {
+            Some((index_key, n)) => {
+                buf_off += n;
+                index_keys.push(index_key);
+            }
+            None => {
+                let reason = "index key block is corrupt".to_owned();
+                return Err(EikvError::SstCorrpution(reason));
+            }
+        }
+    }
+    Ok(index_keys)
+}
+
+fn load_range_tombstones<K: Key, V: Value>(
+    path: &str,
+    start: u64,
+    end: u64,
+) -> EikvResult<Vec<RangeTombstone<K>>> {
+    let buf = read_block(path, start, end, "range deletion")?;
+    let mut range_tombstones = vec![];
+    let mut buf_off = 0;
+    while buf_off < buf.len() {
+        let (entry, n): (Entry<K, V>, usize) = Entry::decode(&buf[buf_off..])?;
+        buf_off += n;
+        match entry.op {
+            Op::DeleteRange(end) => range_tombstones.push(RangeTombstone {
+                start: entry.key,
+                end,
+                seq: entry.seq,
+            }),
+            _ => {
+                let reason = "range deletion block is corrupt".to_owned();
+                return Err(EikvError::SstCorrpution(reason));
+            }
+        }
     }
+    Ok(range_tombstones)
 }
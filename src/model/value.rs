@@ -1,27 +1,35 @@
 use std::error::Error;
 
 pub trait Value: Clone + Send + Sync {
-    fn encode(self) -> Result<Vec<u8>, Box<dyn Error>>;
-    fn decode(bytes: Vec<u8>) -> Result<Self, Box<dyn Error>>;
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>>;
+    fn decode(bytes: &[u8]) -> Result<Self, Box<dyn Error>>;
+
+    fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut buf = vec![];
+        self.encode(&mut buf)?;
+        Ok(buf)
+    }
 }
 
 impl Value for Vec<u8> {
-    fn encode(self) -> Result<Vec<u8>, Box<dyn Error>> {
-        Ok(self)
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        buf.extend(self);
+        Ok(())
     }
 
-    fn decode(bytes: Vec<u8>) -> Result<Self, Box<dyn Error>> {
-        Ok(bytes)
+    fn decode(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        Ok(bytes.to_vec())
     }
 }
 
 impl Value for String {
-    fn encode(self) -> Result<Vec<u8>, Box<dyn Error>> {
-        Ok(self.into_bytes())
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        buf.extend(self.as_bytes());
+        Ok(())
     }
 
-    fn decode(bytes: Vec<u8>) -> Result<Self, Box<dyn Error>> {
-        let s = String::from_utf8(bytes)?;
-        Ok(s)
+    fn decode(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let s = std::str::from_utf8(bytes)?;
+        Ok(s.to_owned())
     }
 }
@@ -1,5 +1,5 @@
 use crate::{
-    model::Entry,
+    model::{Entry, Op},
     util::{
         checksum::crc32_checksum,
         coding::{
@@ -7,19 +7,14 @@ use crate::{
             decode_fixed_u32, decode_var_u32, decode_var_u64,
         },
     },
-    Compressor, DBOptions, EikvError, EikvResult, Filter, FilterFactory, Key, Value,
+    Comparator, Compressor, DBOptions, EikvError, EikvResult, Filter, FilterFactory, Key, Value,
 };
 use std::{
     cmp::{min, Ordering},
     sync::Arc,
 };
 
-struct SharePrefixEntry {
-    shared_len: u32,
-    unshared_key: Vec<u8>,
-    seq: u64,
-    value: Option<Vec<u8>>,
-}
+type RawEntry = Entry<Vec<u8>, Vec<u8>>;
 
 fn shared_len(s1: &[u8], s2: &[u8]) -> usize {
     let min_len = min(s1.len(), s2.len());
@@ -31,87 +26,66 @@ fn shared_len(s1: &[u8], s2: &[u8]) -> usize {
     min_len
 }
 
-impl SharePrefixEntry {
-    fn new(key: &[u8], seq: u64, value: Option<Vec<u8>>, prev_key: &[u8]) -> SharePrefixEntry {
-        let shared_len = shared_len(key, prev_key);
-        let unshared_key = key[shared_len..].to_vec();
-        let shared_len = shared_len as u32;
-        SharePrefixEntry {
-            shared_len,
-            unshared_key,
-            seq,
-            value,
-        }
+fn decode_share_prefix_entry(buf: &[u8], prev_key: Vec<u8>) -> Option<(RawEntry, usize)> {
+    let (shared_len, mut buf_off) = match decode_var_u32(buf) {
+        Some((shared_len, n)) => (shared_len as usize, n),
+        None => return None,
+    };
+    if shared_len > prev_key.len() {
+        return None;
     }
 
-    fn encode(&self, buf: &mut Vec<u8>) {
-        append_var_u32(buf, self.shared_len);
-        append_var_u32(buf, self.unshared_key.len() as u32);
-        buf.extend(&self.unshared_key);
-        append_var_u64(buf, self.seq);
-        match &self.value {
-            Some(value) => {
-                buf.push(1);
-                append_var_u32(buf, value.len() as u32);
-                buf.extend(value);
-            }
-            None => buf.push(2),
+    let unshared_key = match decode_bytes_with_len(&buf[buf_off..]) {
+        Some((unshared_key, n)) => {
+            buf_off += n;
+            unshared_key
         }
-    }
-
-    fn decode(buf: &[u8], prev_key: Vec<u8>) -> Option<(Entry<Vec<u8>, Vec<u8>>, usize)> {
-        let (shared_len, mut buf_off) = match decode_var_u32(buf) {
-            Some((shared_len, n)) => (shared_len as usize, n),
-            None => return None,
-        };
-        if shared_len > prev_key.len() {
-            return None;
+        None => return None,
+    };
+    let mut key = prev_key;
+    key.truncate(shared_len);
+    key.extend(&unshared_key);
+
+    let seq = match decode_var_u64(&buf[buf_off..]) {
+        Some((seq, n)) => {
+            buf_off += n;
+            seq
         }
+        None => return None,
+    };
 
-        let unshared_key = match decode_bytes_with_len(&buf[buf_off..]) {
-            Some((unshared_key, n)) => {
-                buf_off += n;
-                unshared_key
-            }
-            None => return None,
-        };
-        let mut key = prev_key;
-        key.truncate(shared_len);
-        key.extend(&unshared_key);
-
-        let seq = match decode_var_u64(&buf[buf_off..]) {
-            Some((seq, n)) => {
-                buf_off += n;
-                seq
-            }
-            None => return None,
-        };
-
-        if buf_off == buf.len() {
-            return None;
+    if buf_off == buf.len() {
+        return None;
+    }
+    buf_off += 1;
+    let op = match buf[buf_off - 1] {
+        1 => {
+            let (value, n) = decode_bytes_with_len(&buf[buf_off..])?;
+            buf_off += n;
+            Op::Put(value)
         }
-        buf_off += 1;
-        let mut entry = Entry {
-            key,
-            seq,
-            value: None,
-        };
-        match buf[buf_off - 1] {
-            1 => {
-                let value = match decode_bytes_with_len(&buf[buf_off..]) {
-                    Some((value, n)) => {
-                        buf_off += n;
-                        value
-                    }
-                    None => return None,
-                };
-                entry.value = Some(value);
-                Some((entry, buf_off))
-            }
-            2 => Some((entry, buf_off)),
-            _ => None,
+        2 => Op::Delete,
+        3 => {
+            let (end, n) = decode_bytes_with_len(&buf[buf_off..])?;
+            buf_off += n;
+            Op::DeleteRange(end)
         }
-    }
+        4 => {
+            let (operand, n) = decode_bytes_with_len(&buf[buf_off..])?;
+            buf_off += n;
+            Op::Merge(operand)
+        }
+        5 => {
+            let (expire_at, n) = decode_var_u64(&buf[buf_off..])?;
+            buf_off += n;
+            let (value, n) = decode_bytes_with_len(&buf[buf_off..])?;
+            buf_off += n;
+            Op::PutWithExpiry(value, expire_at)
+        }
+        _ => return None,
+    };
+    let entry = Entry { key, seq, op };
+    Some((entry, buf_off))
 }
 
 pub(super) struct Builder<K: Key, V: Value> {
@@ -126,10 +100,10 @@ pub(super) struct Builder<K: Key, V: Value> {
 
 impl<K: Key, V: Value> Builder<K, V> {
     pub(super) fn new(options: DBOptions) -> Builder<K, V> {
-        let filter = match &options.filter_factory {
-            Some(filter_factory) => Some(filter_factory.create()),
-            None => None,
-        };
+        let filter = options
+            .filter_factory
+            .as_ref()
+            .map(|filter_factory| filter_factory.create());
         Builder {
             options,
             entries: vec![],
@@ -145,9 +119,13 @@ impl<K: Key, V: Value> Builder<K, V> {
         self.entries.len() >= self.options.block_size
     }
 
-    pub(super) fn append(&mut self, entry: Entry<K, V>) -> EikvResult<()> {
+    // The key is passed encoded as well, the writer already has it.
+    pub(super) fn append(&mut self, entry: &Entry<K, V>, key: &[u8]) -> EikvResult<()> {
+        if let Some(filter) = &mut self.filter {
+            filter.add(key);
+        }
         if self.min_entry.is_none() {
-            self.min_entry = Some(entry);
+            self.min_entry = Some(entry.clone());
             return Ok(());
         }
 
@@ -157,22 +135,15 @@ impl<K: Key, V: Value> Builder<K, V> {
         }
         self.restart_index = (self.restart_index + 1) % self.options.restart_interval;
 
-        let key = entry.key.encode()?;
-        let entry = SharePrefixEntry::new(
-            &key,
-            entry.seq,
-            match entry.value {
-                Some(value) => Some(value.encode()?),
-                None => None,
-            },
-            &self.prev_key,
-        );
-        if let Some(filter) = &mut self.filter {
-            filter.add(&key);
-        }
-        self.prev_key = key;
+        let shared_len = shared_len(key, &self.prev_key);
+        append_var_u32(&mut self.entries, shared_len as u32);
+        append_var_u32(&mut self.entries, (key.len() - shared_len) as u32);
+        self.entries.extend(&key[shared_len..]);
+        append_var_u64(&mut self.entries, entry.seq);
+        entry.op.encode_to(&mut self.entries)?;
 
-        entry.encode(&mut self.entries);
+        self.prev_key.clear();
+        self.prev_key.extend(key);
         Ok(())
     }
 
@@ -196,7 +167,7 @@ impl<K: Key, V: Value> Builder<K, V> {
         let min_entry_offset = block.len() as u32;
         self.min_entry.unwrap().encode(&mut block)?;
 
-        if let Some(_) = &self.filter {
+        if self.filter.is_some() {
             append_fixed_u32(&mut block, filter_offset);
         }
         append_fixed_u32(&mut block, min_entry_offset);
@@ -238,6 +209,14 @@ fn decode_min_entry<K: Key, V: Value>(
     Ok(min_entry)
 }
 
+// The bytes of the first key in the block, without decoding the rest.
+pub(super) fn decode_min_key<K: Key, V: Value>(block: &[u8]) -> EikvResult<Vec<u8>> {
+    verify_checksum(block)?;
+    let min_entry_offset = decode_min_entry_offset(block)?;
+    let min_entry: Entry<K, V> = decode_min_entry(block, min_entry_offset)?;
+    Ok(min_entry.key.to_bytes()?)
+}
+
 fn decode_filter_offset(block: &[u8], min_entry_offset: usize) -> EikvResult<usize> {
     let block_size = block.len();
     let filter_offset = decode_fixed_u32(&block[block_size - 12..block_size - 8]) as usize;
@@ -272,17 +251,14 @@ fn decode_payload<K: Key, V: Value>(
     let mut prev_key = vec![];
     let mut buf_off = 0;
     while buf_off < entries_buf.len() {
-        match SharePrefixEntry::decode(&payload[buf_off..], prev_key) {
+        match decode_share_prefix_entry(&payload[buf_off..], prev_key) {
             Some((entry, n)) => {
                 buf_off += n;
                 prev_key = entry.key.clone();
-                let key = K::decode(entry.key)?;
+                let key = K::decode(&entry.key)?;
                 let seq = entry.seq;
-                let value = match entry.value {
-                    Some(value) => Some(Value::decode(value)?),
-                    None => None,
-                };
-                entries.push(Entry { key, seq, value });
+                let op = Op::decode(&entry.op)?;
+                entries.push(Entry { key, seq, op });
             }
             None => {
                 let reason = "data block is corrupt".to_owned();
@@ -311,7 +287,7 @@ pub(super) fn decode_block<K: Key, V: Value>(
     let mut entries = vec![min_entry];
     match compressor {
         Some(compressor) => {
-            let payload = compressor.uncompress(block)?;
+            let payload = compressor.uncompress(payload)?;
             decode_payload(&payload, &mut entries)?;
         }
         None => decode_payload(payload, &mut entries)?,
@@ -320,18 +296,20 @@ pub(super) fn decode_block<K: Key, V: Value>(
     Ok(entries)
 }
 
-fn find<K: Key, V: Value>(
+pub(super) fn find<K: Key, V: Value>(
     block: &[u8],
     key: &K,
-    min_entry_offset: usize,
+    key_bytes: &[u8],
+    comparator: &dyn Comparator,
     compressor: Option<Arc<dyn Compressor>>,
     filter_factory: Option<Arc<dyn FilterFactory>>,
 ) -> EikvResult<Option<Entry<K, V>>> {
+    verify_checksum(block)?;
+    let min_entry_offset = decode_min_entry_offset(block)?;
     let payload_end = match filter_factory {
         Some(filter_factory) => {
             let filter = decode_filter(block, min_entry_offset, filter_factory)?;
-            let key_bytes = key.clone().encode()?;
-            if !filter.may_match(&key_bytes) {
+            if !filter.may_match(key_bytes) {
                 return Ok(None);
             }
             decode_filter_offset(block, min_entry_offset)?
@@ -342,10 +320,10 @@ fn find<K: Key, V: Value>(
     let payload = &block[..payload_end];
     let entry = match compressor {
         Some(compressor) => {
-            let payload = compressor.uncompress(block)?;
-            find_in_payload(&payload, key)?
+            let payload = compressor.uncompress(payload)?;
+            find_in_payload(&payload, key_bytes, comparator)?
         }
-        None => find_in_payload(&payload, key)?,
+        None => find_in_payload(payload, key_bytes, comparator)?,
     };
 
     let min_entry = decode_min_entry(block, min_entry_offset)?;
@@ -375,8 +353,7 @@ fn decode_restart_points(payload: &[u8]) -> EikvResult<Vec<u32>> {
     let payload_size = payload.len();
     let restart_point_count = decode_fixed_u32(&payload[payload_size - 4..]) as usize;
     let restart_point_start = payload_size - 4 - restart_point_count * 4;
-    let mut restart_points = vec![];
-    restart_points.reserve(restart_point_count);
+    let mut restart_points = Vec::with_capacity(restart_point_count);
     for off in (restart_point_start..payload_size - 4).step_by(4) {
         let restart_point = decode_fixed_u32(&payload[off..off + 4]);
         restart_points.push(restart_point);
@@ -384,10 +361,13 @@ fn decode_restart_points(payload: &[u8]) -> EikvResult<Vec<u32>> {
     Ok(restart_points)
 }
 
-fn find_in_payload<K: Key, V: Value>(payload: &[u8], key: &K) -> EikvResult<Option<Entry<K, V>>> {
+fn find_in_payload<K: Key, V: Value>(
+    payload: &[u8],
+    key: &[u8],
+    comparator: &dyn Comparator,
+) -> EikvResult<Option<Entry<K, V>>> {
     let mut restart_points = decode_restart_points(payload)?;
-    let mut chunks = vec![];
-    chunks.reserve(restart_points.len());
+    let mut chunks = Vec::with_capacity(restart_points.len());
     let buf_end = payload.len() - (restart_points.len() + 1) * 4;
     restart_points.push(buf_end as u32);
 
@@ -395,25 +375,31 @@ fn find_in_payload<K: Key, V: Value>(payload: &[u8], key: &K) -> EikvResult<Opti
         chunks.push((restart_points[i], restart_points[i + 1]));
     }
 
+    if chunks.is_empty() {
+        return Ok(None);
+    }
+
     let buf = &payload[..buf_end];
-    find_dichotomic(buf, key, &chunks)
+    find_dichotomic(buf, key, comparator, &chunks)
 }
 
+// Keys are compared encoded, only the entry found is decoded.
 fn find_dichotomic<K: Key, V: Value>(
     buf: &[u8],
-    key: &K,
+    key: &[u8],
+    comparator: &dyn Comparator,
     chunks: &[(u32, u32)],
 ) -> EikvResult<Option<Entry<K, V>>> {
     if chunks.len() == 1 {
         let start = chunks[0].0 as usize;
         let end = chunks[0].1 as usize;
-        return find_in_sequence(&buf[start..end], key);
+        return find_in_sequence(&buf[start..end], key, comparator);
     }
 
     let mid = chunks.len() / 2;
     let start = chunks[mid].0 as usize;
     let end = chunks[mid].1 as usize;
-    let entry = match SharePrefixEntry::decode(&buf[start..end], vec![]) {
+    let entry = match decode_share_prefix_entry(&buf[start..end], vec![]) {
         Some((entry, _)) => entry,
         None => {
             let reason = "data block is corrupt".to_owned();
@@ -421,42 +407,34 @@ fn find_dichotomic<K: Key, V: Value>(
         }
     };
 
-    let k = K::decode(entry.key)?;
-    match key.cmp(&k) {
-        Ordering::Less => find_dichotomic(buf, key, &chunks[..mid]),
-        _ => find_dichotomic(buf, key, &chunks[mid..]),
+    match comparator.compare(key, &entry.key) {
+        Ordering::Less => find_dichotomic(buf, key, comparator, &chunks[..mid]),
+        _ => find_dichotomic(buf, key, comparator, &chunks[mid..]),
     }
 }
 
-fn find_in_sequence<K: Key, V: Value>(buf: &[u8], key: &K) -> EikvResult<Option<Entry<K, V>>> {
+fn find_in_sequence<K: Key, V: Value>(
+    buf: &[u8],
+    key: &[u8],
+    comparator: &dyn Comparator,
+) -> EikvResult<Option<Entry<K, V>>> {
     let mut prev_key = vec![];
     let mut buf_off = 0;
     let mut target = None;
 
     while buf_off < buf.len() {
-        match SharePrefixEntry::decode(&buf[buf_off..], prev_key) {
+        match decode_share_prefix_entry(&buf[buf_off..], prev_key) {
             Some((entry, n)) => {
                 buf_off += n;
 
-                let k = K::decode(entry.key)?;
-                match k.cmp(key) {
+                match comparator.compare(&entry.key, key) {
                     Ordering::Less => {
-                        prev_key = k.encode()?;
+                        prev_key = entry.key;
                         continue;
                     }
                     Ordering::Equal => {
-                        let v: Option<V> = match entry.value {
-                            Some(value) => Some(Value::decode(value)?),
-                            None => None,
-                        };
-                        let entry = Entry {
-                            key: k.clone(),
-                            seq: entry.seq,
-                            value: v,
-                        };
-                        target = Some(entry);
-
-                        prev_key = k.encode()?;
+                        target = Some((entry.seq, entry.op));
+                        prev_key = entry.key;
                         continue;
                     }
                     Ordering::Greater => {
@@ -471,5 +449,15 @@ fn find_in_sequence<K: Key, V: Value>(buf: &[u8], key: &K) -> EikvResult<Option<
         }
     }
 
-    Ok(target)
+    match target {
+        Some((seq, op)) => {
+            let entry = Entry {
+                key: K::decode(key)?,
+                seq,
+                op: Op::decode(&op)?,
+            };
+            Ok(Some(entry))
+        }
+        None => Ok(None),
+    }
 }
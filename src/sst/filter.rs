@@ -1,6 +1,6 @@
 use std::error::Error;
 
-pub trait Filter {
+pub trait Filter: Send {
     fn add(&mut self, key: &[u8]);
     fn may_match(&self, key: &[u8]) -> bool;
     fn encode(&self, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>>;
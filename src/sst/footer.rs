@@ -12,11 +12,21 @@ use crate::{
     EikvError, EikvResult, Key, Value,
 };
 
+// Footers of the first version end after the data block count, later ones
+// follow it with the version, the fields of that version and the magic.
+pub(super) const FOOTER_VERSION: u32 = 1;
+const FOOTER_MAGIC: u64 = 0x6569_6b76_666f_6f74;
+
 pub(crate) struct Footer<K: Key, V: Value> {
+    pub(crate) version: u32,
     pub(crate) min_entry: Entry<K, V>,
     pub(crate) max_entry: Entry<K, V>,
     pub(crate) data_block_end: u64,
     pub(crate) data_block_count: u32,
+    pub(crate) range_del_block_end: u64,
+    pub(crate) index_key_block_end: u64,
+    pub(crate) max_seq: u64,
+    pub(crate) min_expire_at: u64,
 }
 
 impl<K: Key, V: Value> Footer<K, V> {
@@ -25,22 +35,62 @@ impl<K: Key, V: Value> Footer<K, V> {
         self.max_entry.encode(buf)?;
         append_fixed_u64(buf, self.data_block_end);
         append_fixed_u32(buf, self.data_block_count);
+        append_fixed_u32(buf, self.version);
+        append_fixed_u64(buf, self.range_del_block_end);
+        append_fixed_u64(buf, self.index_key_block_end);
+        append_fixed_u64(buf, self.max_seq);
+        append_fixed_u64(buf, self.min_expire_at);
+        append_fixed_u64(buf, FOOTER_MAGIC);
         Ok(())
     }
 
     fn decode(buf: &[u8]) -> EikvResult<Footer<K, V>> {
-        let (min_entry, mut buf_off) = Entry::decode(buf)?;
-        let (max_entry, n) = Entry::decode(&buf[buf_off..])?;
+        let corrupt = || EikvError::SstCorrpution("footer is corrupt".to_owned());
+        let (min_entry, mut buf_off) = Entry::decode(buf).map_err(|_| corrupt())?;
+        let (max_entry, n) = Entry::decode(&buf[buf_off..]).map_err(|_| corrupt())?;
         buf_off += n;
+        if buf.len() < buf_off + 12 {
+            return Err(corrupt());
+        }
         let data_block_end = decode_fixed_u64(&buf[buf_off..buf_off + 8]);
         buf_off += 8;
         let data_block_count = decode_fixed_u32(&buf[buf_off..buf_off + 4]);
-        let footer = Footer {
+        buf_off += 4;
+
+        // A file of the first version has no range tombstones, no index key
+        // block and no values with a TTL. Its max seq isn't known, so it is
+        // taken to be newer than everything.
+        let mut footer = Footer {
+            version: 0,
             min_entry,
             max_entry,
             data_block_end,
-            data_block_count: data_block_count,
+            data_block_count,
+            range_del_block_end: 0,
+            index_key_block_end: 0,
+            max_seq: u64::MAX,
+            min_expire_at: u64::MAX,
         };
+        if buf_off == buf.len() {
+            return Ok(footer);
+        }
+
+        if buf.len() != buf_off + 44 || decode_fixed_u64(&buf[buf.len() - 8..]) != FOOTER_MAGIC {
+            return Err(corrupt());
+        }
+        footer.version = decode_fixed_u32(&buf[buf_off..buf_off + 4]);
+        if footer.version != FOOTER_VERSION {
+            let reason = format!("footer version {} isn't supported", footer.version);
+            return Err(EikvError::SstCorrpution(reason));
+        }
+        buf_off += 4;
+        footer.range_del_block_end = decode_fixed_u64(&buf[buf_off..buf_off + 8]);
+        buf_off += 8;
+        footer.index_key_block_end = decode_fixed_u64(&buf[buf_off..buf_off + 8]);
+        buf_off += 8;
+        footer.max_seq = decode_fixed_u64(&buf[buf_off..buf_off + 8]);
+        buf_off += 8;
+        footer.min_expire_at = decode_fixed_u64(&buf[buf_off..buf_off + 8]);
         Ok(footer)
     }
 
@@ -56,6 +106,10 @@ impl<K: Key, V: Value> Footer<K, V> {
         }
         let footer_size = decode_fixed_u32(&buf[..4]) as usize;
         let checksum = decode_fixed_u32(&buf[4..]);
+        if footer_size as u64 + 8 > file.metadata()?.len() {
+            let reason = format!("footer size is {}, larger than the file", footer_size);
+            return Err(EikvError::SstCorrpution(reason));
+        }
 
         file.seek(SeekFrom::End(-(footer_size as i64 + 8)))?;
         let mut buf = vec![0; footer_size + 4];
@@ -74,3 +128,77 @@ impl<K: Key, V: Value> Footer<K, V> {
         Ok(footer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Footer;
+    use crate::{
+        model::{Entry, Op},
+        util::coding::{append_fixed_u32, append_fixed_u64},
+    };
+
+    fn entry(key: &str, seq: u64) -> Entry<String, String> {
+        Entry {
+            key: key.to_owned(),
+            seq,
+            op: Op::Put("v".to_owned()),
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let footer: Footer<String, String> = Footer {
+            version: 1,
+            min_entry: entry("a", 1),
+            max_entry: entry("z", 2),
+            data_block_end: 100,
+            data_block_count: 3,
+            range_del_block_end: 120,
+            index_key_block_end: 140,
+            max_seq: 9,
+            min_expire_at: 50,
+        };
+        let mut buf = vec![];
+        footer.encode(&mut buf).unwrap();
+        let footer: Footer<String, String> = Footer::decode(&buf).unwrap();
+        assert_eq!(
+            (1, 100, 3),
+            (
+                footer.version,
+                footer.data_block_end,
+                footer.data_block_count
+            )
+        );
+        assert_eq!(
+            (120, 140),
+            (footer.range_del_block_end, footer.index_key_block_end)
+        );
+        assert_eq!((9, 50), (footer.max_seq, footer.min_expire_at));
+
+        // Cut at the end of the first version fields it decodes as a footer
+        // of that version.
+        let first_version_len = buf.len() - 44;
+        for len in (0..buf.len()).filter(|len| *len != first_version_len) {
+            assert!(Footer::<String, String>::decode(&buf[..len]).is_err());
+        }
+    }
+
+    #[test]
+    fn test_decode_first_version() {
+        let mut buf = vec![];
+        entry("a", 1).encode(&mut buf).unwrap();
+        entry("z", 2).encode(&mut buf).unwrap();
+        append_fixed_u64(&mut buf, 100);
+        append_fixed_u32(&mut buf, 3);
+        let footer: Footer<String, String> = Footer::decode(&buf).unwrap();
+        assert_eq!(
+            (0, 100, 3),
+            (
+                footer.version,
+                footer.data_block_end,
+                footer.data_block_count
+            )
+        );
+        assert_eq!((u64::MAX, u64::MAX), (footer.max_seq, footer.min_expire_at));
+    }
+}
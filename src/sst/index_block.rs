@@ -85,7 +85,7 @@ impl Iterator {
         let offset_count = self.block_size / 8 - 1;
         let offset_count =
             if self.index_block_offset + self.block_size as u64 == self.index_block_end {
-                self.data_block_count as usize % offset_count
+                (self.data_block_count as usize - 1) % offset_count + 1
             } else {
                 offset_count
             };
@@ -8,13 +8,16 @@ use std::{
     sync::Arc,
 };
 
-use super::{data_block::decode_block, index_block};
+use super::{data_block::decode_block, index_block, IOPriority};
 
 pub(crate) struct Iterator<K: Key, V: Value> {
     entries: Vec<Entry<K, V>>,
     entry_index: usize,
     file: File,
     index_block_iterator: index_block::Iterator,
+    index_keys: Arc<Vec<Vec<u8>>>,
+    // Compaction reads go through the rate limiter, foreground reads don't.
+    io_priority: Option<IOPriority>,
     options: DBOptions,
 }
 
@@ -23,14 +26,18 @@ impl<K: Key, V: Value> Iterator<K, V> {
         path: &str,
         options: DBOptions,
         sst_meta: SstMeta<K, V>,
+        io_priority: Option<IOPriority>,
     ) -> EikvResult<Iterator<K, V>> {
         let file = OpenOptions::new().read(true).open(path)?;
+        let index_keys = sst_meta.index_keys(path)?;
         let index_block_iterator = index_block::Iterator::new(sst_meta);
         let iterator = Iterator {
             entry_index: 0,
             entries: vec![],
             file,
             index_block_iterator,
+            index_keys,
+            io_priority,
             options,
         };
         Ok(iterator)
@@ -47,6 +54,11 @@ impl<K: Key, V: Value> Iterator<K, V> {
 
         let start = data_block_pos.0;
         let block_size = (data_block_pos.1 - data_block_pos.0) as usize;
+        if let (Some(rate_limiter), Some(io_priority)) =
+            (&self.options.rate_limiter, self.io_priority)
+        {
+            rate_limiter.request(block_size, io_priority);
+        }
         let mut block = vec![0; block_size];
         self.file.seek(SeekFrom::Start(start))?;
         let n = self.file.read(&mut block)?;
@@ -67,6 +79,26 @@ impl<K: Key, V: Value> Iterator<K, V> {
         Ok(())
     }
 
+    // Positions the iterator at the first entry not less than the key.
+    pub(crate) fn seek(&mut self, key: &K) -> EikvResult<()> {
+        let key_bytes = key.to_bytes()?;
+        let comparator = self.options.comparator.clone();
+        let (_, index_keys) = self.index_keys.split_last().unwrap();
+        // Versions of the key may end the block before the one indexed by it.
+        let skipped_blocks = index_keys
+            .partition_point(|index_key| comparator.compare(index_key, &key_bytes).is_lt())
+            .saturating_sub(1);
+        self.index_block_iterator.seek_to_first(&mut self.file)?;
+        for _ in 0..skipped_blocks {
+            self.index_block_iterator.next(&mut self.file)?;
+        }
+        self.next_block()?;
+        while self.entry().is_some_and(|entry| entry.key < *key) {
+            self.next()?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn entry(&self) -> Option<&Entry<K, V>> {
         if self.entries.len() == self.entry_index {
             None
@@ -1,10 +1,38 @@
-use super::{Iterator, Writer};
-use crate::{model::Entry, util::time::unix_now, DBOptions, EikvResult, Key, Value};
-use std::{cmp::min, mem::swap};
+use super::{IOPriority, Iterator, Writer};
+use crate::{
+    limit::LEVEL_MAX,
+    model::{collect_versions, full_merge, max_covering_seq, Entry, Op, RangeTombstone},
+    util::time::unix_now,
+    CompactionDecision, CompactionFilterContext, DBOptions, EikvResult, Key, Value,
+};
+use std::{
+    cmp::{min, Reverse},
+    mem::swap,
+};
+
+// The keys from start on and before end, a missing bound leaves the range
+// open on its side.
+pub(crate) struct KeyRange<K: Key> {
+    pub(crate) start: Option<K>,
+    pub(crate) end: Option<K>,
+}
+
+impl<K: Key> KeyRange<K> {
+    fn contains(&self, key: &K) -> bool {
+        self.start.as_ref().is_none_or(|start| start <= key)
+            && self.end.as_ref().is_none_or(|end| key < end)
+    }
+}
 
 pub(crate) struct Merger<K: Key, V: Value> {
+    filter_context: CompactionFilterContext,
+    // Live reads may see the versions up to it, the compaction filter leaves
+    // them alone.
+    filter_seq: u64,
     iterators: Vec<Iterator<K, V>>,
+    key_range: KeyRange<K>,
     options: DBOptions,
+    range_tombstones: Vec<RangeTombstone<K>>,
     seq_guard: u64,
     time_limit: usize,
     writer: Writer<K, V>,
@@ -17,18 +45,42 @@ pub(crate) enum MergeResult {
 }
 
 impl<K: Key, V: Value> Merger<K, V> {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         path: &str,
         iterators: Vec<Iterator<K, V>>,
+        range_tombstones: Vec<RangeTombstone<K>>,
+        written_range_tombstones: Vec<RangeTombstone<K>>,
         options: DBOptions,
         seq_guard: u64,
+        filter_seq: u64,
         size_limit: u64,
         time_limit: usize,
+        filter_context: CompactionFilterContext,
+        key_range: KeyRange<K>,
     ) -> EikvResult<Merger<K, V>> {
-        let writer = Writer::new(path, options.clone(), size_limit)?;
+        let mut iterators = iterators;
+        for iterator in iterators.iter_mut() {
+            match &key_range.start {
+                Some(start) => iterator.seek(start)?,
+                None => iterator.seek_to_first()?,
+            }
+        }
+        // All the range tombstones hide versions, each written one is written
+        // by the merger of the range holding its start.
+        let mut writer = Writer::new(path, options.clone(), size_limit, IOPriority::Low)?;
+        for range_tombstone in written_range_tombstones {
+            if key_range.contains(&range_tombstone.start) {
+                writer.add_range_tombstone(range_tombstone);
+            }
+        }
         let merger = Merger {
+            filter_context,
+            filter_seq,
             iterators,
+            key_range,
             options,
+            range_tombstones,
             seq_guard,
             time_limit,
             writer,
@@ -36,9 +88,19 @@ impl<K: Key, V: Value> Merger<K, V> {
         Ok(merger)
     }
 
+    // Entries after the key range are left to other mergers.
+    fn entry<'a>(&self, iterator: &'a Iterator<K, V>) -> Option<&'a Entry<K, V>> {
+        iterator.entry().filter(|entry| {
+            self.key_range
+                .end
+                .as_ref()
+                .is_none_or(|end| entry.key < *end)
+        })
+    }
+
     fn finished(&self) -> bool {
         for iterator in &self.iterators {
-            if iterator.entry().is_some() {
+            if self.entry(iterator).is_some() {
                 return false;
             }
         }
@@ -50,7 +112,7 @@ impl<K: Key, V: Value> Merger<K, V> {
 
         let mut min_entry = None;
         for iterator in &self.iterators {
-            let entry = match iterator.entry() {
+            let entry = match self.entry(iterator) {
                 Some(entry) => entry,
                 None => continue,
             };
@@ -64,35 +126,169 @@ impl<K: Key, V: Value> Merger<K, V> {
     }
 
     fn read_some(&mut self) -> EikvResult<Vec<Entry<K, V>>> {
-        let min_entry = self.get_min_entry();
-        let mut last_before_guard = None;
-        let mut entries = vec![];
+        let key = self.get_min_entry().key;
+        let mut versions = vec![];
+        let mut newer = vec![];
         for iterator in self.iterators.iter_mut() {
-            loop {
-                let entry = match iterator.entry() {
-                    Some(entry) => entry,
-                    None => break,
-                };
-                if entry.key != min_entry.key {
+            while let Some(entry) = iterator.entry() {
+                if entry.key != key {
                     break;
                 }
-                if entry.seq <= self.seq_guard {
-                    last_before_guard = Some(entry.clone());
+                if entry.seq > self.seq_guard {
+                    newer.push(entry.clone());
                 } else {
-                    entries.push(entry.clone());
+                    versions.push(entry.clone());
                 }
                 iterator.next()?;
             }
         }
 
-        if let Some(last_before_guard) = last_before_guard {
-            entries.push(last_before_guard);
+        versions.sort_unstable_by_key(|entry| Reverse(entry.seq));
+        let tombstone_seq = max_covering_seq(&self.range_tombstones, &key, self.seq_guard);
+        let mut chain = vec![];
+        let complete = collect_versions(&key, &versions, tombstone_seq, &mut chain);
+        // The deletion standing for a range tombstone isn't written, the
+        // tombstone itself is kept in the output.
+        if chain
+            .last()
+            .is_some_and(|entry| matches!(entry.op, Op::Delete) && Some(entry.seq) == tombstone_seq)
+        {
+            chain.pop();
         }
+        // An expired value still hides the older versions, only its value is
+        // dropped. Nothing older is left below the last level, so an expired
+        // value hiding everything there is dropped as a whole.
+        let now = unix_now() as u64;
+        if self.filter_context.level == LEVEL_MAX
+            && complete
+            && chain.len() == 1
+            && chain[0].is_expired(now)
+        {
+            chain.clear();
+        }
+        for entry in chain.iter_mut().chain(newer.iter_mut()) {
+            if entry.is_expired(now) {
+                entry.op = Op::Delete;
+            }
+        }
+        let chain = self.merge_chain(&key, chain, complete)?;
+        // Versions above the seq guard aren't collapsed, the filter still
+        // applies to them.
+        let mut entries = self.filter_chain(&key, newer)?;
+        entries.extend(self.filter_chain(&key, chain)?);
 
         entries.sort_unstable();
         Ok(entries)
     }
 
+    fn merge_chain(
+        &self,
+        key: &K,
+        chain: Vec<Entry<K, V>>,
+        complete: bool,
+    ) -> EikvResult<Vec<Entry<K, V>>> {
+        let merge_operator = match &self.options.merge_operator {
+            Some(merge_operator) if chain.first().is_some_and(|e| matches!(e.op, Op::Merge(_))) => {
+                merge_operator
+            }
+            _ => return Ok(chain),
+        };
+        let seq = chain[0].seq;
+
+        if complete {
+            let mut operands = vec![];
+            let mut existing_value = None;
+            let mut expire_at = None;
+            for entry in chain {
+                match entry.op {
+                    Op::Merge(operand) => operands.push(operand),
+                    Op::Put(value) => {
+                        existing_value = Some(value);
+                        break;
+                    }
+                    Op::PutWithExpiry(value, at) => {
+                        existing_value = Some(value);
+                        expire_at = Some(at);
+                        break;
+                    }
+                    _ => break,
+                }
+            }
+            operands.reverse();
+            let value = full_merge(Some(merge_operator), key, existing_value, operands)?;
+            let op = match expire_at {
+                Some(expire_at) => Op::PutWithExpiry(value, expire_at),
+                None => Op::Put(value),
+            };
+            let entry = Entry {
+                key: key.clone(),
+                seq,
+                op,
+            };
+            return Ok(vec![entry]);
+        }
+
+        // Older versions may live in files outside of this merge, so operands
+        // can only be combined with each other.
+        let key_bytes = key.to_bytes()?;
+        let mut operands: Vec<(u64, Vec<u8>)> = vec![];
+        for entry in chain.into_iter().rev() {
+            let operand = match entry.op {
+                Op::Merge(operand) => operand.to_bytes()?,
+                _ => continue,
+            };
+            if let Some((last_seq, last_operand)) = operands.last_mut() {
+                if let Some(merged) =
+                    merge_operator.partial_merge(&key_bytes, last_operand, &operand)?
+                {
+                    *last_seq = entry.seq;
+                    *last_operand = merged;
+                    continue;
+                }
+            }
+            operands.push((entry.seq, operand));
+        }
+
+        let mut entries = Vec::with_capacity(operands.len());
+        for (seq, operand) in operands {
+            entries.push(Entry {
+                key: key.clone(),
+                seq,
+                op: Op::Merge(V::decode(&operand)?),
+            });
+        }
+        Ok(entries)
+    }
+
+    fn filter_chain(&self, key: &K, mut chain: Vec<Entry<K, V>>) -> EikvResult<Vec<Entry<K, V>>> {
+        let compaction_filter = match &self.options.compaction_filter {
+            Some(compaction_filter) => compaction_filter,
+            None => return Ok(chain),
+        };
+
+        let key_bytes = key.to_bytes()?;
+        for entry in chain.iter_mut().filter(|entry| entry.seq > self.filter_seq) {
+            let value = match &entry.op {
+                Op::Put(value) | Op::PutWithExpiry(value, _) => value.to_bytes()?,
+                _ => continue,
+            };
+            match compaction_filter.filter(&self.filter_context, &key_bytes, &value) {
+                CompactionDecision::Keep => {}
+                // A deletion still hides the versions in files outside of
+                // this merge.
+                CompactionDecision::Remove => entry.op = Op::Delete,
+                CompactionDecision::ChangeValue(value) => {
+                    let value = V::decode(&value)?;
+                    entry.op = match entry.op {
+                        Op::PutWithExpiry(_, expire_at) => Op::PutWithExpiry(value, expire_at),
+                        _ => Op::Put(value),
+                    };
+                }
+            }
+        }
+        Ok(chain)
+    }
+
     pub(crate) fn merge(&mut self) -> EikvResult<MergeResult> {
         let start_at = unix_now();
         loop {
@@ -105,7 +301,6 @@ impl<K: Key, V: Value> Merger<K, V> {
             }
 
             let entries = self.read_some()?;
-            debug_assert!(!entries.is_empty());
             for entry in entries {
                 self.writer.append(entry)?;
             }
@@ -122,7 +317,11 @@ impl<K: Key, V: Value> Merger<K, V> {
         writer.finish()
     }
 
-    pub(crate) fn finish(self) -> EikvResult<()> {
-        self.writer.finish()
+    pub(crate) fn finish(self) -> EikvResult<bool> {
+        if self.writer.is_empty() {
+            return Ok(false);
+        }
+        self.writer.finish()?;
+        Ok(true)
     }
 }
@@ -5,11 +5,16 @@ mod footer;
 mod index_block;
 mod iterator;
 mod merger;
+mod rate_limiter;
+mod reader;
 mod writer;
 
 pub use compressor::Compressor;
 pub use filter::{Filter, FilterFactory};
 pub(crate) use footer::Footer;
 pub(crate) use iterator::Iterator;
-pub(crate) use merger::{MergeResult, Merger};
+pub(crate) use merger::{KeyRange, MergeResult, Merger};
+pub(crate) use rate_limiter::IOPriority;
+pub use rate_limiter::RateLimiter;
+pub(crate) use reader::{read_index_keys, Reader};
 pub(crate) use writer::Writer;
@@ -0,0 +1,168 @@
+use std::{
+    cmp::{max, min},
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+const REFILL_PERIOD: Duration = Duration::from_millis(100);
+// An auto-tuned rate is adjusted once per this many refill periods.
+const TUNE_PERIODS: u32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum IOPriority {
+    // Flushes, they free the memtables writes may be stalled on.
+    High,
+    // Compactions.
+    Low,
+}
+
+struct Bucket {
+    bytes_per_sec: u64,
+    available: u64,
+    next_refill: Instant,
+    high_waiters: usize,
+    periods: u32,
+    drained_periods: u32,
+}
+
+// A token bucket capping the bytes per second flushes and compactions of all
+// the DBs sharing it write, and compactions read. Waiting flushes are served
+// before compactions.
+pub struct RateLimiter {
+    auto_tuned: bool,
+    max_bytes_per_sec: u64,
+    bucket: Mutex<Bucket>,
+    refilled: Condvar,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> RateLimiter {
+        RateLimiter::with_tuning(bytes_per_sec, false)
+    }
+
+    // The rate starts at the max and moves between a twentieth of it and the
+    // max, up while the bucket keeps running dry and down while it doesn't.
+    pub fn new_auto_tuned(max_bytes_per_sec: u64) -> RateLimiter {
+        RateLimiter::with_tuning(max_bytes_per_sec, true)
+    }
+
+    fn with_tuning(bytes_per_sec: u64, auto_tuned: bool) -> RateLimiter {
+        let bytes_per_sec = max(bytes_per_sec, 1);
+        RateLimiter {
+            auto_tuned,
+            max_bytes_per_sec: bytes_per_sec,
+            bucket: Mutex::new(Bucket {
+                bytes_per_sec,
+                available: refill_bytes(bytes_per_sec),
+                next_refill: Instant::now() + REFILL_PERIOD,
+                high_waiters: 0,
+                periods: 0,
+                drained_periods: 0,
+            }),
+            refilled: Condvar::new(),
+        }
+    }
+
+    pub fn bytes_per_sec(&self) -> u64 {
+        self.bucket.lock().unwrap().bytes_per_sec
+    }
+
+    pub fn is_auto_tuned(&self) -> bool {
+        self.auto_tuned
+    }
+
+    // Blocks until the bytes are granted, a request larger than the bucket
+    // is granted over several refills.
+    pub(crate) fn request(&self, bytes: usize, priority: IOPriority) {
+        let mut bytes = bytes as u64;
+        let mut bucket = self.bucket.lock().unwrap();
+        while bytes > 0 {
+            let now = Instant::now();
+            if now >= bucket.next_refill {
+                self.refill(&mut bucket, now);
+            }
+            let has_turn = priority == IOPriority::High || bucket.high_waiters == 0;
+            if has_turn && bucket.available > 0 {
+                let granted = min(bucket.available, bytes);
+                bucket.available -= granted;
+                bytes -= granted;
+                continue;
+            }
+
+            let timeout = bucket.next_refill.saturating_duration_since(now);
+            if priority == IOPriority::High {
+                bucket.high_waiters += 1;
+            }
+            bucket = self.refilled.wait_timeout(bucket, timeout).unwrap().0;
+            if priority == IOPriority::High {
+                bucket.high_waiters -= 1;
+            }
+        }
+    }
+
+    // Bytes left unused in a period don't carry over to the next one.
+    fn refill(&self, bucket: &mut Bucket, now: Instant) {
+        bucket.periods += 1;
+        if bucket.available == 0 {
+            bucket.drained_periods += 1;
+        }
+        if bucket.periods == TUNE_PERIODS {
+            if self.auto_tuned {
+                bucket.bytes_per_sec = tuned_rate(
+                    bucket.bytes_per_sec,
+                    self.max_bytes_per_sec,
+                    bucket.drained_periods,
+                    bucket.periods,
+                );
+            }
+            bucket.periods = 0;
+            bucket.drained_periods = 0;
+        }
+        bucket.available = refill_bytes(bucket.bytes_per_sec);
+        bucket.next_refill = now + REFILL_PERIOD;
+        self.refilled.notify_all();
+    }
+}
+
+fn refill_bytes(bytes_per_sec: u64) -> u64 {
+    let periods_per_sec = (Duration::from_secs(1).as_millis() / REFILL_PERIOD.as_millis()) as u64;
+    max(bytes_per_sec / periods_per_sec, 1)
+}
+
+// Raises the rate by 5% when the bucket ran dry in more than 90% of the
+// periods and lowers it by 5% when in less than 50%.
+fn tuned_rate(bytes_per_sec: u64, max_bytes_per_sec: u64, drained: u32, periods: u32) -> u64 {
+    let min_bytes_per_sec = max(max_bytes_per_sec / 20, 1);
+    let drained_percent = drained * 100 / periods;
+    let bytes_per_sec = if drained_percent > 90 {
+        bytes_per_sec + max(bytes_per_sec / 20, 1)
+    } else if drained_percent < 50 {
+        bytes_per_sec - bytes_per_sec / 20
+    } else {
+        bytes_per_sec
+    };
+    bytes_per_sec.clamp(min_bytes_per_sec, max_bytes_per_sec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tuned_rate() {
+        assert_eq!(tuned_rate(1000, 2000, 10, 10), 1050);
+        assert_eq!(tuned_rate(1000, 2000, 7, 10), 1000);
+        assert_eq!(tuned_rate(1000, 2000, 0, 10), 950);
+        assert_eq!(tuned_rate(1990, 2000, 10, 10), 2000);
+        assert_eq!(tuned_rate(100, 2000, 0, 10), 100);
+    }
+
+    #[test]
+    fn test_request() {
+        let rate_limiter = RateLimiter::new(10_000);
+        let start = Instant::now();
+        // The first 1000 bytes are in the bucket, the rest take two refills.
+        rate_limiter.request(3000, IOPriority::Low);
+        assert!(start.elapsed() >= REFILL_PERIOD * 2);
+    }
+}
@@ -0,0 +1,208 @@
+use super::{
+    data_block::{decode_block, decode_min_key, find},
+    index_block,
+};
+use crate::{
+    model::{Entry, SstMeta},
+    DBOptions, EikvError, EikvResult, Key, Value,
+};
+use std::{
+    collections::{hash_map, HashMap},
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom},
+    marker::PhantomData,
+    sync::Arc,
+};
+
+// Files of the first version have no index key block, their index keys are
+// the first key of each data block followed by the max key.
+pub(crate) fn read_index_keys<K: Key, V: Value>(
+    path: &str,
+    sst_meta: SstMeta<K, V>,
+) -> EikvResult<Vec<Vec<u8>>> {
+    let mut file = OpenOptions::new().read(true).open(path)?;
+    let upper_bound = sst_meta.max_entry.key.to_bytes()?;
+    let mut index_block_iterator = index_block::Iterator::new(sst_meta);
+    index_block_iterator.seek_to_first(&mut file)?;
+    let mut index_keys = vec![];
+    while let Some((start, end)) = index_block_iterator.next(&mut file)? {
+        let block_size = (end - start) as usize;
+        let mut block = vec![0; block_size];
+        file.seek(SeekFrom::Start(start))?;
+        let n = file.read(&mut block)?;
+        if n != block_size || block_size < 8 {
+            let reason = format!("data block size is {}, read {} bytes", block_size, n);
+            return Err(EikvError::SstCorrpution(reason));
+        }
+        index_keys.push(decode_min_key::<K, V>(&block)?);
+    }
+    index_keys.push(upper_bound);
+    Ok(index_keys)
+}
+
+pub(crate) struct Reader<K: Key, V: Value> {
+    file: File,
+    options: DBOptions,
+    data_blocks: Vec<(u64, u64)>,
+    index_keys: Arc<Vec<Vec<u8>>>,
+    _marker: PhantomData<(K, V)>,
+}
+
+impl<K: Key, V: Value> Reader<K, V> {
+    pub(crate) fn open(
+        path: &str,
+        options: DBOptions,
+        sst_meta: SstMeta<K, V>,
+    ) -> EikvResult<Reader<K, V>> {
+        let mut file = OpenOptions::new().read(true).open(path)?;
+        let index_keys = sst_meta.index_keys(path)?;
+        let mut index_block_iterator = index_block::Iterator::new(sst_meta);
+        index_block_iterator.seek_to_first(&mut file)?;
+        let mut data_blocks = vec![];
+        while let Some(data_block_pos) = index_block_iterator.next(&mut file)? {
+            data_blocks.push(data_block_pos);
+        }
+
+        let reader = Reader {
+            file,
+            options,
+            data_blocks,
+            index_keys,
+            _marker: PhantomData,
+        };
+        Ok(reader)
+    }
+
+    pub(crate) fn read_block(&mut self, block_index: usize) -> EikvResult<Vec<u8>> {
+        let (start, end) = self.data_blocks[block_index];
+        let block_size = (end - start) as usize;
+        let mut block = vec![0; block_size];
+        self.file.seek(SeekFrom::Start(start))?;
+        let n = self.file.read(&mut block)?;
+        if n != block_size {
+            let reason = format!("data block size is {}, read {} bytes", block_size, n);
+            return Err(EikvError::SstCorrpution(reason));
+        }
+        Ok(block)
+    }
+
+    // Returns the last block whose index key isn't greater than the key.
+    fn find_block(&self, key: &[u8]) -> EikvResult<Option<usize>> {
+        let comparator = &self.options.comparator;
+        let (upper_bound, index_keys) = self.index_keys.split_last().unwrap();
+        if comparator.compare(key, upper_bound).is_gt() {
+            return Ok(None);
+        }
+
+        let block_count =
+            index_keys.partition_point(|index_key| comparator.compare(index_key, key).is_le());
+        if block_count == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(block_count - 1))
+        }
+    }
+
+    fn find_in_block(
+        &self,
+        block: &[u8],
+        key: &K,
+        key_bytes: &[u8],
+    ) -> EikvResult<Option<Entry<K, V>>> {
+        find(
+            block,
+            key,
+            key_bytes,
+            self.options.comparator.as_ref(),
+            self.options.compressor.clone(),
+            self.options.filter_factory.clone(),
+        )
+    }
+
+    pub(crate) fn get(&mut self, key: &K) -> EikvResult<Option<Entry<K, V>>> {
+        let key_bytes = key.to_bytes()?;
+        let block_index = match self.find_block(&key_bytes)? {
+            Some(block_index) => block_index,
+            None => return Ok(None),
+        };
+        let block = self.read_block(block_index)?;
+        self.find_in_block(&block, key, &key_bytes)
+    }
+
+    // Returns every version of the key in the file, newest first. Versions of
+    // one key may span several blocks, so blocks are scanned backward from the
+    // last candidate until one starts with a smaller key.
+    pub(crate) fn get_versions(&mut self, key: &K) -> EikvResult<Vec<Entry<K, V>>> {
+        let mut block_index = match self.find_block(&key.to_bytes()?)? {
+            Some(block_index) => block_index,
+            None => return Ok(vec![]),
+        };
+
+        let has_filter = self.options.filter_factory.is_some();
+        let mut versions = vec![];
+        loop {
+            let block = self.read_block(block_index)?;
+            let entries = decode_block(&block, self.options.compressor.clone(), has_filter)?;
+            let starts_before_key = entries.first().is_some_and(|entry| entry.key < *key);
+            versions.extend(entries.into_iter().rev().filter(|entry| entry.key == *key));
+            if starts_before_key || block_index == 0 {
+                break;
+            }
+            block_index -= 1;
+        }
+        Ok(versions)
+    }
+
+    // Returns the versions not newer than the seq guard of each of the sorted
+    // keys, newest first. A block shared by several keys is read and decoded
+    // once.
+    pub(crate) fn multi_get_versions(
+        &mut self,
+        keys: &[&K],
+        seq_guard: u64,
+    ) -> Vec<EikvResult<Vec<Entry<K, V>>>> {
+        let mut blocks = HashMap::new();
+        keys.iter()
+            .map(|key| self.cached_versions(key, seq_guard, &mut blocks))
+            .collect()
+    }
+
+    fn cached_versions(
+        &mut self,
+        key: &K,
+        seq_guard: u64,
+        blocks: &mut HashMap<usize, Vec<Entry<K, V>>>,
+    ) -> EikvResult<Vec<Entry<K, V>>> {
+        let mut block_index = match self.find_block(&key.to_bytes()?)? {
+            Some(block_index) => block_index,
+            None => return Ok(vec![]),
+        };
+
+        let has_filter = self.options.filter_factory.is_some();
+        let mut versions = vec![];
+        loop {
+            if let hash_map::Entry::Vacant(entry) = blocks.entry(block_index) {
+                let block = self.read_block(block_index)?;
+                entry.insert(decode_block(
+                    &block,
+                    self.options.compressor.clone(),
+                    has_filter,
+                )?);
+            }
+            let entries = &blocks[&block_index];
+            let starts_before_key = entries.first().is_some_and(|entry| entry.key < *key);
+            versions.extend(
+                entries
+                    .iter()
+                    .rev()
+                    .filter(|entry| entry.key == *key && entry.seq <= seq_guard)
+                    .cloned(),
+            );
+            if starts_before_key || block_index == 0 {
+                break;
+            }
+            block_index -= 1;
+        }
+        Ok(versions)
+    }
+}
@@ -1,13 +1,14 @@
-use super::{data_block::Builder, Footer};
+use super::{data_block::Builder, footer::FOOTER_VERSION, Footer, IOPriority};
 use crate::{
-    model::Entry,
+    model::{Entry, Op, RangeTombstone},
     util::{
         checksum::crc32_checksum,
-        coding::{append_fixed_u32, append_fixed_u64},
+        coding::{append_fixed_u32, append_fixed_u64, append_var_u32},
     },
-    DBOptions, EikvResult, Key, Value,
+    DBOptions, EikvError, EikvResult, Key, Value,
 };
 use std::{
+    cmp::{max, min},
     fs::{File, OpenOptions},
     io::{Seek, Write},
     mem::swap,
@@ -16,30 +17,83 @@ use std::{
 pub(crate) struct Writer<K: Key, V: Value> {
     options: DBOptions,
     file: File,
+    io_priority: IOPriority,
     block_builder: Builder<K, V>,
     block_offsets: Vec<u64>,
+    index_keys: Vec<Vec<u8>>,
+    last_key: Vec<u8>,
     size_limit: u64,
     min_entry: Option<Entry<K, V>>,
     max_entry: Option<Entry<K, V>>,
+    max_seq: u64,
+    min_expire_at: u64,
+    range_tombstones: Vec<RangeTombstone<K>>,
 }
 
 impl<K: Key, V: Value> Writer<K, V> {
-    pub(crate) fn new(path: &str, options: DBOptions, size_limit: u64) -> EikvResult<Writer<K, V>> {
-        let file = OpenOptions::new().create(true).append(true).open(path)?;
+    pub(crate) fn new(
+        path: &str,
+        options: DBOptions,
+        size_limit: u64,
+        io_priority: IOPriority,
+    ) -> EikvResult<Writer<K, V>> {
+        // A file left by a crash is overwritten.
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(path)?;
         let writer = Writer {
             options: options.clone(),
             file,
+            io_priority,
             block_builder: Builder::new(options),
             block_offsets: vec![],
+            index_keys: vec![],
+            last_key: vec![],
             size_limit,
             min_entry: None,
             max_entry: None,
+            max_seq: 0,
+            min_expire_at: u64::MAX,
+            range_tombstones: vec![],
         };
         Ok(writer)
     }
 
+    fn write(&mut self, buf: &[u8]) -> EikvResult<()> {
+        if let Some(rate_limiter) = &self.options.rate_limiter {
+            rate_limiter.request(buf.len(), self.io_priority);
+        }
+        self.file.write_all(buf)?;
+        Ok(())
+    }
+
     pub(crate) fn append(&mut self, entry: Entry<K, V>) -> EikvResult<()> {
+        let key = entry.key.to_bytes()?;
+        // Entries come sorted by the Ord of their keys while the file is
+        // searched by the comparator, a file the two disagree on isn't written.
+        if let Some(max_entry) = &self.max_entry {
+            let comparator = &self.options.comparator;
+            if comparator.compare(&self.last_key, &key) != max_entry.key.cmp(&entry.key) {
+                let reason = format!("{} disagrees with the Ord of the keys", comparator.name());
+                return Err(EikvError::ComparatorError(reason));
+            }
+        }
+        if self.index_keys.is_empty() {
+            self.index_keys.push(key.clone());
+        }
         if self.block_builder.full() {
+            // The index key of a block sits after the last key of the
+            // previous block and not after its own first key.
+            let comparator = &self.options.comparator;
+            let separator = comparator.find_shortest_separator(&self.last_key, &key);
+            if comparator.compare(&separator, &self.last_key).is_gt() {
+                self.index_keys.push(separator);
+            } else {
+                self.index_keys.push(key.clone());
+            }
+
             let offset = self.file.stream_position()?;
             self.block_offsets.push(offset);
 
@@ -47,15 +101,29 @@ impl<K: Key, V: Value> Writer<K, V> {
             swap(&mut self.block_builder, &mut block_builder);
 
             let buf = block_builder.build()?;
-            self.file.write(&buf)?;
+            self.write(&buf)?;
         }
+        self.block_builder.append(&entry, &key)?;
+        self.last_key = key;
 
         if self.min_entry.is_none() {
             self.min_entry = Some(entry.clone());
         }
-        self.max_entry = Some(entry.clone());
+        self.max_seq = max(self.max_seq, entry.seq);
+        if let Op::PutWithExpiry(_, expire_at) = entry.op {
+            self.min_expire_at = min(self.min_expire_at, expire_at);
+        }
+        self.max_entry = Some(entry);
+        Ok(())
+    }
 
-        self.block_builder.append(entry)
+    pub(crate) fn add_range_tombstone(&mut self, range_tombstone: RangeTombstone<K>) {
+        self.max_seq = max(self.max_seq, range_tombstone.seq);
+        self.range_tombstones.push(range_tombstone);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.min_entry.is_none() && self.range_tombstones.is_empty()
     }
 
     pub(crate) fn full(&mut self) -> EikvResult<bool> {
@@ -67,21 +135,19 @@ impl<K: Key, V: Value> Writer<K, V> {
         debug_assert_eq!(self.options.block_size % 8, 0);
 
         let block_size = self.options.block_size as u64;
-        let index_block_start = if data_block_end % block_size == 0 {
+        let index_block_start = if data_block_end.is_multiple_of(block_size) {
             data_block_end
         } else {
             let padding_size = (block_size - data_block_end % block_size) as usize;
             let padding = vec![0; padding_size];
-            self.file.write(&padding)?;
+            self.write(&padding)?;
             data_block_end + padding_size as u64
         };
 
         let offset_count_one_block = self.options.block_size / 8 - 1;
-        let index_block_count =
-            (self.block_offsets.len() + offset_count_one_block - 1) / offset_count_one_block;
+        let index_block_count = self.block_offsets.len().div_ceil(offset_count_one_block);
 
-        let mut index_block = vec![];
-        index_block.reserve(self.options.block_size);
+        let mut index_block = Vec::with_capacity(self.options.block_size);
 
         for i in 0..index_block_count {
             index_block.clear();
@@ -99,29 +165,79 @@ impl<K: Key, V: Value> Writer<K, V> {
             append_fixed_u32(&mut index_block, 0);
             let checksum = crc32_checksum(&index_block);
             append_fixed_u32(&mut index_block, checksum);
-            self.file.write(&index_block)?;
+            self.write(&index_block)?;
         }
 
         Ok(index_block_start)
     }
 
+    fn build_range_del_block(&mut self) -> EikvResult<u64> {
+        let mut block = vec![];
+        for range_tombstone in &self.range_tombstones {
+            range_tombstone.to_entry::<V>().encode(&mut block)?;
+        }
+        let checksum = crc32_checksum(&block);
+        append_fixed_u32(&mut block, checksum);
+        self.write(&block)?;
+
+        let range_del_block_end = self.file.stream_position()?;
+        Ok(range_del_block_end)
+    }
+
+    fn build_index_key_block(&mut self) -> EikvResult<u64> {
+        let mut block = vec![];
+        for index_key in &self.index_keys {
+            append_var_u32(&mut block, index_key.len() as u32);
+            block.extend(index_key);
+        }
+        // Keys past the upper bound of the file don't need any block.
+        let upper_bound = self.options.comparator.find_short_successor(&self.last_key);
+        append_var_u32(&mut block, upper_bound.len() as u32);
+        block.extend(upper_bound);
+        let checksum = crc32_checksum(&block);
+        append_fixed_u32(&mut block, checksum);
+        self.write(&block)?;
+
+        let index_key_block_end = self.file.stream_position()?;
+        Ok(index_key_block_end)
+    }
+
     pub(crate) fn finish(mut self) -> EikvResult<()> {
+        // The footer needs a point entry, so a file holding only range
+        // tombstones gets a deletion at the start of the first one.
+        if self.min_entry.is_none() {
+            let range_tombstone = self.range_tombstones[0].clone();
+            let entry = Entry {
+                key: range_tombstone.start,
+                seq: range_tombstone.seq,
+                op: Op::Delete,
+            };
+            self.append(entry)?;
+        }
+
         let offset = self.file.stream_position()?;
         self.block_offsets.push(offset);
 
         let mut block_builder = Builder::new(self.options.clone());
         swap(&mut self.block_builder, &mut block_builder);
         let buf = block_builder.build()?;
-        self.file.write(&buf)?;
+        self.write(&buf)?;
 
         let data_block_end = self.file.stream_position()?;
         self.build_index_block(data_block_end)?;
+        let range_del_block_end = self.build_range_del_block()?;
+        let index_key_block_end = self.build_index_key_block()?;
 
         let footer = Footer {
-            min_entry: self.min_entry.unwrap(),
-            max_entry: self.max_entry.unwrap(),
+            version: FOOTER_VERSION,
+            min_entry: self.min_entry.take().unwrap(),
+            max_entry: self.max_entry.take().unwrap(),
             data_block_end,
             data_block_count: self.block_offsets.len() as u32,
+            range_del_block_end,
+            index_key_block_end,
+            max_seq: self.max_seq,
+            min_expire_at: self.min_expire_at,
         };
         let mut buf = vec![];
         footer.encode(&mut buf)?;
@@ -129,7 +245,7 @@ impl<K: Key, V: Value> Writer<K, V> {
         append_fixed_u32(&mut buf, footer_size);
         let checksum = crc32_checksum(&buf);
         append_fixed_u32(&mut buf, checksum);
-        self.file.write(&buf)?;
+        self.write(&buf)?;
 
         Ok(())
     }
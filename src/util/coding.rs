@@ -114,7 +114,7 @@ pub(crate) fn decode_var_u64(buf: &[u8]) -> Option<(u64, usize)> {
     None
 }
 
-pub(crate) fn decode_bytes_with_len(buf: &[u8]) -> Option<(Vec<u8>, usize)> {
+pub(crate) fn decode_slice_with_len(buf: &[u8]) -> Option<(&[u8], usize)> {
     let (bytes_len, mut buf_off) = match decode_var_u32(buf) {
         Some((bytes_len, n)) => (bytes_len as usize, n),
         None => return None,
@@ -122,7 +122,26 @@ pub(crate) fn decode_bytes_with_len(buf: &[u8]) -> Option<(Vec<u8>, usize)> {
     if buf_off + bytes_len > buf.len() {
         return None;
     }
-    let bytes = buf[buf_off..buf_off + bytes_len].to_vec();
+    let bytes = &buf[buf_off..buf_off + bytes_len];
     buf_off += bytes_len;
     Some((bytes, buf_off))
 }
+
+pub(crate) fn decode_bytes_with_len(buf: &[u8]) -> Option<(Vec<u8>, usize)> {
+    let (bytes, buf_off) = decode_slice_with_len(buf)?;
+    Some((bytes.to_vec(), buf_off))
+}
+
+// Appends what `encode` writes prefixed with its length, the prefix is put in
+// front afterwards so nothing is encoded twice.
+pub(crate) fn append_with_len<E>(
+    buf: &mut Vec<u8>,
+    encode: impl FnOnce(&mut Vec<u8>) -> Result<(), E>,
+) -> Result<(), E> {
+    let start = buf.len();
+    encode(buf)?;
+    let mut len = vec![];
+    append_var_u32(&mut len, (buf.len() - start) as u32);
+    buf.splice(start..start, len);
+    Ok(())
+}
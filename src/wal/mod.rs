@@ -1,7 +1,10 @@
 mod reader;
 mod write_batch;
+mod write_batch_with_index;
 mod writer;
 
 pub(crate) use reader::Reader;
-pub use write_batch::WriteBatch;
+pub(crate) use write_batch::{Marker, Precondition};
+pub use write_batch::{WriteBatch, WriteBatchHandler};
+pub use write_batch_with_index::{WriteBatchWithIndex, WriteBatchWithIndexIterator};
 pub(crate) use writer::Writer;
@@ -1,3 +1,4 @@
+use super::write_batch::EXTENDED_LEN_FLAG;
 use crate::{util::coding::decode_fixed_u32, EikvError, EikvResult, Key, Value, WriteBatch};
 use std::{fs::File, io::Read};
 
@@ -23,7 +24,7 @@ impl Reader {
         }
 
         let checksum = decode_fixed_u32(&buf[..4]);
-        let len = decode_fixed_u32(&buf[4..]) as usize;
+        let len = (decode_fixed_u32(&buf[4..]) & !EXTENDED_LEN_FLAG) as usize;
 
         let mut wb_buf = vec![0; len];
         wb_buf[4..8].copy_from_slice(&buf[4..]);
@@ -1,52 +1,260 @@
 use crate::{
-    model::Entry,
+    db::DEFAULT_CF_ID,
+    model::{Entry, Op},
     util::{
         checksum::crc32_checksum,
-        coding::{append_fixed_u32, encode_fixed_u32},
+        coding::{
+            append_fixed_u32, append_var_u32, decode_bytes_with_len, decode_fixed_u32,
+            decode_var_u32, encode_fixed_u32,
+        },
+        time::unix_now,
     },
-    EikvError, EikvResult, Key, Value,
+    ColumnFamilyHandle, EikvError, EikvResult, Key, Value,
 };
+use std::time::Duration;
 
+// Set in the length of a batch that has markers or entries of another column
+// family than the default. Such a batch stores a marker count and the markers
+// after its header and a column family id before each entry, other batches
+// keep the layout of the first version, its entries right after the header.
+pub(super) const EXTENDED_LEN_FLAG: u32 = 1 << 31;
+
+// The operations added after put and delete have default impls so handlers
+// written before them keep compiling.
+pub trait WriteBatchHandler<K: Key, V: Value> {
+    fn put(&mut self, key: &K, value: &V);
+
+    fn put_with_expiry(&mut self, key: &K, value: &V, _expire_at: u64) {
+        self.put(key, value);
+    }
+
+    fn delete(&mut self, key: &K);
+
+    fn delete_range(&mut self, _start: &K, _end: &K) {}
+
+    fn merge(&mut self, _key: &K, _operand: &V) {}
+
+    // The methods a handler of several column families implements, by default
+    // the entries of the default column family go to the methods above and
+    // the others are skipped.
+    fn put_cf(&mut self, cf_id: u32, key: &K, value: &V) {
+        if cf_id == DEFAULT_CF_ID {
+            self.put(key, value);
+        }
+    }
+
+    fn put_with_expiry_cf(&mut self, cf_id: u32, key: &K, value: &V, expire_at: u64) {
+        if cf_id == DEFAULT_CF_ID {
+            self.put_with_expiry(key, value, expire_at);
+        }
+    }
+
+    fn delete_cf(&mut self, cf_id: u32, key: &K) {
+        if cf_id == DEFAULT_CF_ID {
+            self.delete(key);
+        }
+    }
+
+    fn delete_range_cf(&mut self, cf_id: u32, start: &K, end: &K) {
+        if cf_id == DEFAULT_CF_ID {
+            self.delete_range(start, end);
+        }
+    }
+
+    fn merge_cf(&mut self, cf_id: u32, key: &K, operand: &V) {
+        if cf_id == DEFAULT_CF_ID {
+            self.merge(key, operand);
+        }
+    }
+}
+
+// Two-phase commit markers of the transaction named by the xid. The entries
+// of a batch with a prepare marker aren't applied until a commit marker.
+#[derive(Clone)]
+pub(crate) enum Marker {
+    Prepare(String),
+    Commit(String),
+    Rollback(String),
+}
+
+impl Marker {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        let (tag, xid) = match self {
+            Marker::Prepare(xid) => (1, xid),
+            Marker::Commit(xid) => (2, xid),
+            Marker::Rollback(xid) => (3, xid),
+        };
+        buf.push(tag);
+        append_var_u32(buf, xid.len() as u32);
+        buf.extend(xid.as_bytes());
+    }
+
+    fn decode(buf: &[u8]) -> Option<(Marker, usize)> {
+        let tag = *buf.first()?;
+        let (xid, n) = decode_bytes_with_len(&buf[1..])?;
+        let xid = String::from_utf8(xid).ok()?;
+        let marker = match tag {
+            1 => Marker::Prepare(xid),
+            2 => Marker::Commit(xid),
+            3 => Marker::Rollback(xid),
+            _ => return None,
+        };
+        Some((marker, n + 1))
+    }
+}
+
+// Checked by the leader of the write group right before the batch is written,
+// a batch is dropped as a whole if one of them doesn't hold.
+#[derive(Clone)]
+pub(crate) enum Precondition<K: Key, V: Value> {
+    Unchanged { key: K, seq: u64 },
+    Equals { key: K, value: Option<V> },
+}
+
+#[derive(Clone)]
 pub struct WriteBatch<K: Key, V: Value> {
     entries: Vec<Entry<K, V>>,
+    // The column family ids of the entries.
+    cf_ids: Vec<u32>,
+    markers: Vec<Marker>,
+    preconditions: Vec<Precondition<K, V>>,
+    save_points: Vec<(usize, usize)>,
+}
+
+impl<K: Key, V: Value> Default for WriteBatch<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<K: Key, V: Value> WriteBatch<K, V> {
     pub fn new() -> WriteBatch<K, V> {
-        WriteBatch { entries: vec![] }
+        WriteBatch {
+            entries: vec![],
+            cf_ids: vec![],
+            markers: vec![],
+            preconditions: vec![],
+            save_points: vec![],
+        }
     }
 
     pub(crate) fn entries(&self) -> &Vec<Entry<K, V>> {
         &self.entries
     }
 
-    pub(crate) fn len(&self) -> usize {
+    pub(crate) fn cf_entries(&self) -> impl Iterator<Item = (u32, &Entry<K, V>)> {
+        self.cf_ids.iter().copied().zip(self.entries.iter())
+    }
+
+    pub(crate) fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    pub(crate) fn add_marker(&mut self, marker: Marker) {
+        self.markers.push(marker);
+    }
+
+    pub(crate) fn clear_markers(&mut self) {
+        self.markers.clear();
+    }
+
+    pub(crate) fn take_preconditions(&mut self) -> Vec<Precondition<K, V>> {
+        std::mem::take(&mut self.preconditions)
+    }
+
+    pub(crate) fn prepared_xid(&self) -> Option<&str> {
+        self.markers.iter().find_map(|marker| match marker {
+            Marker::Prepare(xid) => Some(xid.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn len(&self) -> usize {
         self.entries.len()
     }
 
-    pub(crate) fn is_empty(&self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.entries.is_empty()
     }
 
-    pub(crate) fn extend(&mut self, other: Self) {
-        self.entries.extend(other.entries);
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cf_ids.clear();
+        self.markers.clear();
+        self.preconditions.clear();
+        self.save_points.clear();
+    }
+
+    pub fn set_save_point(&mut self) {
+        self.save_points
+            .push((self.entries.len(), self.preconditions.len()));
+    }
+
+    pub fn rollback_to_save_point(&mut self) -> EikvResult<()> {
+        match self.save_points.pop() {
+            Some((entry_count, precondition_count)) => {
+                self.entries.truncate(entry_count);
+                self.cf_ids.truncate(entry_count);
+                self.preconditions.truncate(precondition_count);
+                Ok(())
+            }
+            None => Err(EikvError::SavePointError(
+                "no save point has been set".to_owned(),
+            )),
+        }
+    }
+
+    pub fn pop_save_point(&mut self) -> EikvResult<()> {
+        match self.save_points.pop() {
+            Some(_) => Ok(()),
+            None => Err(EikvError::SavePointError(
+                "no save point has been set".to_owned(),
+            )),
+        }
+    }
+
+    pub fn data_size(&self) -> EikvResult<usize> {
+        let mut buf = vec![];
+        self.encode(&mut buf)?;
+        Ok(buf.len())
+    }
+
+    pub fn iterate<H: WriteBatchHandler<K, V>>(&self, handler: &mut H) {
+        for (cf_id, entry) in self.cf_entries() {
+            match &entry.op {
+                Op::Put(value) => handler.put_cf(cf_id, &entry.key, value),
+                Op::PutWithExpiry(value, expire_at) => {
+                    handler.put_with_expiry_cf(cf_id, &entry.key, value, *expire_at)
+                }
+                Op::Delete => handler.delete_cf(cf_id, &entry.key),
+                Op::DeleteRange(end) => handler.delete_range_cf(cf_id, &entry.key, end),
+                Op::Merge(operand) => handler.merge_cf(cf_id, &entry.key, operand),
+            }
+        }
+    }
+
+    pub(crate) fn into_cf_entries(self) -> impl Iterator<Item = (u32, Entry<K, V>)> {
+        self.cf_ids.into_iter().zip(self.entries)
     }
 
     pub(crate) fn set_seqs(&mut self, start: u64) {
-        let mut seq = start;
-        for entry in &mut self.entries {
+        for (seq, entry) in (start..).zip(self.entries.iter_mut()) {
             entry.seq = seq;
-            seq += 1;
         }
     }
 
+    fn push(&mut self, cf_id: u32, entry: Entry<K, V>) {
+        self.entries.push(entry);
+        self.cf_ids.push(cf_id);
+    }
+
     pub fn put(&mut self, key: K, value: V) -> &mut Self {
         let entry = Entry {
             key,
             seq: 0,
-            value: Some(value),
+            op: Op::Put(value),
         };
-        self.entries.push(entry);
+        self.push(DEFAULT_CF_ID, entry);
         self
     }
 
@@ -54,24 +262,175 @@ impl<K: Key, V: Value> WriteBatch<K, V> {
         let entry = Entry {
             key,
             seq: 0,
+            op: Op::Delete,
+        };
+        self.push(DEFAULT_CF_ID, entry);
+        self
+    }
+
+    pub fn delete_range(&mut self, start: K, end: K) -> &mut Self {
+        let entry = Entry {
+            key: start,
+            seq: 0,
+            op: Op::DeleteRange(end),
+        };
+        self.push(DEFAULT_CF_ID, entry);
+        self
+    }
+
+    pub fn put_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> &mut Self {
+        // A TTL past the end of time never expires.
+        let expire_at =
+            u64::try_from(unix_now().saturating_add(ttl.as_millis())).unwrap_or(u64::MAX);
+        let entry = Entry {
+            key,
+            seq: 0,
+            op: Op::PutWithExpiry(value, expire_at),
+        };
+        self.push(DEFAULT_CF_ID, entry);
+        self
+    }
+
+    pub fn put_if_absent(&mut self, key: K, value: V) -> &mut Self {
+        self.preconditions.push(Precondition::Equals {
+            key: key.clone(),
             value: None,
+        });
+        self.put(key, value)
+    }
+
+    pub fn delete_if_equals(&mut self, key: K, value: V) -> &mut Self {
+        self.preconditions.push(Precondition::Equals {
+            key: key.clone(),
+            value: Some(value),
+        });
+        self.delete(key)
+    }
+
+    pub fn merge(&mut self, key: K, operand: V) -> &mut Self {
+        let entry = Entry {
+            key,
+            seq: 0,
+            op: Op::Merge(operand),
         };
-        self.entries.push(entry);
+        self.push(DEFAULT_CF_ID, entry);
         self
     }
 
-    pub(super) fn encode(self, buf: &mut Vec<u8>) -> EikvResult<()> {
+    pub fn put_cf(&mut self, cf: &ColumnFamilyHandle, key: K, value: V) -> &mut Self {
+        let entry = Entry {
+            key,
+            seq: 0,
+            op: Op::Put(value),
+        };
+        self.push(cf.id(), entry);
+        self
+    }
+
+    pub fn delete_cf(&mut self, cf: &ColumnFamilyHandle, key: K) -> &mut Self {
+        let entry = Entry {
+            key,
+            seq: 0,
+            op: Op::Delete,
+        };
+        self.push(cf.id(), entry);
+        self
+    }
+
+    pub fn delete_range_cf(&mut self, cf: &ColumnFamilyHandle, start: K, end: K) -> &mut Self {
+        let entry = Entry {
+            key: start,
+            seq: 0,
+            op: Op::DeleteRange(end),
+        };
+        self.push(cf.id(), entry);
+        self
+    }
+
+    pub fn merge_cf(&mut self, cf: &ColumnFamilyHandle, key: K, operand: V) -> &mut Self {
+        let entry = Entry {
+            key,
+            seq: 0,
+            op: Op::Merge(operand),
+        };
+        self.push(cf.id(), entry);
+        self
+    }
+
+    // The preconditions aren't encoded, a batch holding any fails rather than
+    // losing them.
+    pub fn to_bytes(&self) -> EikvResult<Vec<u8>> {
+        if !self.preconditions.is_empty() {
+            let reason = format!(
+                "the write batch has {} preconditions, they can't be turned into bytes",
+                self.preconditions.len()
+            );
+            return Err(EikvError::WriteError(reason));
+        }
+        let mut buf = vec![];
+        self.encode(&mut buf)?;
+        Ok(buf)
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> EikvResult<Self> {
+        if buf.len() < 8 {
+            let reason = format!(
+                "the size of write batch header is 8, got {} bytes",
+                buf.len()
+            );
+            return Err(EikvError::WalCorrpution(reason));
+        }
+
+        let checksum = decode_fixed_u32(&buf[..4]);
+        let len = (decode_fixed_u32(&buf[4..8]) & !EXTENDED_LEN_FLAG) as usize;
+        if len != buf.len() {
+            let reason = format!(
+                "the size of write batch is {}, got {} bytes",
+                len,
+                buf.len()
+            );
+            return Err(EikvError::WalCorrpution(reason));
+        }
+
+        let mut wb_buf = buf.to_vec();
+        encode_fixed_u32(&mut wb_buf[..4], 0);
+        WriteBatch::decode(&wb_buf, checksum)
+    }
+
+    // The preconditions are checked before the batch is logged, they aren't
+    // encoded.
+    pub(crate) fn encode(&self, buf: &mut Vec<u8>) -> EikvResult<()> {
         let old_len = buf.len();
 
         append_fixed_u32(buf, 0);
         append_fixed_u32(buf, 0);
 
-        for entry in self.entries {
+        let extended =
+            !self.markers.is_empty() || self.cf_ids.iter().any(|&id| id != DEFAULT_CF_ID);
+        if extended {
+            append_var_u32(buf, self.markers.len() as u32);
+            for marker in &self.markers {
+                marker.encode(buf);
+            }
+        }
+        for (cf_id, entry) in self.cf_entries() {
+            if extended {
+                append_var_u32(buf, cf_id);
+            }
             entry.encode(buf)?;
         }
 
         let len = buf.len() - old_len;
-        encode_fixed_u32(&mut buf[old_len + 4..old_len + 8], len as u32);
+        if len as u32 & EXTENDED_LEN_FLAG != 0 {
+            let reason = format!("the size of write batch is too large, got {} bytes", len);
+            return Err(EikvError::WalCorrpution(reason));
+        }
+        let len = if extended {
+            len as u32 | EXTENDED_LEN_FLAG
+        } else {
+            len as u32
+        };
+        encode_fixed_u32(&mut buf[old_len + 4..old_len + 8], len);
         let checksum = crc32_checksum(&buf[old_len..]);
         encode_fixed_u32(&mut buf[old_len..old_len + 4], checksum);
 
@@ -79,20 +438,255 @@ impl<K: Key, V: Value> WriteBatch<K, V> {
     }
 
     pub(super) fn decode(buf: &[u8], checksum: u32) -> EikvResult<Self> {
-        if checksum != crc32_checksum(&buf) {
+        if checksum != crc32_checksum(buf) {
             let reason = "the checksumes of the write batch doesn't match".to_owned();
             return Err(EikvError::WalCorrpution(reason));
         }
 
+        let extended = decode_fixed_u32(&buf[4..8]) & EXTENDED_LEN_FLAG != 0;
         let mut buf_off = 8;
+        let mut markers = vec![];
+        if extended {
+            let corrupt = || EikvError::WalCorrpution("write batch markers are corrupt".to_owned());
+            let (marker_count, n) = decode_var_u32(&buf[buf_off..]).ok_or_else(corrupt)?;
+            buf_off += n;
+            for _ in 0..marker_count {
+                let (marker, n) = Marker::decode(&buf[buf_off..]).ok_or_else(corrupt)?;
+                markers.push(marker);
+                buf_off += n;
+            }
+        }
+
         let mut entries = vec![];
+        let mut cf_ids = vec![];
+        let corrupt = || EikvError::WalCorrpution("entry is corrupt".to_owned());
         while buf_off != buf.len() {
+            let mut cf_id = DEFAULT_CF_ID;
+            if extended {
+                let (id, n) = decode_var_u32(&buf[buf_off..]).ok_or_else(corrupt)?;
+                cf_id = id;
+                buf_off += n;
+            }
             let (entry, n) = Entry::decode(&buf[buf_off..])?;
             entries.push(entry);
+            cf_ids.push(cf_id);
             buf_off += n;
         }
 
-        let write_batch = WriteBatch { entries };
+        let write_batch = WriteBatch {
+            entries,
+            cf_ids,
+            markers,
+            preconditions: vec![],
+            save_points: vec![],
+        };
         Ok(write_batch)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Op, WriteBatch, WriteBatchHandler};
+    use crate::{
+        util::{checksum::crc32_checksum, coding::encode_fixed_u32},
+        ColumnFamilyHandle,
+    };
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct Recorder {
+        ops: Vec<(String, Option<String>)>,
+    }
+
+    fn range(start: &str, end: &str) -> String {
+        format!("[{start}, {end})")
+    }
+
+    impl WriteBatchHandler<String, String> for Recorder {
+        fn put(&mut self, key: &String, value: &String) {
+            self.ops.push((key.clone(), Some(value.clone())));
+        }
+
+        fn put_with_expiry(&mut self, key: &String, value: &String, expire_at: u64) {
+            self.ops
+                .push((format!("{key} until {expire_at}"), Some(value.clone())));
+        }
+
+        fn delete(&mut self, key: &String) {
+            self.ops.push((key.clone(), None));
+        }
+
+        fn delete_range(&mut self, start: &String, end: &String) {
+            self.ops.push((range(start, end), None));
+        }
+
+        fn merge(&mut self, key: &String, operand: &String) {
+            self.ops
+                .push((format!("merge {key}"), Some(operand.clone())));
+        }
+
+        fn put_cf(&mut self, cf_id: u32, key: &String, value: &String) {
+            self.ops.push((cf_key(cf_id, key), Some(value.clone())));
+        }
+
+        fn delete_cf(&mut self, cf_id: u32, key: &String) {
+            self.ops.push((cf_key(cf_id, key), None));
+        }
+    }
+
+    fn cf_key(cf_id: u32, key: &str) -> String {
+        match cf_id {
+            0 => key.to_owned(),
+            _ => format!("{cf_id}:{key}"),
+        }
+    }
+
+    fn ops(write_batch: &WriteBatch<String, String>) -> Vec<(String, Option<String>)> {
+        let mut recorder = Recorder::default();
+        write_batch.iterate(&mut recorder);
+        recorder.ops
+    }
+
+    #[test]
+    fn test_iterate() {
+        let mut write_batch = WriteBatch::new();
+        write_batch
+            .put("a".to_owned(), "1".to_owned())
+            .delete("b".to_owned())
+            .delete_range("c".to_owned(), "e".to_owned());
+        let want = vec![
+            ("a".to_owned(), Some("1".to_owned())),
+            ("b".to_owned(), None),
+            (range("c", "e"), None),
+        ];
+        assert_eq!(want, ops(&write_batch));
+        assert_eq!(3, write_batch.len());
+
+        write_batch.clear();
+        assert!(write_batch.is_empty());
+    }
+
+    #[test]
+    fn test_default_handler() {
+        // Only implements the operations of the first version.
+        struct PutRecorder(Vec<String>);
+
+        impl WriteBatchHandler<String, String> for PutRecorder {
+            fn put(&mut self, key: &String, _value: &String) {
+                self.0.push(key.clone());
+            }
+
+            fn delete(&mut self, _key: &String) {}
+        }
+
+        let mut write_batch = WriteBatch::new();
+        write_batch
+            .put("a".to_owned(), "1".to_owned())
+            .put_with_ttl("b".to_owned(), "2".to_owned(), Duration::from_secs(60))
+            .delete_range("c".to_owned(), "e".to_owned())
+            .merge("f".to_owned(), "3".to_owned());
+        let cf = ColumnFamilyHandle::new(3, "cf");
+        write_batch.put_cf(&cf, "g".to_owned(), "4".to_owned());
+        let mut recorder = PutRecorder(vec![]);
+        write_batch.iterate(&mut recorder);
+        assert_eq!(vec!["a".to_owned(), "b".to_owned()], recorder.0);
+    }
+
+    #[test]
+    fn test_put_with_ttl_overflow() {
+        let mut write_batch = WriteBatch::new();
+        write_batch.put_with_ttl("a".to_owned(), "1".to_owned(), Duration::MAX);
+        assert!(matches!(
+            write_batch.entries()[0].op,
+            Op::PutWithExpiry(_, u64::MAX)
+        ));
+    }
+
+    #[test]
+    fn test_save_point() {
+        let mut write_batch = WriteBatch::new();
+        write_batch.put("a".to_owned(), "1".to_owned());
+        write_batch.set_save_point();
+        write_batch.delete("a".to_owned());
+        write_batch.set_save_point();
+        write_batch.put("b".to_owned(), "2".to_owned());
+
+        write_batch.rollback_to_save_point().unwrap();
+        assert_eq!(2, write_batch.len());
+        write_batch.pop_save_point().unwrap();
+        assert_eq!(2, write_batch.len());
+        assert!(write_batch.rollback_to_save_point().is_err());
+    }
+
+    #[test]
+    fn test_bytes() {
+        let mut write_batch = WriteBatch::new();
+        write_batch
+            .put("a".to_owned(), "1".to_owned())
+            .delete("b".to_owned())
+            .delete_range("c".to_owned(), "e".to_owned())
+            .merge("f".to_owned(), "2".to_owned());
+        let bytes = write_batch.to_bytes().unwrap();
+        assert_eq!(bytes.len(), write_batch.data_size().unwrap());
+
+        let decoded = WriteBatch::from_bytes(&bytes).unwrap();
+        assert_eq!(ops(&write_batch), ops(&decoded));
+
+        assert!(WriteBatch::<String, String>::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_precondition_bytes() {
+        let mut write_batch = WriteBatch::new();
+        write_batch.put("a".to_owned(), "1".to_owned());
+        write_batch.set_save_point();
+        write_batch.put_if_absent("b".to_owned(), "2".to_owned());
+        assert!(write_batch.to_bytes().is_err());
+
+        write_batch.rollback_to_save_point().unwrap();
+        let bytes = write_batch.to_bytes().unwrap();
+        let decoded = WriteBatch::<String, String>::from_bytes(&bytes).unwrap();
+        assert_eq!(ops(&write_batch), ops(&decoded));
+    }
+
+    #[test]
+    fn test_cf_bytes() {
+        let cf = ColumnFamilyHandle::new(3, "cf");
+        let mut write_batch = WriteBatch::new();
+        write_batch
+            .put("a".to_owned(), "1".to_owned())
+            .put_cf(&cf, "a".to_owned(), "2".to_owned())
+            .delete_cf(&cf, "b".to_owned());
+        let bytes = write_batch.to_bytes().unwrap();
+
+        let decoded = WriteBatch::<String, String>::from_bytes(&bytes).unwrap();
+        let cf_ids: Vec<u32> = decoded.cf_entries().map(|(cf_id, _)| cf_id).collect();
+        assert_eq!(vec![0, 3, 3], cf_ids);
+        let want = vec![
+            ("a".to_owned(), Some("1".to_owned())),
+            ("3:a".to_owned(), Some("2".to_owned())),
+            ("3:b".to_owned(), None),
+        ];
+        assert_eq!(want, ops(&decoded));
+    }
+
+    #[test]
+    fn test_legacy_bytes() {
+        // The layout of the first version, entries right after the header.
+        let mut bytes = vec![0; 8];
+        bytes.extend_from_slice(&[1, b'a', 0, 1, 1, b'1']);
+        bytes.extend_from_slice(&[1, b'b', 0, 2]);
+        let len = bytes.len() as u32;
+        encode_fixed_u32(&mut bytes[4..8], len);
+        let checksum = crc32_checksum(&bytes);
+        encode_fixed_u32(&mut bytes[..4], checksum);
+
+        let decoded = WriteBatch::<String, String>::from_bytes(&bytes).unwrap();
+        let mut write_batch = WriteBatch::new();
+        write_batch
+            .put("a".to_owned(), "1".to_owned())
+            .delete("b".to_owned());
+        assert_eq!(ops(&write_batch), ops(&decoded));
+        assert_eq!(bytes, write_batch.to_bytes().unwrap());
+    }
+}
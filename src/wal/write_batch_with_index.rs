@@ -0,0 +1,229 @@
+use super::WriteBatch;
+use crate::{
+    db::DEFAULT_CF_ID, model::Op, ColumnFamilyHandle, DBIterator, EikvError, EikvResult, Key,
+    Value, DB,
+};
+use std::collections::{btree_map, BTreeMap};
+
+pub struct WriteBatchWithIndex<K: Key, V: Value> {
+    write_batch: WriteBatch<K, V>,
+    // The index of the last entry of each key in each column family.
+    index: BTreeMap<(u32, K), usize>,
+}
+
+impl<K: Key, V: Value> Default for WriteBatchWithIndex<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The index holds the last value of each key, range deletions, merges and
+// values with an expiry aren't resolved by it, so batches holding them are
+// rejected.
+impl<K: Key, V: Value> TryFrom<WriteBatch<K, V>> for WriteBatchWithIndex<K, V> {
+    type Error = EikvError;
+
+    fn try_from(write_batch: WriteBatch<K, V>) -> EikvResult<Self> {
+        if write_batch
+            .entries()
+            .iter()
+            .any(|entry| !matches!(entry.op, Op::Put(_) | Op::Delete))
+        {
+            let reason = "only puts and deletes can be indexed".to_owned();
+            return Err(EikvError::WriteError(reason));
+        }
+        let mut write_batch_with_index = WriteBatchWithIndex {
+            write_batch,
+            index: BTreeMap::new(),
+        };
+        write_batch_with_index.rebuild_index();
+        Ok(write_batch_with_index)
+    }
+}
+
+impl<K: Key, V: Value> WriteBatchWithIndex<K, V> {
+    pub fn new() -> WriteBatchWithIndex<K, V> {
+        WriteBatchWithIndex {
+            write_batch: WriteBatch::new(),
+            index: BTreeMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.write_batch.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.write_batch.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.write_batch.clear();
+        self.index.clear();
+    }
+
+    pub fn set_save_point(&mut self) {
+        self.write_batch.set_save_point();
+    }
+
+    pub fn rollback_to_save_point(&mut self) -> EikvResult<()> {
+        self.write_batch.rollback_to_save_point()?;
+        self.rebuild_index();
+        Ok(())
+    }
+
+    fn rebuild_index(&mut self) {
+        self.index.clear();
+        for (entry_index, (cf_id, entry)) in self.write_batch.cf_entries().enumerate() {
+            self.index.insert((cf_id, entry.key.clone()), entry_index);
+        }
+    }
+
+    pub fn pop_save_point(&mut self) -> EikvResult<()> {
+        self.write_batch.pop_save_point()
+    }
+
+    pub fn write_batch(&self) -> &WriteBatch<K, V> {
+        &self.write_batch
+    }
+
+    pub fn into_write_batch(self) -> WriteBatch<K, V> {
+        self.write_batch
+    }
+
+    pub fn put(&mut self, key: K, value: V) -> &mut Self {
+        self.index
+            .insert((DEFAULT_CF_ID, key.clone()), self.write_batch.len());
+        self.write_batch.put(key, value);
+        self
+    }
+
+    pub fn put_cf(&mut self, cf: &ColumnFamilyHandle, key: K, value: V) -> &mut Self {
+        self.index
+            .insert((cf.id(), key.clone()), self.write_batch.len());
+        self.write_batch.put_cf(cf, key, value);
+        self
+    }
+
+    pub fn delete(&mut self, key: K) -> &mut Self {
+        self.index
+            .insert((DEFAULT_CF_ID, key.clone()), self.write_batch.len());
+        self.write_batch.delete(key);
+        self
+    }
+
+    pub fn delete_cf(&mut self, cf: &ColumnFamilyHandle, key: K) -> &mut Self {
+        self.index
+            .insert((cf.id(), key.clone()), self.write_batch.len());
+        self.write_batch.delete_cf(cf, key);
+        self
+    }
+
+    fn get_from_batch(&self, cf_id: u32, key: &K) -> Option<Option<V>> {
+        self.index
+            .get(&(cf_id, key.clone()))
+            .map(|entry_index| self.write_batch.entries()[*entry_index].value().cloned())
+    }
+}
+
+impl<K: Key + 'static, V: Value + 'static> WriteBatchWithIndex<K, V> {
+    pub fn get_from_batch_and_db(&self, db: &DB<K, V>, key: K) -> EikvResult<Option<V>> {
+        self.get_from_batch_and_db_cf(db, &db.default_cf(), key)
+    }
+
+    pub fn get_from_batch_and_db_cf(
+        &self,
+        db: &DB<K, V>,
+        cf: &ColumnFamilyHandle,
+        key: K,
+    ) -> EikvResult<Option<V>> {
+        match self.get_from_batch(cf.id(), &key) {
+            Some(value) => Ok(value),
+            None => db.get_cf(cf, key),
+        }
+    }
+
+    pub fn iter(&self, db: &DB<K, V>) -> EikvResult<WriteBatchWithIndexIterator<'_, K, V>> {
+        self.iter_cf(db, &db.default_cf())
+    }
+
+    pub fn iter_cf(
+        &self,
+        db: &DB<K, V>,
+        cf: &ColumnFamilyHandle,
+    ) -> EikvResult<WriteBatchWithIndexIterator<'_, K, V>> {
+        let mut db_iterator = db.iter_cf(cf)?;
+        let db_next = db_iterator.next().transpose()?;
+        let mut iterator = WriteBatchWithIndexIterator {
+            write_batch: &self.write_batch,
+            cf_id: cf.id(),
+            batch_iterator: self.index.iter(),
+            batch_next: None,
+            db_iterator,
+            db_next,
+        };
+        iterator.batch_next = iterator.next_batch_entry();
+        Ok(iterator)
+    }
+}
+
+pub struct WriteBatchWithIndexIterator<'a, K: Key, V: Value> {
+    write_batch: &'a WriteBatch<K, V>,
+    cf_id: u32,
+    batch_iterator: btree_map::Iter<'a, (u32, K), usize>,
+    batch_next: Option<(&'a K, &'a usize)>,
+    db_iterator: DBIterator<K, V>,
+    db_next: Option<(K, V)>,
+}
+
+impl<'a, K: Key, V: Value> WriteBatchWithIndexIterator<'a, K, V> {
+    // The index is ordered by column family first.
+    fn next_batch_entry(&mut self) -> Option<(&'a K, &'a usize)> {
+        loop {
+            let ((cf_id, key), entry_index) = self.batch_iterator.next()?;
+            if *cf_id == self.cf_id {
+                return Some((key, entry_index));
+            } else if *cf_id > self.cf_id {
+                return None;
+            }
+        }
+    }
+
+    fn next_entry(&mut self) -> EikvResult<Option<(K, V)>> {
+        loop {
+            let (key, entry_index) = match (self.batch_next, &self.db_next) {
+                (None, None) => return Ok(None),
+                (None, Some(_)) => {
+                    let db_next = self.db_iterator.next().transpose()?;
+                    return Ok(std::mem::replace(&mut self.db_next, db_next));
+                }
+                (Some((key, _)), Some((db_key, _))) if db_key < key => {
+                    let db_next = self.db_iterator.next().transpose()?;
+                    return Ok(std::mem::replace(&mut self.db_next, db_next));
+                }
+                (Some(batch_next), db_next) => {
+                    if db_next
+                        .as_ref()
+                        .is_some_and(|(db_key, _)| db_key == batch_next.0)
+                    {
+                        self.db_next = self.db_iterator.next().transpose()?;
+                    }
+                    batch_next
+                }
+            };
+
+            self.batch_next = self.next_batch_entry();
+            if let Some(value) = self.write_batch.entries()[*entry_index].value() {
+                return Ok(Some((key.clone(), value.clone())));
+            }
+        }
+    }
+}
+
+impl<K: Key, V: Value> Iterator for WriteBatchWithIndexIterator<'_, K, V> {
+    type Item = EikvResult<(K, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{EikvResult, Key, Value, WriteBatch};
+use crate::EikvResult;
 use std::{
     fs::{File, OpenOptions},
     io::{Seek, Write},
@@ -26,10 +26,9 @@ impl Writer {
         Ok(writer)
     }
 
-    pub(crate) fn append<K: Key, V: Value>(&self, write_batch: WriteBatch<K, V>) -> EikvResult<()> {
-        let mut buf = vec![];
-        write_batch.encode(&mut buf)?;
-        self.file.lock().unwrap().write(&buf)?;
+    // Appends encoded write batches.
+    pub(crate) fn append(&self, buf: &[u8]) -> EikvResult<()> {
+        self.file.lock().unwrap().write_all(buf)?;
         Ok(())
     }
 
@@ -0,0 +1,110 @@
+mod common;
+
+use eikv::{DBOptions, EikvError, RateLimiter, DB};
+use std::{fs, sync::Arc, thread, time::Duration};
+
+fn fill(db: &DB<String, String>, count: usize) {
+    for i in 0..count {
+        db.put(format!("key{i:05}"), format!("value{i:05}"))
+            .unwrap();
+    }
+}
+
+fn assert_filled(db: &DB<String, String>, count: usize) {
+    for i in (0..count).step_by(97) {
+        let value = db.get(format!("key{i:05}")).unwrap();
+        assert_eq!(Some(format!("value{i:05}")), value);
+    }
+    assert_eq!(count, db.iter().unwrap().count());
+}
+
+#[test]
+fn test_cancel_and_retry_manual_compaction() {
+    let path = common::db_path("cancel_manual_compaction");
+    let options = DBOptions {
+        max_subcompactions: 2,
+        // Slow enough for the cancel to hit a running merge.
+        rate_limiter: Some(Arc::new(RateLimiter::new(256 * 1024))),
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    fill(&db, 20000);
+
+    db.disable_manual_compaction();
+    assert!(matches!(
+        db.compact(),
+        Err(EikvError::CompactionCancelled(_))
+    ));
+    db.enable_manual_compaction();
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            thread::sleep(Duration::from_millis(200));
+            db.disable_manual_compaction();
+        });
+        match db.compact() {
+            Ok(()) | Err(EikvError::CompactionCancelled(_)) => (),
+            Err(err) => panic!("{err}"),
+        }
+    });
+
+    // The inputs of the cancelled merge are free to compact again.
+    db.enable_manual_compaction();
+    db.compact().unwrap();
+    assert_filled(&db, 20000);
+    let tmp_files = fs::read_dir(format!("{path}/sst/tmp")).unwrap().count();
+    assert_eq!(0, tmp_files);
+}
+
+#[test]
+fn test_pause_background_work() {
+    let path = common::db_path("pause_background_work");
+    let options = DBOptions {
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    db.pause_background_work();
+    let ssts = || fs::read_dir(format!("{path}/sst")).unwrap().count();
+    let ssts_before = ssts();
+    // Fewer memtables than max_write_buffer_number, so writes don't stop.
+    for i in 0..50 {
+        db.put(format!("key{i:05}"), "x".repeat(100)).unwrap();
+    }
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(ssts_before, ssts());
+
+    db.continue_background_work();
+    fill(&db, 5000);
+    db.compact().unwrap();
+    assert!(ssts() > ssts_before);
+    assert_filled(&db, 5000);
+}
+
+// A failed flush stops the background work, the writes and the manual
+// compactions fail with its error from then on while reads go on.
+#[test]
+fn test_background_error() {
+    let path = common::db_path("background_error");
+    let options = DBOptions {
+        max_write_buffer_number: 2,
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    db.put("key".to_owned(), "value".to_owned()).unwrap();
+    // Flushes write to the temporary directory first.
+    fs::remove_dir_all(format!("{path}/sst/tmp")).unwrap();
+
+    let err = (0..100000)
+        .find_map(|i| db.put(format!("key{i:05}"), "x".repeat(100)).err())
+        .unwrap();
+    assert!(matches!(err, EikvError::BackgroundError(_)), "{err}");
+    assert!(matches!(
+        db.put("key".to_owned(), "value".to_owned()),
+        Err(EikvError::BackgroundError(_))
+    ));
+    assert!(matches!(db.compact(), Err(EikvError::BackgroundError(_))));
+    assert_eq!(Some("value".to_owned()), db.get("key".to_owned()).unwrap());
+}
@@ -0,0 +1,158 @@
+mod common;
+
+use eikv::{DBOptions, RateLimiter, WriteBatch, WriteBatchHandler, WriteBatchWithIndex, DB};
+use std::{collections::HashMap, fs, sync::Arc, thread, time::Duration};
+
+#[test]
+fn test_column_families() {
+    let path = common::db_path("column_families");
+    let options = DBOptions {
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let cfs = HashMap::from([("other".to_owned(), options.clone())]);
+    {
+        let db: DB<String, String> = DB::new(&path, options.clone()).unwrap();
+        let cf = db.create_cf("other", options.clone()).unwrap();
+        for i in 0..1000 {
+            db.put(format!("key{i:04}"), format!("default{i}")).unwrap();
+            db.put_cf(&cf, format!("key{i:04}"), format!("other{i}"))
+                .unwrap();
+        }
+        db.delete_cf(&cf, "key0001".to_owned()).unwrap();
+        db.delete_range_cf(&cf, "key0100".to_owned(), "key0200".to_owned())
+            .unwrap();
+    }
+
+    let db: DB<String, String> = DB::new_with_cfs(&path, options, cfs).unwrap();
+    let cf = db.cf_handle("other").unwrap();
+    assert_eq!(
+        Some("default1".to_owned()),
+        db.get("key0001".to_owned()).unwrap()
+    );
+    assert_eq!(None, db.get_cf(&cf, "key0001".to_owned()).unwrap());
+    assert_eq!(
+        Some("other7".to_owned()),
+        db.get_cf(&cf, "key0007".to_owned()).unwrap()
+    );
+    assert_eq!(1000, db.iter().unwrap().count());
+    assert_eq!(899, db.iter_cf(&cf).unwrap().count());
+}
+
+#[test]
+fn test_write_batch_column_families() {
+    #[derive(Default)]
+    struct Recorder(Vec<(u32, String)>);
+
+    impl WriteBatchHandler<String, String> for Recorder {
+        fn put(&mut self, key: &String, _value: &String) {
+            self.0.push((0, key.clone()));
+        }
+
+        fn delete(&mut self, _key: &String) {}
+
+        fn put_cf(&mut self, cf_id: u32, key: &String, _value: &String) {
+            self.0.push((cf_id, key.clone()));
+        }
+    }
+
+    let path = common::db_path("write_batch_column_families");
+    let db: DB<String, String> = DB::new(&path, DBOptions::default()).unwrap();
+    let cf = db.create_cf("other", DBOptions::default()).unwrap();
+    db.put("a".to_owned(), "db".to_owned()).unwrap();
+    db.put_cf(&cf, "b".to_owned(), "db".to_owned()).unwrap();
+
+    let mut write_batch = WriteBatch::new();
+    write_batch
+        .put("a".to_owned(), "1".to_owned())
+        .put_cf(&cf, "a".to_owned(), "2".to_owned());
+    let mut recorder = Recorder::default();
+    write_batch.iterate(&mut recorder);
+    assert_eq!(
+        vec![(0, "a".to_owned()), (cf.id(), "a".to_owned())],
+        recorder.0
+    );
+
+    let mut write_batch = WriteBatchWithIndex::try_from(write_batch).unwrap();
+    write_batch.delete_cf(&cf, "b".to_owned());
+    let default_cf = db.default_cf();
+    let get = |cf, key: &str| {
+        write_batch
+            .get_from_batch_and_db_cf(&db, cf, key.to_owned())
+            .unwrap()
+    };
+    assert_eq!(Some("1".to_owned()), get(&default_cf, "a"));
+    assert_eq!(Some("2".to_owned()), get(&cf, "a"));
+    assert_eq!(None, get(&cf, "b"));
+    let keys: Vec<String> = write_batch
+        .iter(&db)
+        .unwrap()
+        .map(|item| item.unwrap().0)
+        .collect();
+    assert_eq!(vec!["a".to_owned()], keys);
+    let entries: Vec<(String, String)> = write_batch
+        .iter_cf(&db, &cf)
+        .unwrap()
+        .map(|item| item.unwrap())
+        .collect();
+    assert_eq!(vec![("a".to_owned(), "2".to_owned())], entries);
+
+    db.write(write_batch.into_write_batch()).unwrap();
+    assert_eq!(Some("1".to_owned()), db.get("a".to_owned()).unwrap());
+    assert_eq!(
+        Some("2".to_owned()),
+        db.get_cf(&cf, "a".to_owned()).unwrap()
+    );
+    assert_eq!(None, db.get_cf(&cf, "b".to_owned()).unwrap());
+
+    // The index resolves neither range deletions nor merges.
+    let mut write_batch = WriteBatch::new();
+    write_batch
+        .put("a".to_owned(), "1".to_owned())
+        .delete_range("a".to_owned(), "c".to_owned());
+    assert!(WriteBatchWithIndex::try_from(write_batch).is_err());
+    let mut write_batch = WriteBatch::new();
+    write_batch.merge_cf(&cf, "a".to_owned(), "1".to_owned());
+    assert!(WriteBatchWithIndex::try_from(write_batch).is_err());
+}
+
+// The frozen memtables of a column family created while a flush runs are
+// flushed with their own WALs.
+#[test]
+fn test_create_cf_during_flush() {
+    let path = common::db_path("create_cf_during_flush");
+    let options = DBOptions {
+        // Slow enough for the flush to outlast the new column family.
+        rate_limiter: Some(Arc::new(RateLimiter::new(4096))),
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options.clone()).unwrap();
+    let wal_count = || fs::read_dir(format!("{path}/wal")).unwrap().count();
+    let rotate = |prefix: &str| {
+        let count = wal_count();
+        for i in 0.. {
+            db.put(format!("{prefix}{i:04}"), "v".repeat(100)).unwrap();
+            if wal_count() > count {
+                break;
+            }
+        }
+    };
+
+    rotate("a");
+    let cf = db.create_cf("other", options).unwrap();
+    for i in 0..10 {
+        db.put_cf(&cf, format!("key{i:04}"), format!("other{i}"))
+            .unwrap();
+    }
+    rotate("b");
+    while wal_count() > 1 {
+        thread::sleep(Duration::from_millis(10));
+    }
+    for i in 0..10 {
+        assert_eq!(
+            Some(format!("other{i}")),
+            db.get_cf(&cf, format!("key{i:04}")).unwrap()
+        );
+    }
+}
@@ -0,0 +1,88 @@
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+// A fresh directory for a test DB, removed first if an earlier run left it.
+pub fn db_path(name: &str) -> String {
+    let path = env::temp_dir().join(format!("eikv_test_{name}"));
+    if path.exists() {
+        fs::remove_dir_all(&path).unwrap();
+    }
+    path.to_str().unwrap().to_owned()
+}
+
+// Copies a DB under tests/fixtures to a fresh directory.
+pub fn fixture_db_path(fixture: &str, name: &str) -> String {
+    let path = db_path(name);
+    let src = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(fixture);
+    copy_dir(&src, Path::new(&path));
+    path
+}
+
+fn copy_dir(src: &Path, dst: &Path) {
+    fs::create_dir_all(dst).unwrap();
+    for entry in fs::read_dir(src).unwrap() {
+        let entry = entry.unwrap();
+        let dst: PathBuf = dst.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &dst);
+        } else {
+            fs::copy(entry.path(), dst).unwrap();
+        }
+    }
+}
+
+// The manifest lines of the SSTs of the column family in the manifest the
+// current file names.
+fn sst_lines(path: &str, cf_id: u32) -> Vec<String> {
+    let path = Path::new(path);
+    loop {
+        // A manifest replaced in between is read again.
+        let manifest_seq = fs::read_to_string(path.join("current")).unwrap();
+        let manifest_path = path.join(format!("manifest/{manifest_seq}.manifest"));
+        if let Ok(manifest) = fs::read_to_string(manifest_path) {
+            let prefix = format!("sst:{cf_id}:");
+            return manifest
+                .lines()
+                .filter(|line| line.starts_with(&prefix))
+                .map(|line| line.to_owned())
+                .collect();
+        }
+    }
+}
+
+pub fn sst_count(path: &str, cf_id: u32) -> usize {
+    sst_lines(path, cf_id).len()
+}
+
+// The smallest and the largest encoded key of every SST of the column family.
+pub fn sst_key_ranges(path: &str, cf_id: u32) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let decode_hex = |hex: &str| -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    };
+    sst_lines(path, cf_id)
+        .iter()
+        .map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            (decode_hex(fields[5]), decode_hex(fields[6]))
+        })
+        .collect()
+}
+
+// The bytes taken by the SSTs of the DB.
+pub fn sst_size(path: &str) -> u64 {
+    fs::read_dir(Path::new(path).join("sst"))
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_name().to_str().unwrap().ends_with(".sst"))
+        .map(|entry| entry.metadata().map_or(0, |metadata| metadata.len()))
+        .sum()
+}
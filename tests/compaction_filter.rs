@@ -0,0 +1,90 @@
+mod common;
+
+use eikv::{CompactionDecision, CompactionFilter, CompactionFilterContext, DBOptions, Key, DB};
+use std::sync::Arc;
+
+// Removes the values of the keys starting with "gdpr".
+struct GdprFilter;
+
+impl CompactionFilter for GdprFilter {
+    fn filter(
+        &self,
+        _context: &CompactionFilterContext,
+        key: &[u8],
+        _value: &[u8],
+    ) -> CompactionDecision {
+        let key = <String as Key>::decode(key).unwrap();
+        if key.starts_with("gdpr") {
+            CompactionDecision::Remove
+        } else {
+            CompactionDecision::Keep
+        }
+    }
+}
+
+// Rewrites the files holding the gdpr keys.
+fn fill_and_compact(db: &DB<String, String>, round: usize) {
+    for i in 0..5000 {
+        db.put(format!("a{i:05}"), format!("value{round}")).unwrap();
+    }
+    db.put("zzz".to_owned(), format!("value{round}")).unwrap();
+    for i in 0..5000 {
+        db.put(format!("b{i:05}"), format!("value{round}")).unwrap();
+    }
+    db.compact().unwrap();
+}
+
+#[test]
+fn test_compaction_filter_keeps_read_versions() {
+    let path = common::db_path("compaction_filter_read_versions");
+    let options = DBOptions {
+        compaction_filter: Some(Arc::new(GdprFilter)),
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    db.put("gdpr1".to_owned(), "secret".to_owned()).unwrap();
+
+    // The version the transaction read stays until it ends.
+    let mut transaction = db.begin_optimistic_transaction();
+    assert_eq!(
+        Some("secret".to_owned()),
+        transaction.get("gdpr1".to_owned()).unwrap()
+    );
+    fill_and_compact(&db, 1);
+    assert_eq!(
+        Some("secret".to_owned()),
+        db.get("gdpr1".to_owned()).unwrap()
+    );
+    transaction.rollback();
+
+    fill_and_compact(&db, 2);
+    assert_eq!(None, db.get("gdpr1".to_owned()).unwrap());
+    assert_eq!(
+        Some("value2".to_owned()),
+        db.get("a00001".to_owned()).unwrap()
+    );
+}
+
+// Files moving down on their own still reach the filter in the last level.
+#[test]
+fn test_compaction_filter_last_level() {
+    let path = common::db_path("compaction_filter_last_level");
+    let options = DBOptions {
+        compaction_filter: Some(Arc::new(GdprFilter)),
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    db.put("gdpr1".to_owned(), "secret".to_owned()).unwrap();
+    // The files of the ascending keys overlap nothing.
+    for i in 0..2000 {
+        db.put(format!("key{i:05}"), "value".to_owned()).unwrap();
+    }
+    db.compact().unwrap();
+    assert_eq!(None, db.get("gdpr1".to_owned()).unwrap());
+    assert_eq!(
+        Some("value".to_owned()),
+        db.get("key00000".to_owned()).unwrap()
+    );
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use eikv::{Comparator, DBOptions, EikvError, Key, DB};
+use std::{cmp::Ordering, error::Error, sync::Arc};
+
+// Orders keys from the largest to the smallest, agreeing with RevKey.
+struct ReverseComparator;
+
+impl Comparator for ReverseComparator {
+    fn name(&self) -> &str {
+        "test.ReverseComparator"
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        b.cmp(a)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+struct RevKey(String);
+
+impl PartialOrd for RevKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RevKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.0.cmp(&self.0)
+    }
+}
+
+impl Key for RevKey {
+    fn encode(&self, buf: &mut Vec<u8>) -> Result<(), Box<dyn Error>> {
+        self.0.encode(buf)
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        Ok(RevKey(String::decode(bytes)?))
+    }
+}
+
+#[test]
+fn test_custom_comparator() {
+    let path = common::db_path("custom_comparator");
+    let options = DBOptions {
+        comparator: Arc::new(ReverseComparator),
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    {
+        let db: DB<RevKey, String> = DB::new(&path, options.clone()).unwrap();
+        for i in 0..2000 {
+            db.put(RevKey(format!("key{i:04}")), format!("value{i}"))
+                .unwrap();
+        }
+        db.compact().unwrap();
+    }
+
+    let db: DB<RevKey, String> = DB::new(&path, options).unwrap();
+    for i in (0..2000).step_by(7) {
+        let value = db.get(RevKey(format!("key{i:04}"))).unwrap();
+        assert_eq!(Some(format!("value{i}")), value);
+    }
+    assert_eq!(None, db.get(RevKey("key2000".to_owned())).unwrap());
+    let keys: Vec<String> = db.iter().unwrap().map(|item| item.unwrap().0 .0).collect();
+    assert_eq!(2000, keys.len());
+    assert_eq!("key1999", keys[0]);
+    assert_eq!("key0000", keys[1999]);
+}
+
+// A comparator disagreeing with the Ord of the keys fails the flushes, the
+// writes fail with their error instead of waiting for them.
+#[test]
+fn test_comparator_disagreeing_with_ord() {
+    let path = common::db_path("comparator_disagreeing_with_ord");
+    let options = DBOptions {
+        comparator: Arc::new(ReverseComparator),
+        max_write_buffer_number: 2,
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    let err = (0..100000)
+        .find_map(|i| db.put(format!("key{i:05}"), format!("value{i}")).err())
+        .unwrap();
+    assert!(matches!(err, EikvError::BackgroundError(_)), "{err}");
+    assert!(err.to_string().contains("test.ReverseComparator"));
+    assert!(db.put("key".to_owned(), "value".to_owned()).is_err());
+    assert_eq!(
+        Some("value1".to_owned()),
+        db.get("key00001".to_owned()).unwrap()
+    );
+}
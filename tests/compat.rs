@@ -0,0 +1,67 @@
+mod common;
+
+use eikv::{DBOptions, DB};
+use std::{fs, path::Path};
+
+// The fixtures were written by the first version of eikv, it kept the SSTs of
+// each level in a directory of its own. Git doesn't keep the empty ones.
+fn baseline_db_path(fixture: &str, name: &str) -> String {
+    let path = common::fixture_db_path(&format!("baseline/{fixture}"), name);
+    for dir in ["1", "2", "3", "4", "5", "6", "7", "8", "tmp"] {
+        fs::create_dir_all(format!("{path}/sst/{dir}")).unwrap();
+    }
+    path
+}
+
+#[test]
+fn test_open_baseline_wal() {
+    let path = baseline_db_path("wal_only", "baseline_wal");
+    for round in 0..2 {
+        let db: DB<String, String> = DB::new(&path, DBOptions::default()).unwrap();
+        for i in 0..10 {
+            let value = db.get(format!("key{i:02}")).unwrap();
+            if i == 3 {
+                assert_eq!(None, value);
+            } else {
+                assert_eq!(Some(format!("value{i}")), value);
+            }
+        }
+        assert_eq!(9 + round, db.iter().unwrap().count());
+        db.put("key10".to_owned(), "value10".to_owned()).unwrap();
+    }
+}
+
+#[test]
+fn test_open_baseline_ssts() {
+    let path = baseline_db_path("with_sst", "baseline_ssts");
+    // Not referred to by the manifest.
+    fs::write(format!("{path}/sst/3/000100.sst"), b"orphan").unwrap();
+    let options = DBOptions {
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    for _ in 0..2 {
+        let db: DB<String, String> = DB::new(&path, options.clone()).unwrap();
+        for level in 1..=8 {
+            assert!(!Path::new(&format!("{path}/sst/{level}")).exists());
+        }
+        for i in 0..300 {
+            let value = db.get(format!("key{i:04}")).unwrap();
+            if i == 3 {
+                assert_eq!(None, value);
+            } else {
+                assert_eq!(Some(format!("value{i}")), value);
+            }
+        }
+        assert_eq!(299, db.iter().unwrap().count());
+
+        // Old files are compacted with new ones.
+        for i in 0..300 {
+            db.put(format!("key{i:04}"), format!("value{i}")).unwrap();
+        }
+        db.compact().unwrap();
+        assert_eq!(300, db.iter().unwrap().count());
+        db.delete("key0003".to_owned()).unwrap();
+        assert_eq!(299, db.iter().unwrap().count());
+    }
+}
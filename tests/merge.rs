@@ -0,0 +1,90 @@
+mod common;
+
+use eikv::{DBOptions, MergeOperator, Value, DB};
+use std::{error::Error, sync::Arc};
+
+// Adds up the decimal operands.
+struct Sum;
+
+fn decode(buf: &[u8]) -> Result<u64, Box<dyn Error>> {
+    Ok(<String as Value>::decode(buf)?.parse()?)
+}
+
+fn encode(n: u64) -> Result<Vec<u8>, Box<dyn Error>> {
+    n.to_string().to_bytes()
+}
+
+impl MergeOperator for Sum {
+    fn full_merge(
+        &self,
+        _key: &[u8],
+        existing_value: Option<&[u8]>,
+        operands: &[Vec<u8>],
+    ) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut sum = existing_value.map_or(Ok(0), decode)?;
+        for operand in operands {
+            sum += decode(operand)?;
+        }
+        encode(sum)
+    }
+
+    fn partial_merge(
+        &self,
+        _key: &[u8],
+        left_operand: &[u8],
+        right_operand: &[u8],
+    ) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        Ok(Some(encode(
+            decode(left_operand)? + decode(right_operand)?,
+        )?))
+    }
+}
+
+fn check(db: &DB<String, String>) {
+    assert_eq!(
+        Some("1010".to_owned()),
+        db.get("counter".to_owned()).unwrap()
+    );
+    assert_eq!(Some("15".to_owned()), db.get("fresh".to_owned()).unwrap());
+    assert_eq!(Some("2".to_owned()), db.get("gone".to_owned()).unwrap());
+    let values = db.multi_get(vec!["counter".to_owned(), "fresh".to_owned()]);
+    assert_eq!(Some("1010".to_owned()), *values[0].as_ref().unwrap());
+    assert_eq!(Some("15".to_owned()), *values[1].as_ref().unwrap());
+    let counter = db
+        .iter()
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .find(|(key, _)| key == "counter");
+    assert_eq!(Some(("counter".to_owned(), "1010".to_owned())), counter);
+}
+
+#[test]
+fn test_merge() {
+    let path = common::db_path("merge");
+    let options = DBOptions {
+        merge_operator: Some(Arc::new(Sum)),
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options.clone()).unwrap();
+    db.put("counter".to_owned(), "10".to_owned()).unwrap();
+    db.put("gone".to_owned(), "100".to_owned()).unwrap();
+    // The operands spread over the memtables and many SSTs.
+    for i in 0..1000 {
+        db.merge("counter".to_owned(), "1".to_owned()).unwrap();
+        db.put(format!("key{i:04}"), "value".to_owned()).unwrap();
+    }
+    for _ in 0..3 {
+        db.merge("fresh".to_owned(), "5".to_owned()).unwrap();
+    }
+    db.delete("gone".to_owned()).unwrap();
+    db.merge("gone".to_owned(), "2".to_owned()).unwrap();
+    check(&db);
+
+    db.compact().unwrap();
+    check(&db);
+    drop(db);
+
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    check(&db);
+}
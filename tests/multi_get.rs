@@ -0,0 +1,47 @@
+mod common;
+
+use eikv::{DBOptions, DB};
+
+#[test]
+fn test_multi_get() {
+    let path = common::db_path("multi_get");
+    let options = DBOptions {
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    for i in 0..10000 {
+        db.put(format!("key{i:05}"), format!("value{i:05}"))
+            .unwrap();
+    }
+    for i in (0..10000).step_by(3) {
+        db.delete(format!("key{i:05}")).unwrap();
+    }
+
+    // Unsorted keys spread over many SSTs, with repeated and missing ones.
+    let mut keys: Vec<String> = (0..10000)
+        .rev()
+        .step_by(7)
+        .map(|i| format!("key{i:05}"))
+        .collect();
+    keys.push("key00001".to_owned());
+    keys.push("key00001".to_owned());
+    keys.push("missing".to_owned());
+    let want: Vec<Option<String>> = keys
+        .iter()
+        .map(|key| db.get(key.clone()).unwrap())
+        .collect();
+    assert!(want.iter().any(Option::is_none));
+
+    for parallel in [false, true] {
+        let values: Vec<Option<String>> = if parallel {
+            db.multi_get_parallel(keys.clone())
+        } else {
+            db.multi_get(keys.clone())
+        }
+        .into_iter()
+        .map(|value| value.unwrap())
+        .collect();
+        assert_eq!(want, values);
+    }
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use eikv::{DBOptions, DB};
+use std::{
+    fs,
+    path::Path,
+    thread,
+    time::{Duration, Instant},
+};
+
+fn fill(db: &DB<String, String>) {
+    for i in 0..2000 {
+        db.put(format!("key{i:04}"), "value".to_owned()).unwrap();
+    }
+}
+
+fn assert_filled(db: &DB<String, String>) {
+    assert_eq!(
+        Some("value".to_owned()),
+        db.get("key0000".to_owned()).unwrap()
+    );
+    assert_eq!(2000, db.iter().unwrap().count());
+}
+
+#[test]
+fn test_remove_orphan_files_on_open() {
+    let path = common::db_path("orphan_files_on_open");
+    let options = DBOptions {
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options.clone()).unwrap();
+    fill(&db);
+    drop(db);
+
+    let orphans = [
+        "current.tmp",
+        "manifest/999999.manifest",
+        "sst/999999.sst",
+        "sst/tmp/major_000001_1.sst",
+        "wal/999999.wal",
+    ]
+    .map(|orphan| Path::new(&path).join(orphan));
+    for orphan in &orphans {
+        fs::write(orphan, b"orphan").unwrap();
+    }
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    for orphan in &orphans {
+        assert!(!orphan.exists(), "{} is left", orphan.display());
+    }
+    assert_filled(&db);
+}
+
+// While the DB is open, the temporary files of running merges are kept.
+#[test]
+fn test_remove_orphan_files_periodically() {
+    let path = common::db_path("orphan_files_periodically");
+    let options = DBOptions {
+        delete_obsolete_files_period: Duration::from_millis(100),
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    let orphan = Path::new(&path).join("sst/999999.sst");
+    let tmp = Path::new(&path).join("sst/tmp/major_999999_1.sst");
+    fs::write(&orphan, b"orphan").unwrap();
+    fs::write(&tmp, b"tmp").unwrap();
+    fill(&db);
+
+    let start = Instant::now();
+    while orphan.exists() {
+        assert!(start.elapsed() < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(tmp.exists());
+    assert_filled(&db);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use eikv::{DBOptions, DB};
+use std::{collections::HashMap, thread, time::Duration};
+
+#[test]
+fn test_range_delete() {
+    let path = common::db_path("range_delete");
+    let options = DBOptions {
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    for i in 0..2000 {
+        db.put(format!("key{i:04}"), format!("value{i:04}"))
+            .unwrap();
+    }
+    db.delete_range("key0500".to_owned(), "key1500".to_owned())
+        .unwrap();
+    db.put("key1000".to_owned(), "again".to_owned()).unwrap();
+
+    let check = |db: &DB<String, String>| {
+        assert_eq!(
+            Some("value0499".to_owned()),
+            db.get("key0499".to_owned()).unwrap()
+        );
+        assert_eq!(None, db.get("key0500".to_owned()).unwrap());
+        assert_eq!(None, db.get("key1499".to_owned()).unwrap());
+        assert_eq!(
+            Some("value1500".to_owned()),
+            db.get("key1500".to_owned()).unwrap()
+        );
+        assert_eq!(
+            Some("again".to_owned()),
+            db.get("key1000".to_owned()).unwrap()
+        );
+        assert_eq!(1001, db.iter().unwrap().count());
+    };
+    check(&db);
+    db.compact().unwrap();
+    check(&db);
+    drop(db);
+
+    let db: DB<String, String> = DB::new(&path, DBOptions::default()).unwrap();
+    check(&db);
+}
+
+// Once the files under a tombstone are gone and it reaches the last level,
+// nothing of the deleted range is left.
+#[test]
+fn test_range_delete_drops_files() {
+    let path = common::db_path("range_delete_drops_files");
+    let options = DBOptions {
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let cf_options = HashMap::from([("data".to_owned(), options.clone())]);
+    let db: DB<String, String> = DB::new_with_cfs(&path, options, cf_options).unwrap();
+    let data = db.cf_handle("data").unwrap();
+    for i in 0..5000 {
+        db.put_cf(&data, format!("key{i:04}"), format!("value{i:04}"))
+            .unwrap();
+    }
+    db.delete_range_cf(&data, "key".to_owned(), "kez".to_owned())
+        .unwrap();
+    // The column families share the WAL, so writes to the default one flush
+    // the tombstone.
+    for i in 0..5000 {
+        db.put(format!("key{i:04}"), format!("value{i:04}"))
+            .unwrap();
+    }
+
+    let mut sst_count = common::sst_count(&path, data.id());
+    for _ in 0..100 {
+        db.compact_cf(&data).unwrap();
+        sst_count = common::sst_count(&path, data.id());
+        if sst_count == 0 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(0, sst_count);
+    assert_eq!(0, db.iter_cf(&data).unwrap().count());
+    assert_eq!(5000, db.iter().unwrap().count());
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use eikv::{DBOptions, DB};
+use std::{fs, thread, time::Duration};
+
+fn options() -> DBOptions {
+    DBOptions {
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    }
+}
+
+// The sizes of the WALs by file name.
+fn wal_sizes(path: &str) -> Vec<(String, u64)> {
+    let mut wal_sizes: Vec<(String, u64)> = fs::read_dir(format!("{path}/wal"))
+        .unwrap()
+        .map(|entry| {
+            let entry = entry.unwrap();
+            let file_name = entry.file_name().into_string().unwrap();
+            (file_name, entry.metadata().unwrap().len())
+        })
+        .collect();
+    wal_sizes.sort();
+    wal_sizes
+}
+
+// Writes until the WAL rotates and the frozen memtables are flushed, which
+// leaves every entry in the SSTs and nothing in the WAL.
+fn fill_and_flush(db: &DB<String, String>, path: &str, prefix: &str) {
+    for i in 0.. {
+        db.put(format!("{prefix}{i:04}"), "old".to_owned()).unwrap();
+        if wal_sizes(path).last().unwrap().1 == 0 {
+            break;
+        }
+    }
+    while wal_sizes(path).len() > 1 {
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+fn check(db: &DB<String, String>, value: &str) {
+    assert_eq!(
+        Some(value.to_owned()),
+        db.get("key0001".to_owned()).unwrap()
+    );
+    let found = db
+        .iter()
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .find(|(key, _)| key == "key0001");
+    assert_eq!(Some(("key0001".to_owned(), value.to_owned())), found);
+}
+
+// The seqs carry on from the flushed entries after a reopen with an empty
+// WAL, so the newer writes still win once they reach the SSTs.
+#[test]
+fn test_overwrite_after_reopen() {
+    let path = common::db_path("overwrite_after_reopen");
+    {
+        let db: DB<String, String> = DB::new(&path, options()).unwrap();
+        fill_and_flush(&db, &path, "key");
+    }
+
+    {
+        let db: DB<String, String> = DB::new(&path, options()).unwrap();
+        db.put("key0001".to_owned(), "new".to_owned()).unwrap();
+        check(&db, "new");
+        fill_and_flush(&db, &path, "filler");
+        db.compact().unwrap();
+        check(&db, "new");
+    }
+
+    let db: DB<String, String> = DB::new(&path, options()).unwrap();
+    check(&db, "new");
+    db.put("key0001".to_owned(), "newer".to_owned()).unwrap();
+    db.compact().unwrap();
+    check(&db, "newer");
+}
+
+// The SSTs are described by the manifest, range deletions included, and only
+// their sizes are checked on open.
+#[test]
+fn test_reopen_with_range_delete() {
+    let path = common::db_path("reopen_with_range_delete");
+    {
+        let db: DB<String, String> = DB::new(&path, options()).unwrap();
+        fill_and_flush(&db, &path, "key");
+        db.delete_range("key0010".to_owned(), "key0020".to_owned())
+            .unwrap();
+        fill_and_flush(&db, &path, "filler");
+    }
+
+    for _ in 0..2 {
+        let db: DB<String, String> = DB::new(&path, options()).unwrap();
+        check(&db, "old");
+        assert_eq!(None, db.get("key0015".to_owned()).unwrap());
+        assert!(db
+            .iter()
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .all(|(key, _)| !("key0010".to_owned().."key0020".to_owned()).contains(&key)));
+    }
+
+    let sst = fs::read_dir(format!("{path}/sst"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|extension| extension == "sst"))
+        .unwrap();
+    let len = fs::metadata(&sst).unwrap().len();
+    fs::OpenOptions::new()
+        .write(true)
+        .open(&sst)
+        .unwrap()
+        .set_len(len - 1)
+        .unwrap();
+    assert!(DB::<String, String>::new(&path, options()).is_err());
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use eikv::{DBOptions, DB};
+use std::collections::BTreeMap;
+
+#[test]
+fn test_subcompactions() {
+    let path = common::db_path("subcompactions");
+    let options = DBOptions {
+        max_subcompactions: 4,
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options.clone()).unwrap();
+    let mut want = BTreeMap::new();
+    // Overlapping files, rewritten keys and a tombstone across the ranges
+    // of several subcompactions.
+    for round in 0..3 {
+        for i in 0..3000 {
+            let key = format!("key{:04}", (i * 7 + round * 13) % 3000);
+            let value = format!("value{round}_{i}");
+            db.put(key.clone(), value.clone()).unwrap();
+            want.insert(key, value);
+        }
+    }
+    db.delete_range("key0500".to_owned(), "key2500".to_owned())
+        .unwrap();
+    want.retain(|key, _| key.as_str() < "key0500" || key.as_str() >= "key2500");
+    for i in (0..3000).step_by(11) {
+        let key = format!("key{i:04}");
+        db.put(key.clone(), "last".to_owned()).unwrap();
+        want.insert(key, "last".to_owned());
+    }
+
+    db.compact().unwrap();
+    let got: BTreeMap<String, String> = db.iter().unwrap().map(|entry| entry.unwrap()).collect();
+    assert_eq!(want, got);
+    drop(db);
+
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    for (key, value) in want.iter().step_by(17) {
+        assert_eq!(Some(value.clone()), db.get(key.clone()).unwrap());
+    }
+    assert_eq!(want.len(), db.iter().unwrap().count());
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use eikv::{DBOptions, EikvError, TransactionDB, TransactionDBOptions, DB};
+use std::{sync::mpsc, thread, time::Duration};
+
+#[test]
+fn test_optimistic_transaction() {
+    let path = common::db_path("optimistic_transaction");
+    let db: DB<String, String> = DB::new(&path, DBOptions::default()).unwrap();
+    db.put("a".to_owned(), "1".to_owned()).unwrap();
+
+    let mut txn = db.begin_optimistic_transaction();
+    assert_eq!(Some("1".to_owned()), txn.get("a".to_owned()).unwrap());
+    txn.put("a".to_owned(), "2".to_owned());
+    assert_eq!(Some("2".to_owned()), txn.get("a".to_owned()).unwrap());
+    assert_eq!(Some("1".to_owned()), db.get("a".to_owned()).unwrap());
+    txn.commit().unwrap();
+    assert_eq!(Some("2".to_owned()), db.get("a".to_owned()).unwrap());
+
+    // A key written after the transaction read it fails the commit.
+    let mut txn = db.begin_optimistic_transaction();
+    txn.get("a".to_owned()).unwrap();
+    txn.put("b".to_owned(), "1".to_owned());
+    db.put("a".to_owned(), "3".to_owned()).unwrap();
+    assert!(matches!(txn.commit(), Err(EikvError::Conflict(_))));
+    assert_eq!(None, db.get("b".to_owned()).unwrap());
+}
+
+#[test]
+fn test_lock_timeout() {
+    let path = common::db_path("lock_timeout");
+    let txn_db_options = TransactionDBOptions {
+        lock_timeout: Duration::from_millis(100),
+        ..TransactionDBOptions::default()
+    };
+    let txn_db: TransactionDB<String, String> =
+        TransactionDB::new(&path, DBOptions::default(), txn_db_options).unwrap();
+
+    let mut txn1 = txn_db.begin_transaction();
+    txn1.put("a".to_owned(), "1".to_owned()).unwrap();
+    let mut txn2 = txn_db.begin_transaction();
+    assert!(matches!(
+        txn2.put("a".to_owned(), "2".to_owned()),
+        Err(EikvError::LockTimeout(_))
+    ));
+    txn1.commit().unwrap();
+    txn2.put("a".to_owned(), "2".to_owned()).unwrap();
+    txn2.commit().unwrap();
+    assert_eq!(Some("2".to_owned()), txn_db.get("a".to_owned()).unwrap());
+}
+
+#[test]
+fn test_deadlock() {
+    let path = common::db_path("deadlock");
+    let txn_db_options = TransactionDBOptions {
+        lock_timeout: Duration::from_secs(10),
+        ..TransactionDBOptions::default()
+    };
+    let txn_db: TransactionDB<String, String> =
+        TransactionDB::new(&path, DBOptions::default(), txn_db_options).unwrap();
+
+    let mut txn1 = txn_db.begin_transaction();
+    txn1.put("a".to_owned(), "1".to_owned()).unwrap();
+    thread::scope(|scope| {
+        let (locked, wait_locked) = mpsc::channel();
+        let txn_db = &txn_db;
+        let handle = scope.spawn(move || {
+            let mut txn2 = txn_db.begin_transaction();
+            txn2.put("b".to_owned(), "2".to_owned()).unwrap();
+            locked.send(()).unwrap();
+            // Waits for the first transaction to give up.
+            txn2.put("a".to_owned(), "2".to_owned()).unwrap();
+            txn2.commit().unwrap();
+        });
+        wait_locked.recv().unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert!(matches!(
+            txn1.put("b".to_owned(), "1".to_owned()),
+            Err(EikvError::Deadlock(_))
+        ));
+        txn1.rollback().unwrap();
+        handle.join().unwrap();
+    });
+    assert_eq!(Some("2".to_owned()), txn_db.get("a".to_owned()).unwrap());
+    assert_eq!(Some("2".to_owned()), txn_db.get("b".to_owned()).unwrap());
+}
+
+// Prepared transactions survive a restart, and the WALs rotated and flushed
+// meanwhile.
+#[test]
+fn test_two_phase_commit_recovery() {
+    let path = common::db_path("two_phase_commit_recovery");
+    let options = DBOptions {
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    {
+        let txn_db: TransactionDB<String, String> =
+            TransactionDB::new(&path, options.clone(), TransactionDBOptions::default()).unwrap();
+        for (name, key) in [("txn1", "x"), ("txn2", "y")] {
+            let mut txn = txn_db.begin_transaction();
+            txn.set_name(name.to_owned()).unwrap();
+            txn.put(key.to_owned(), name.to_owned()).unwrap();
+            txn.prepare().unwrap();
+        }
+        for i in 0..2000 {
+            txn_db
+                .put(format!("key{i:04}"), "value".to_owned())
+                .unwrap();
+        }
+        assert_eq!(None, txn_db.get("x".to_owned()).unwrap());
+    }
+
+    {
+        let txn_db: TransactionDB<String, String> =
+            TransactionDB::new(&path, options.clone(), TransactionDBOptions::default()).unwrap();
+        let mut txns = txn_db.recovered_transactions().unwrap();
+        txns.sort_by(|a, b| a.name().cmp(&b.name()));
+        assert_eq!(2, txns.len());
+        assert_eq!(None, txn_db.get("x".to_owned()).unwrap());
+        let txn2 = txns.pop().unwrap();
+        let txn1 = txns.pop().unwrap();
+        assert_eq!(Some("txn1"), txn1.name());
+        txn1.commit().unwrap();
+        txn2.rollback().unwrap();
+        assert_eq!(Some("txn1".to_owned()), txn_db.get("x".to_owned()).unwrap());
+        assert_eq!(None, txn_db.get("y".to_owned()).unwrap());
+    }
+
+    let txn_db: TransactionDB<String, String> =
+        TransactionDB::new(&path, options, TransactionDBOptions::default()).unwrap();
+    assert!(txn_db.recovered_transactions().unwrap().is_empty());
+    assert_eq!(Some("txn1".to_owned()), txn_db.get("x".to_owned()).unwrap());
+    assert_eq!(None, txn_db.get("y".to_owned()).unwrap());
+    assert_eq!(
+        Some("value".to_owned()),
+        txn_db.get("key1999".to_owned()).unwrap()
+    );
+}
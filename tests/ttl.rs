@@ -0,0 +1,67 @@
+mod common;
+
+use eikv::{DBOptions, DB};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn test_put_with_ttl() {
+    let path = common::db_path("put_with_ttl");
+    let db: DB<String, String> = DB::new(&path, DBOptions::default()).unwrap();
+    db.put_with_ttl(
+        "short".to_owned(),
+        "1".to_owned(),
+        Duration::from_millis(200),
+    )
+    .unwrap();
+    db.put_with_ttl("forever".to_owned(), "2".to_owned(), Duration::MAX)
+        .unwrap();
+    assert_eq!(Some("1".to_owned()), db.get("short".to_owned()).unwrap());
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(None, db.get("short".to_owned()).unwrap());
+    assert_eq!(Some("2".to_owned()), db.get("forever".to_owned()).unwrap());
+}
+
+// Expired values in the last level are dropped by rewriting their files.
+#[test]
+fn test_ttl_last_level() {
+    let path = common::db_path("ttl_last_level");
+    let options = DBOptions {
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+    for i in 0..200 {
+        db.put_with_ttl(
+            format!("key{i:03}"),
+            "v".repeat(1000),
+            Duration::from_secs(1),
+        )
+        .unwrap();
+    }
+    db.put("other".to_owned(), "v".to_owned()).unwrap();
+    // Rotates the WAL with the values into SSTs.
+    for i in 0..100 {
+        db.put(format!("other{i:03}"), "v".repeat(100)).unwrap();
+    }
+    db.compact().unwrap();
+    let size = common::sst_size(&path);
+    assert!(size > 200 * 1000);
+
+    // Not even deletions of the expired keys are left.
+    let has_expired_keys = || {
+        common::sst_key_ranges(&path, 0)
+            .iter()
+            .any(|(min_key, _)| min_key.as_slice() <= b"key199".as_slice())
+    };
+    let start = Instant::now();
+    while common::sst_size(&path) > size / 2 || has_expired_keys() {
+        assert!(start.elapsed() < Duration::from_secs(20));
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert_eq!(None, db.get("key000".to_owned()).unwrap());
+    assert_eq!(Some("v".to_owned()), db.get("other".to_owned()).unwrap());
+    assert_eq!(101, db.iter().unwrap().count());
+}
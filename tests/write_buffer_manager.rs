@@ -0,0 +1,57 @@
+mod common;
+
+use eikv::{DBOptions, WriteBufferManager, DB};
+use std::{sync::Arc, thread, time::Duration};
+
+fn put_values(db: &DB<String, String>, prefix: &str, count: usize) {
+    for i in 0..count {
+        db.put(format!("{prefix}{i:05}"), "v".repeat(100)).unwrap();
+    }
+}
+
+#[test]
+fn test_shared_write_buffer_manager() {
+    let manager = Arc::new(WriteBufferManager::new(64 * 1024));
+    let options = DBOptions {
+        write_buffer_manager: Some(manager.clone()),
+        ..DBOptions::default()
+    };
+
+    // The frozen memtables of one DB fill the buffer while it can't flush.
+    let path1 = common::db_path("write_buffer_manager1");
+    let options1 = DBOptions {
+        max_write_buffer_number: 1000,
+        wal_size_limit: 4096,
+        ..options.clone()
+    };
+    let db1: DB<String, String> = DB::new(&path1, options1).unwrap();
+    db1.pause_background_work();
+    put_values(&db1, "key", 2000);
+    assert!(manager.memory_usage() > manager.buffer_size());
+    assert!(!manager.should_flush());
+
+    // The other DB doesn't flush its small mutable memtable for them.
+    let path2 = common::db_path("write_buffer_manager2");
+    let db2: DB<String, String> = DB::new(&path2, options).unwrap();
+    put_values(&db2, "small", 100);
+    thread::sleep(Duration::from_millis(300));
+    assert_eq!(0, common::sst_count(&path2, 0));
+
+    // Past half of the buffer its mutable memtable is flushed.
+    put_values(&db2, "large", 300);
+    let mut sst_count = 0;
+    for _ in 0..50 {
+        sst_count = common::sst_count(&path2, 0);
+        if sst_count > 0 {
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    assert!(sst_count > 0);
+
+    db1.continue_background_work();
+    drop(db1);
+    drop(db2);
+    assert_eq!(0, manager.memory_usage());
+    assert_eq!(0, manager.mutable_memory_usage());
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use eikv::{DBOptions, DB};
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+#[test]
+fn test_write_stall() {
+    let path = common::db_path("write_stall");
+    let options = DBOptions {
+        max_write_buffer_number: 3,
+        wal_size_limit: 4096,
+        ..DBOptions::default()
+    };
+    let db: DB<String, String> = DB::new(&path, options).unwrap();
+
+    // Writes stop while the frozen memtables can't be flushed.
+    db.pause_background_work();
+    let finished = AtomicBool::new(false);
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            for i in 0..2000 {
+                db.put(format!("key{i:04}"), "v".repeat(100)).unwrap();
+            }
+            finished.store(true, Ordering::Release);
+        });
+        thread::sleep(Duration::from_millis(500));
+        assert!(!finished.load(Ordering::Acquire));
+        db.continue_background_work();
+    });
+
+    let stats = db.stats();
+    assert!(stats.write_slowdowns > 0);
+    assert!(stats.write_stops > 0);
+    assert!(stats.write_stop_duration >= Duration::from_millis(100));
+    assert_eq!(2000, db.iter().unwrap().count());
+}